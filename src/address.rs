@@ -1,11 +1,17 @@
 use bech32::{self, Bech32, Hrp};
 use ed25519_dalek::{SigningKey, VerifyingKey, Signer, Signature, Verifier};
 use rand::rngs::OsRng;
-use sha2::{Sha256, Digest};
+use sha2::{Sha256, Sha512, Digest};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
 const ADDRESS_HRP: &str = "mvm1";
+/// Account addresses encode the first 20 bytes of SHA-256(public key)
+const ADDRESS_PAYLOAD_LEN: usize = 20;
+
+/// BIP39 English wordlist (2048 words, one per line)
+const BIP39_WORDLIST: &str = include_str!("bip39_english.txt");
+const BIP39_PBKDF2_ROUNDS: u32 = 2048;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Address(pub String);
@@ -19,26 +25,41 @@ impl Address {
         let mut hasher = Sha256::new();
         hasher.update(public_key);
         let hash = hasher.finalize();
-        let hash_bytes = &hash[..20];
+        let hash_bytes = &hash[..ADDRESS_PAYLOAD_LEN];
         
         let hrp = Hrp::parse(ADDRESS_HRP).unwrap();
         let encoded = bech32::encode::<Bech32>(hrp, hash_bytes).unwrap();
         Address(encoded)
     }
 
+    /// Address of the contract or token (`prefix` = `mvm1contract` / `mvm1code` / `mvm1token`)
+    /// that `creator` creates with the tx at `nonce`: the prefix plus the first
+    /// 10 bytes of SHA-256(creator ‖ nonce as little-endian u64), in hex
+    pub fn derived(prefix: &str, creator: &str, nonce: u64) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(creator.as_bytes());
+        hasher.update(nonce.to_le_bytes());
+        let hash = hasher.finalize();
+        Address(format!("{}{}", prefix, hex::encode(&hash[..10])))
+    }
+
     pub fn is_valid(&self) -> bool {
         if !self.0.starts_with(ADDRESS_HRP) {
             return false;
         }
-        // Contract and token addresses use hex format, not bech32
-        if self.0.starts_with("mvm1contract") || self.0.starts_with("mvm1token") {
+        // Contract, code and token addresses use hex format, not bech32
+        if self.0.starts_with("mvm1contract") || self.0.starts_with("mvm1token") || self.0.starts_with("mvm1code") {
             return self.0.len() > 12 && self.0.chars().skip(4).all(|c| c.is_ascii_alphanumeric());
         }
         // Special addresses
         if self.0 == "mvm1faucet" {
             return true;
         }
-        bech32::decode(&self.0).is_ok()
+        // Account addresses: bech32 with our HRP over a 20-byte key hash
+        match bech32::decode(&self.0) {
+            Ok((hrp, payload)) => hrp.as_str() == ADDRESS_HRP && payload.len() == ADDRESS_PAYLOAD_LEN,
+            Err(_) => false,
+        }
     }
 
     pub fn as_str(&self) -> &str {
@@ -90,6 +111,27 @@ impl Keypair {
         Self::from_bytes(&key_bytes)
     }
 
+    /// New keypair plus the 12-word BIP39 mnemonic it is derived from
+    pub fn generate_with_mnemonic() -> (Self, String) {
+        let mut entropy = [0u8; 16];
+        rand::RngCore::fill_bytes(&mut OsRng, &mut entropy);
+        let phrase = entropy_to_mnemonic(&entropy);
+        let keypair = Self::from_mnemonic(&phrase).expect("generated mnemonic is valid");
+        (keypair, phrase)
+    }
+
+    /// Derive a keypair from a 12–24 word BIP39 mnemonic (no passphrase).
+    /// The signing key is the SLIP-0010 ed25519 master key of the BIP39 seed.
+    pub fn from_mnemonic(phrase: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let words: Vec<String> = phrase.split_whitespace().map(|w| w.to_lowercase()).collect();
+        validate_mnemonic(&words)?;
+        let seed = mnemonic_to_seed(&words.join(" "), "");
+        let master = hmac_sha512(b"ed25519 seed", &seed);
+        let mut key_bytes = [0u8; 32];
+        key_bytes.copy_from_slice(&master[..32]);
+        Self::from_bytes(&key_bytes)
+    }
+
     pub fn address(&self) -> Address {
         Address::from_public_key(self.verifying_key.as_bytes())
     }
@@ -121,12 +163,110 @@ impl Keypair {
     }
 }
 
-/// Verify a transaction signature
+fn bip39_words() -> Vec<&'static str> {
+    BIP39_WORDLIST.lines().collect()
+}
+
+/// Encode entropy as words: 11-bit groups of entropy followed by its SHA-256 checksum bits
+fn entropy_to_mnemonic(entropy: &[u8]) -> String {
+    let wordlist = bip39_words();
+    let checksum_bits = entropy.len() * 8 / 32;
+    let hash = Sha256::digest(entropy);
+    let bit = |i: usize| -> usize {
+        let byte = if i < entropy.len() * 8 { entropy[i / 8] } else { hash[(i - entropy.len() * 8) / 8] };
+        ((byte >> (7 - i % 8)) & 1) as usize
+    };
+    let total_bits = entropy.len() * 8 + checksum_bits;
+    (0..total_bits / 11)
+        .map(|w| wordlist[(0..11).fold(0, |acc, b| (acc << 1) | bit(w * 11 + b))])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Check word count, that every word is in the list, and the checksum
+fn validate_mnemonic(words: &[String]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !matches!(words.len(), 12 | 15 | 18 | 21 | 24) {
+        return Err(format!("Mnemonic must have 12, 15, 18, 21 or 24 words, got {}", words.len()).into());
+    }
+    let wordlist = bip39_words();
+    let mut bits = Vec::with_capacity(words.len() * 11);
+    for word in words {
+        let index = wordlist.binary_search(&word.as_str())
+            .map_err(|_| format!("Unknown mnemonic word: {}", word))?;
+        bits.extend((0..11).rev().map(|b| (index >> b) & 1 == 1));
+    }
+
+    let checksum_bits = bits.len() / 33;
+    let entropy: Vec<u8> = bits[..bits.len() - checksum_bits]
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0u8, |acc, &b| (acc << 1) | b as u8))
+        .collect();
+    let hash = Sha256::digest(&entropy);
+    let expected = (0..checksum_bits).map(|i| (hash[i / 8] >> (7 - i % 8)) & 1 == 1);
+    if !expected.eq(bits[bits.len() - checksum_bits..].iter().copied()) {
+        return Err("Invalid mnemonic checksum".into());
+    }
+    Ok(())
+}
+
+/// BIP39 seed: PBKDF2-HMAC-SHA512(mnemonic, "mnemonic" + passphrase, 2048 rounds)
+fn mnemonic_to_seed(phrase: &str, passphrase: &str) -> [u8; 64] {
+    let salt = format!("mnemonic{}", passphrase);
+    let mut block_salt = salt.into_bytes();
+    block_salt.extend_from_slice(&1u32.to_be_bytes());
+
+    // 64-byte output is exactly one SHA-512 block, so only block 1 is needed
+    let mut u = hmac_sha512(phrase.as_bytes(), &block_salt);
+    let mut seed = u;
+    for _ in 1..BIP39_PBKDF2_ROUNDS {
+        u = hmac_sha512(phrase.as_bytes(), &u);
+        for (s, x) in seed.iter_mut().zip(u.iter()) {
+            *s ^= x;
+        }
+    }
+    seed
+}
+
+fn hmac_sha512(key: &[u8], message: &[u8]) -> [u8; 64] {
+    const BLOCK_SIZE: usize = 128;
+    let mut key_block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        key_block[..64].copy_from_slice(&Sha512::digest(key));
+    } else {
+        key_block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha512::new();
+    inner.update(key_block.map(|b| b ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha512::new();
+    outer.update(key_block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+/// Signature scheme ids carried by transactions. Only ed25519 exists today.
+pub const SIG_SCHEME_ED25519: u8 = 0;
+
+/// Verify a transaction signature made with `sig_scheme`
 pub fn verify_tx_signature(
     from_address: &str,
     message: &[u8],
     signature_hex: &str,
     public_key_hex: &str,
+    sig_scheme: u8,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    match sig_scheme {
+        SIG_SCHEME_ED25519 => verify_ed25519(from_address, message, signature_hex, public_key_hex),
+        other => Err(format!("Unsupported signature scheme: {}", other).into()),
+    }
+}
+
+fn verify_ed25519(
+    from_address: &str,
+    message: &[u8],
+    signature_hex: &str,
+    public_key_hex: &str,
 ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
     let public_key_bytes = hex::decode(public_key_hex)?;
     if public_key_bytes.len() != 32 {
@@ -152,6 +292,7 @@ pub fn verify_tx_signature(
 }
 
 /// Hash transaction data for signing
+#[allow(clippy::too_many_arguments)]
 pub fn hash_tx_data(
     tx_type: &str,
     from: &str,
@@ -159,6 +300,10 @@ pub fn hash_tx_data(
     value: u64,
     nonce: u64,
     data: Option<&str>,
+    valid_until: i64,
+    gas_price: u64,
+    gas_limit: u64,
+    sig_scheme: u8,
 ) -> Vec<u8> {
     let mut hasher = Sha256::new();
     hasher.update(tx_type.as_bytes());
@@ -169,6 +314,20 @@ pub fn hash_tx_data(
     if let Some(d) = data {
         hasher.update(d.as_bytes());
     }
+    // Only signed when set so txs without an expiry keep their old hash
+    if valid_until != 0 {
+        hasher.update(valid_until.to_le_bytes());
+    }
+    // Likewise a non-default gas price, so a relay can't raise someone's fee
+    if gas_price != crate::chain::DEFAULT_GAS_PRICE {
+        hasher.update(gas_price.to_le_bytes());
+    }
+    if gas_limit != crate::chain::DEFAULT_GAS_LIMIT {
+        hasher.update(gas_limit.to_le_bytes());
+    }
+    if sig_scheme != SIG_SCHEME_ED25519 {
+        hasher.update([sig_scheme]);
+    }
     hasher.finalize().to_vec()
 }
 
@@ -198,4 +357,74 @@ mod tests {
         let signature = keypair.sign(message);
         assert!(keypair.verify(message, &signature));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_verify_tx_signature_dispatches_on_scheme() {
+        let keypair = Keypair::generate();
+        let from = keypair.address().to_string();
+        let message = hash_tx_data("transfer", &from, None, 1, 0, None, 0, crate::chain::DEFAULT_GAS_PRICE, crate::chain::DEFAULT_GAS_LIMIT, SIG_SCHEME_ED25519);
+        let signature = keypair.sign_hex(&message);
+        let public_key = keypair.public_key_hex();
+
+        assert!(verify_tx_signature(&from, &message, &signature, &public_key, SIG_SCHEME_ED25519).unwrap());
+        assert!(verify_tx_signature(&from, &message, &signature, &public_key, 1).is_err());
+    }
+
+    #[test]
+    fn test_mnemonic_roundtrip() {
+        let (keypair, phrase) = Keypair::generate_with_mnemonic();
+        assert_eq!(phrase.split_whitespace().count(), 12);
+        let restored = Keypair::from_mnemonic(&phrase).unwrap();
+        assert_eq!(restored.address(), keypair.address());
+    }
+
+    #[test]
+    fn test_mnemonic_bip39_vector() {
+        let entropy = [0u8; 16];
+        let phrase = entropy_to_mnemonic(&entropy);
+        assert_eq!(phrase, "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about");
+        let seed = mnemonic_to_seed(&phrase, "TREZOR");
+        assert_eq!(
+            hex::encode(seed),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+    }
+
+    #[test]
+    fn test_mnemonic_rejects_bad_checksum() {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon";
+        assert!(Keypair::from_mnemonic(phrase).is_err());
+        assert!(Keypair::from_mnemonic("not a real mnemonic").is_err());
+    }
+
+    #[test]
+    fn test_address_validation() {
+        let hrp = Hrp::parse(ADDRESS_HRP).unwrap();
+
+        let valid = Address::new(&bech32::encode::<Bech32>(hrp, &[7u8; 20]).unwrap());
+        assert!(valid.is_valid());
+
+        // Decodable bech32, but another HRP that still begins with "mvm1"
+        let wrong_hrp = Hrp::parse("mvm1x").unwrap();
+        let wrong = Address::new(&bech32::encode::<Bech32>(wrong_hrp, &[7u8; 20]).unwrap());
+        assert!(wrong.as_str().starts_with(ADDRESS_HRP));
+        assert!(!wrong.is_valid());
+
+        let short = Address::new(&bech32::encode::<Bech32>(hrp, &[7u8; 8]).unwrap());
+        assert!(!short.is_valid());
+    }
+
+    #[test]
+    fn test_derived_addresses_depend_on_creator_and_nonce() {
+        let a = Address::derived("mvm1contract", "mvm1alice", 3);
+        assert_eq!(a, Address::derived("mvm1contract", "mvm1alice", 3));
+        assert_ne!(a, Address::derived("mvm1contract", "mvm1alice", 4));
+        assert_ne!(a, Address::derived("mvm1contract", "mvm1bob", 3));
+        assert!(a.is_valid());
+        assert_eq!(a.0.len(), "mvm1contract".len() + 20);
+
+        let token = Address::derived("mvm1token", "mvm1alice", 3);
+        assert!(token.0.starts_with("mvm1token") && token.is_valid());
+        assert!(Address::derived("mvm1code", "mvm1alice", 3).is_valid());
+    }
+}
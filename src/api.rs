@@ -1,18 +1,23 @@
-use crate::chain::{Blockchain, Transaction, TxType, TxData, TxStatus, BoxError};
+use crate::chain::{Blockchain, MempoolInsert, Transaction, TxType, TxData, TxStatus, BoxError, DEFAULT_GAS_LIMIT, DEFAULT_GAS_PRICE};
 use crate::config::Config;
 use crate::state::State;
-use crate::network::{Network, StarNetwork};
-use crate::address::{Address, hash_tx_data, verify_tx_signature};
+use crate::network::{Network, StarNetwork, P2PMessage};
+use crate::address::{Address, hash_tx_data, verify_tx_signature, SIG_SCHEME_ED25519};
+use crate::ratelimit::RateLimiter;
+use crate::cache::CacheStats;
 
 use axum::{
-    extract::{Path, Query, State as AxumState, WebSocketUpgrade, ws::{WebSocket, Message}},
+    extract::{ConnectInfo, Path, Query, Request, State as AxumState, WebSocketUpgrade, ws::{WebSocket, Message}},
     http::StatusCode,
-    response::{IntoResponse, Json},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
     routing::{get, post},
     Router,
 };
 use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tower_http::cors::CorsLayer;
@@ -26,6 +31,7 @@ struct AppState {
     blockchain: Arc<RwLock<Blockchain>>,
     state: Arc<RwLock<State>>,
     network: Arc<RwLock<StarNetwork>>,
+    rate_limiter: RateLimiter,
 }
 
 pub async fn start_api_server(
@@ -39,53 +45,188 @@ pub async fn start_api_server(
         blockchain,
         state,
         network,
+        rate_limiter: RateLimiter::new(config.ratelimit.requests_per_minute, config.ratelimit.burst),
     });
 
-    let app = Router::new()
+    if config.ratelimit.enabled {
+        let cleanup_state = app_state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                cleanup_state.rate_limiter.cleanup();
+            }
+        });
+    }
+
+    let app = router(app_state);
+
+    let addr = format!("{}:{}", config.network.host, config.network.api_port);
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+    
+    Ok(())
+}
+
+/// Every route, with its middleware, over `app_state`
+fn router(app_state: SharedState) -> Router {
+    // Dry runs take the state write lock and execute contracts, so they share
+    // the same per-IP limit. They write nothing, so read-only nodes serve them.
+    let dry_runs = Router::new()
+        .route("/estimate", post(estimate_gas))
+        .route("/simulate", post(simulate_transaction))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), rate_limit));
+
+    // Faucet and tx submission are limited per client IP
+    let limited = Router::new()
+        .route("/faucet/:address", post(faucet))
+        .route("/tx", post(submit_transaction))
+        .route("/tx/batch", post(submit_batch))
+        .route("/tx/sign", post(sign_transaction))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), rate_limit))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), reject_read_only));
+
+    // Test and CI helpers, off unless `[admin] enabled`
+    let admin = Router::new()
+        .route("/produce-block", post(admin_produce_block))
+        .route("/reset-faucet/:address", post(admin_reset_faucet))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), reject_read_only))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), require_admin));
+
+    let mut app = Router::new()
         .route("/", get(index))
         .route("/status", get(get_status))
+        .route("/supply", get(get_supply))
+        .route("/gas", get(get_gas_schedule))
+        .route("/treasury", get(get_treasury))
+        .route("/debug/supply-check", get(supply_check))
+        .route("/health", get(get_health))
+        .route("/peers", get(get_peers))
+        .route("/search/:query", get(search))
         .route("/mempool", get(get_mempool))
+        .route("/mempool/:address", get(get_mempool_by_address))
         .route("/block/:height", get(get_block))
+        .route("/block/:height/txs", get(get_block_transactions))
         .route("/block/latest", get(get_latest_block))
+        .route("/block/hash/:hash", get(get_block_by_hash))
         .route("/blocks", get(get_blocks))
         .route("/tx/:hash", get(get_transaction))
+        .route("/tx/:hash/receipt", get(get_transaction_receipt))
+        .route("/tx/:hash/status", get(get_transaction_status))
         .route("/txs", get(get_recent_transactions))
         .route("/balance/:address", get(get_balance))
         .route("/nonce/:address", get(get_nonce))
         .route("/nonce/pending/:address", get(get_pending_nonce))
         .route("/account/:address", get(get_account))
+        .route("/account/:address/export", get(export_account))
+        .route("/name/:name", get(resolve_name))
+        .route("/resolve/:address", get(reverse_resolve))
         .route("/txs/:address", get(get_address_transactions))
-        .route("/faucet/:address", post(faucet))
-        .route("/tx", post(submit_transaction))
-        .route("/tx/sign", post(sign_transaction))
+        .merge(limited)
+        .merge(dry_runs)
         .route("/tokens", get(get_tokens))
         .route("/tokens/creator/:address", get(get_tokens_by_creator))
         .route("/tokens/holder/:address", get(get_token_holdings))
         .route("/token/:address", get(get_token))
         .route("/token/:contract/balance/:address", get(get_token_balance))
         .route("/token/:contract/holders", get(get_token_holders))
+        .route("/token/:contract/allowance/:owner/:spender", get(get_token_allowance))
         .route("/contracts", get(get_contracts))
         .route("/contracts/creator/:address", get(get_contracts_by_creator))
         .route("/contract/:address", get(get_contract))
         .route("/contract/:address/mbi", get(get_contract_mbi))
+        .route("/contract/:address/verify", post(verify_contract_source)
+            .route_layer(middleware::from_fn_with_state(app_state.clone(), reject_read_only)))
+        .route("/contract/:address/state", get(get_contract_state))
         .route("/contract/:address/var/:name", get(read_contract_var))
         .route("/contract/:address/mapping/:name", get(get_contract_mapping))
         .route("/contract/:address/mapping/:name/:key", get(read_contract_mapping))
+        .route("/contract/:address/mapping/:name/:key/:key2", get(read_contract_mapping2))
         .route("/contract/:address/call/:method", get(call_contract_view))
         .route("/contract/:address/events", get(get_contract_events))
+        .route("/contract/:address/txs", get(get_contract_transactions))
         .route("/leaderboard", get(get_leaderboard))
         .route("/wallet/new", get(create_wallet))
-        .route("/ws", get(ws_handler))
-        .route("/p2p", get(p2p_handler))
-        .layer(CorsLayer::permissive())
-        .with_state(app_state);
+        .route("/wallet/import", post(import_wallet))
+        .nest("/admin", admin)
+        .route("/ws", get(ws_handler));
 
-    let addr = format!("{}:{}", config.network.host, config.network.api_port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    
-    axum::serve(listener, app).await?;
-    
-    Ok(())
+    // A read-only replica doesn't join the network, so it takes no peers
+    if !app_state.config.node.read_only {
+        app = app.route("/p2p", get(p2p_handler));
+    }
+
+    app.layer(CorsLayer::permissive())
+        .with_state(app_state)
+}
+
+async fn rate_limit(
+    AxumState(state): AxumState<SharedState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if state.config.ratelimit.enabled {
+        if let Err(retry_after) = state.rate_limiter.check(addr.ip()) {
+            return (StatusCode::TOO_MANY_REQUESTS, Json(serde_json::json!({
+                "success": false,
+                "error": "rate_limited",
+                "message": format!("Too many requests, retry in {}s", retry_after),
+                "retry_after": retry_after
+            }))).into_response();
+        }
+    }
+    next.run(req).await
+}
+
+/// Gate `/admin/*`: 404 while `[admin]` is disabled, 401 without the bearer token
+async fn require_admin(
+    AxumState(state): AxumState<SharedState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let admin = &state.config.admin;
+    if !admin.enabled {
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "admin_disabled",
+            "message": "Admin endpoints are disabled; set [admin] enabled = true"
+        }))).into_response();
+    }
+    let presented = req.headers().get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or("");
+    if !tokens_match(presented, &admin.token) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({
+            "success": false,
+            "error": "unauthorized",
+            "message": "Missing or invalid admin token"
+        }))).into_response();
+    }
+    next.run(req).await
+}
+
+/// Compare in time independent of where the strings first differ
+fn tokens_match(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Refuse writes on a `node.read_only` replica
+async fn reject_read_only(
+    AxumState(state): AxumState<SharedState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if state.config.node.read_only {
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
+            "success": false,
+            "error": "read_only_mode",
+            "message": "This node is a read-only replica; send writes to the master"
+        }))).into_response();
+    }
+    next.run(req).await
 }
 
 async fn index() -> impl IntoResponse {
@@ -96,50 +237,87 @@ async fn index() -> impl IntoResponse {
         "endpoints": {
             "chain": {
                 "status": "GET /status",
+                "supply": "GET /supply",
+                "gas": "GET /gas",
+                "treasury": "GET /treasury",
+                "supply_check": "GET /debug/supply-check",
+                "health": "GET /health",
+                "peers": "GET /peers",
+                "search": "GET /search/:query",
                 "mempool": "GET /mempool",
+                "mempool_address": "GET /mempool/:address",
                 "blocks": "GET /blocks?limit=10",
                 "block": "GET /block/:height",
+                "block_txs": "GET /block/:height/txs?limit=50&offset=0",
                 "latest": "GET /block/latest",
-                "txs": "GET /txs?limit=20",
-                "tx": "GET /tx/:hash"
+                "block_by_hash": "GET /block/hash/:hash",
+                "txs": "GET /txs?limit=20&type=transfer&from_height=N&to_height=N&cursor=SEQ",
+                "tx": "GET /tx/:hash",
+                "receipt": "GET /tx/:hash/receipt",
+                "tx_status": "GET /tx/:hash/status"
             },
             "accounts": {
-                "balance": "GET /balance/:address",
+                "balance": "GET /balance/:address?height=N",
                 "nonce": "GET /nonce/:address",
                 "pending_nonce": "GET /nonce/pending/:address",
                 "account": "GET /account/:address",
+                "account_export": "GET /account/:address/export",
+                "name": "GET /name/:name",
+                "resolve": "GET /resolve/:address",
                 "txs": "GET /txs/:address",
                 "wallet": "GET /wallet/new",
+                "wallet_import": "POST /wallet/import",
                 "faucet": "POST /faucet/:address"
             },
             "tokens": {
-                "all": "GET /tokens",
+                "all": "GET /tokens?limit=50&offset=0&after=ADDR",
                 "by_creator": "GET /tokens/creator/:address",
                 "by_holder": "GET /tokens/holder/:address",
                 "info": "GET /token/:address",
-                "balance": "GET /token/:contract/balance/:address"
+                "balance": "GET /token/:contract/balance/:address",
+                "holders": "GET /token/:contract/holders?limit=50",
+                "allowance": "GET /token/:contract/allowance/:owner/:spender"
             },
             "contracts_read_FREE": {
-                "all": "GET /contracts",
+                "all": "GET /contracts?limit=50&offset=0&after=ADDR",
                 "by_creator": "GET /contracts/creator/:address",
                 "info": "GET /contract/:address",
                 "mbi": "GET /contract/:address/mbi",
+                "verify": "POST /contract/:address/verify",
+                "state": "GET /contract/:address/state?mappings=false",
                 "var": "GET /contract/:address/var/:name",
                 "mapping_all": "GET /contract/:address/mapping/:name",
                 "mapping_key": "GET /contract/:address/mapping/:name/:key",
-                "call_view": "GET /contract/:address/call/:method?args=a,b,c"
+                "mapping_nested_key": "GET /contract/:address/mapping/:name/:key/:key2",
+                "call_view": "GET /contract/:address/call/:method?args=a,b,c&from=:caller",
+                "call_simulate": "GET /contract/:address/call/:method?args=a,b,c&from=:caller&simulate=true&amount=0&gas_limit=100000",
+                "txs": "GET /contract/:address/txs?limit=50"
             },
             "transactions_write": {
                 "sign": "POST /tx/sign",
-                "submit": "POST /tx"
+                "submit": "POST /tx",
+                "submit_batch": "POST /tx/batch",
+                "estimate": "POST /estimate",
+                "simulate": "POST /simulate"
+            },
+            "admin": {
+                "produce_block": "POST /admin/produce-block",
+                "reset_faucet": "POST /admin/reset-faucet/:address"
             }
         },
-        "tx_types": ["transfer", "create_token", "transfer_token", "deploy_contract", "call_contract"],
+        "tx_types": ["transfer", "create_token", "transfer_token", "update_token_metadata", "register_name", "deploy_contract", "call_contract"],
+        "amounts": {
+            "value": "whole MVM (1 = 100000000 base units)",
+            "value_decimal": "MVM as a decimal string, e.g. \"0.5\"; instead of value",
+            "data.amount": "transfer_token: token base units",
+            "data.amount_decimal": "transfer_token: whole tokens as a decimal string, scaled by the token's decimals; instead of amount",
+            "*_raw": "response fields in base units"
+        },
         "mosh": {
             "types": ["uint64", "u256", "u64", "u8", "string", "bool", "address"],
             "mappings": "map name: keyType => valType",
             "modifiers": ["view/pub (FREE)", "write/mut", "payable/vault", "onlyOwner/seal"],
-            "operations": ["set", "add", "sub", "mul", "div", "mod", "map_set", "map_add", "map_sub", "map_mul", "map_div", "map_mod", "require/guard", "emit/signal", "if", "transfer", "return", "let"],
+            "operations": ["set", "add", "sub", "mul", "div", "mod", "map_set", "map_add", "map_sub", "map_mul", "map_div", "map_mod", "map_del", "del", "require/guard", "emit/signal", "if", "transfer", "transfer_native", "return", "let"],
             "keywords": {
                 "forge": "contract definition",
                 "fn": "function definition",
@@ -152,7 +330,7 @@ async fn index() -> impl IntoResponse {
                 "pub": "view/read-only",
                 "mut": "state-mutating"
             },
-            "special_values": ["msg.sender", "msg.value", "block.height", "block.timestamp", "mosh.balance", "mosh.height", "mosh.time"]
+            "special_values": ["msg.sender", "msg.value", "msg.amount", "block.height", "block.timestamp", "mosh.balance", "mosh.height", "mosh.time"]
         }
     }))
 }
@@ -164,17 +342,281 @@ struct StatusResponse {
     height: u64,
     total_supply: String,
     pending_transactions: usize,
+    mempool_size: usize,
     peers: usize,
     browsers: usize,
     node_type: String,
+    block_time: u64,
+    latest_block_timestamp: i64,
+    seconds_since_last_block: i64,
+    account_cache: CacheStats,
 }
 
 async fn get_status(
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
+    Json(build_status(&state).await)
+}
+
+/// Total vs circulating supply and emission, for trackers
+async fn get_supply(
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let master = state.blockchain.read().await.master_address.to_string();
+    let mut excluded: Vec<String> = vec![master];
+    for addr in &state.config.supply.excluded_addresses {
+        if !excluded.contains(addr) {
+            excluded.push(addr.clone());
+        }
+    }
+
+    let state_guard = state.state.read().await;
+    let total_supply = state_guard.get_total_supply().unwrap_or(0);
+    let excluded_balances: Vec<(String, u64)> = excluded.into_iter()
+        .map(|a| { let bal = state_guard.get_balance(&a).unwrap_or(0); (a, bal) })
+        .collect();
+    drop(state_guard);
+
+    let excluded_total = excluded_balances.iter().fold(0u64, |sum, (_, b)| sum.saturating_add(*b));
+    let circulating = total_supply.saturating_sub(excluded_total);
+
+    let block_reward = state.config.rewards.block_reward * 100_000_000;
+    let blocks_per_day = 86_400 / state.config.block.block_time.max(1);
+    let daily_emission = blocks_per_day.saturating_mul(block_reward);
+
+    Json(serde_json::json!({
+        "success": true,
+        "total_supply": format_balance(total_supply),
+        "total_supply_raw": total_supply,
+        "circulating_supply": format_balance(circulating),
+        "circulating_supply_raw": circulating,
+        "excluded": excluded_balances.iter().map(|(a, b)| serde_json::json!({
+            "address": a,
+            "balance": format_balance(*b),
+            "balance_raw": b
+        })).collect::<Vec<_>>(),
+        "block_reward": format_balance(block_reward),
+        "block_reward_raw": block_reward,
+        "block_time": state.config.block.block_time,
+        "blocks_per_day": blocks_per_day,
+        "daily_emission": format_balance(daily_emission),
+        "daily_emission_raw": daily_emission
+    }))
+}
+
+/// Treasury address and the creation/deploy fees it has collected. Without a
+/// configured address the fees are burned and this reports nothing collected.
+async fn get_treasury(
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let address = state.config.treasury.address.clone();
+    let state_guard = state.state.read().await;
+    let balance = match address {
+        Some(ref a) => state_guard.get_balance(a).unwrap_or(0),
+        None => 0,
+    };
+    let fees_collected = state_guard.get_treasury_fees().unwrap_or(0);
+    drop(state_guard);
+
+    Json(serde_json::json!({
+        "success": true,
+        "address": address,
+        "burns_fees": address.is_none(),
+        "balance": format_balance(balance),
+        "balance_raw": balance,
+        "fees_collected": format_balance(fees_collected),
+        "fees_collected_raw": fees_collected,
+        "creation_fee": state.config.token.creation_fee,
+        "deploy_fee": state.config.contract.deploy_fee,
+        "name_registration_fee": state.config.names.registration_fee
+    }))
+}
+
+/// Compare the stored total supply with the sum of all balances. Off unless
+/// `[supply] debug_check` is set, since it reads every account.
+async fn supply_check(
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    if !state.config.supply.debug_check {
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "debug_disabled",
+            "message": "Set [supply] debug_check = true to enable /debug/supply-check"
+        }))).into_response();
+    }
+
+    let state_guard = state.state.read().await;
+    let height = state_guard.get_height().unwrap_or(0);
+    let stored = state_guard.get_total_supply().unwrap_or(0);
+    let recomputed = match state_guard.recompute_total_supply() {
+        Ok(total) => total,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    };
+    drop(state_guard);
+
+    let discrepancy = stored as i128 - recomputed as i128;
+    Json(serde_json::json!({
+        "success": true,
+        "height": height,
+        "stored_total_supply": format_balance(stored),
+        "stored_total_supply_raw": stored,
+        "recomputed_total_supply": format_balance(recomputed),
+        "recomputed_total_supply_raw": recomputed,
+        "discrepancy_raw": discrepancy,
+        "consistent": discrepancy == 0
+    })).into_response()
+}
+
+/// Fee schedule: base gas per tx type, the MVM's per-step gas and the gas
+/// price policy, read from the same constants the executor charges
+async fn get_gas_schedule(
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let base_gas: serde_json::Map<String, serde_json::Value> = TxType::ALL.iter()
+        .map(|t| (t.as_str().to_string(), serde_json::json!(t.base_gas())))
+        .collect();
+    let block = &state.config.block;
+    Json(serde_json::json!({
+        "success": true,
+        "base_gas": base_gas,
+        "mvm": crate::mvm::GAS_SCHEDULE,
+        "min_gas_price": block.min_gas_price,
+        "default_gas_price": DEFAULT_GAS_PRICE,
+        "default_gas_limit": DEFAULT_GAS_LIMIT,
+        "block_gas_limit": block.gas_limit,
+        "protocol_fees": {
+            "create_token": format_balance(state.config.token.creation_fee * 100_000_000),
+            "create_token_raw": state.config.token.creation_fee * 100_000_000,
+            "deploy_contract": format_balance(state.config.contract.deploy_fee * 100_000_000),
+            "deploy_contract_raw": state.config.contract.deploy_fee * 100_000_000,
+            "register_name": format_balance(state.config.names.registration_fee * 100_000_000),
+            "register_name_raw": state.config.names.registration_fee * 100_000_000
+        }
+    }))
+}
+
+/// Liveness probe: 200 while the DB answers and (on the master) blocks keep
+/// coming, 503 otherwise
+async fn get_health(
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let height = match state.state.read().await.get_height() {
+        Ok(h) => h,
+        Err(e) => return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
+            "success": false,
+            "status": "unavailable",
+            "error": "db_unavailable",
+            "message": e.to_string()
+        }))).into_response(),
+    };
+
+    if state.config.node.node_type == "master" {
+        let block = &state.config.block;
+        let last_activity = {
+            let blockchain = state.blockchain.read().await;
+            // Skipped intervals are expected when empty blocks are disabled
+            if block.produce_when_empty { blockchain.last_block_produced_at } else { blockchain.last_tick_at }
+        };
+        let age = Utc::now().timestamp() - last_activity;
+        if age > (2 * block.block_time) as i64 {
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
+                "success": false,
+                "status": "stale",
+                "error": "stale",
+                "message": format!("No block activity for {}s (block_time {}s)", age, block.block_time),
+                "height": height
+            }))).into_response();
+        }
+    }
+
+    Json(serde_json::json!({
+        "success": true,
+        "status": "ok",
+        "height": height
+    })).into_response()
+}
+
+/// Resolve a free-form explorer query to a block, tx, token, contract or account
+async fn search(
+    Path(query): Path<String>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let q = query.trim();
+    let state_guard = state.state.read().await;
+
+    let found = (|| -> Result<Option<(&str, String, serde_json::Value)>, BoxError> {
+        if let Ok(height) = q.parse::<u64>() {
+            return Ok(state_guard.get_block(height)?
+                .map(|b| ("block", format!("/block/{}", height), serde_json::json!(b))));
+        }
+        if q.len() == 64 && q.chars().all(|c| c.is_ascii_hexdigit()) {
+            if let Some(b) = state_guard.get_block_by_hash(q)? {
+                return Ok(Some(("block", format!("/block/hash/{}", q), serde_json::json!(b))));
+            }
+            return Ok(state_guard.get_transaction(q)?
+                .map(|tx| ("transaction", format!("/tx/{}", q), serde_json::json!(tx))));
+        }
+        if q.starts_with("mvm1token") {
+            return Ok(state_guard.get_token(q)?
+                .map(|t| ("token", format!("/token/{}", q), serde_json::json!(t))));
+        }
+        if q.starts_with("mvm1contract") {
+            return Ok(state_guard.get_mosh_contract(q)?.map(|c| ("contract", format!("/contract/{}", q), serde_json::json!({
+                "address": c.address,
+                "name": c.name,
+                "creator": c.creator,
+                "owner": c.owner,
+                "token": c.token,
+                "created_at": c.created_at,
+                "created_block": c.created_block
+            }))));
+        }
+        if Address::new(q).is_valid() {
+            let balance = state_guard.get_balance(q)?;
+            return Ok(Some(("account", format!("/account/{}", q), serde_json::json!({
+                "address": q,
+                "balance": format_balance(balance),
+                "balance_raw": balance,
+                "nonce": state_guard.get_nonce(q)?
+            }))));
+        }
+        Ok(None)
+    })();
+
+    match found {
+        Ok(Some((kind, path, result))) => Json(serde_json::json!({
+            "success": true,
+            "query": q,
+            "type": kind,
+            "path": path,
+            "result": result
+        })).into_response(),
+        Ok(None) => Json(serde_json::json!({
+            "success": true,
+            "query": q,
+            "type": "not_found",
+            "result": null
+        })).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    }
+}
+
+async fn build_status(state: &AppState) -> StatusResponse {
     let state_guard = state.state.read().await;
     let height = state_guard.get_height().unwrap_or(0);
     let total_supply = state_guard.get_total_supply().unwrap_or(0);
+    let latest_block_timestamp = state_guard.get_block(height).ok().flatten()
+        .map(|b| b.timestamp)
+        .unwrap_or(0);
+    let account_cache = state_guard.account_cache_stats();
     drop(state_guard);
 
     let blockchain = state.blockchain.read().await;
@@ -186,16 +628,44 @@ async fn get_status(
     let browsers = network.browser_count();
     drop(network);
 
-    Json(StatusResponse {
+    StatusResponse {
         chain_id: state.config.chain.chain_id.clone(),
         chain_name: state.config.chain.chain_name.clone(),
         height,
         total_supply: format_balance(total_supply),
         pending_transactions: pending,
+        mempool_size: pending,
         peers,
         browsers,
         node_type: state.config.node.node_type.clone(),
-    })
+        block_time: state.config.block.block_time,
+        latest_block_timestamp,
+        seconds_since_last_block: (Utc::now().timestamp() - latest_block_timestamp).max(0),
+        account_cache,
+    }
+}
+
+async fn get_peers(
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let network = state.network.read().await;
+    let peers = network.list_peers().await;
+    let peer_browsers = network.peer_browser_counts().await;
+    let browsers = network.browser_count();
+    drop(network);
+
+    Json(serde_json::json!({
+        "success": true,
+        "node_id": state.config.node.id,
+        "node_type": state.config.node.node_type,
+        "peer_count": peers.len(),
+        "browser_count": browsers,
+        "peers": peers.iter().map(|(node_id, node_type)| serde_json::json!({
+            "node_id": node_id,
+            "node_type": node_type,
+            "browsers": peer_browsers.get(node_id)
+        })).collect::<Vec<_>>()
+    }))
 }
 
 // ===== MEMPOOL =====
@@ -204,7 +674,7 @@ async fn get_mempool(
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
     let blockchain = state.blockchain.read().await;
-    let pending = blockchain.mempool.get_pending(100);
+    let pending = blockchain.mempool().get_pending(100);
     let count = blockchain.pending_count();
     drop(blockchain);
     
@@ -227,6 +697,43 @@ async fn get_mempool(
     }))
 }
 
+async fn get_mempool_by_address(
+    Path(address): Path<String>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let addr = Address::new(&address);
+    if !addr.is_valid() {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_address",
+            "message": format!("Invalid address format: {}", address)
+        }))).into_response();
+    }
+
+    let blockchain = state.blockchain.read().await;
+    let pending = blockchain.get_pending_txs(&address);
+    drop(blockchain);
+
+    let txs: Vec<serde_json::Value> = pending.iter().map(|tx| {
+        serde_json::json!({
+            "hash": tx.hash,
+            "from": tx.from,
+            "to": tx.to,
+            "value": tx.value,
+            "nonce": tx.nonce,
+            "tx_type": tx.tx_type,
+            "timestamp": tx.timestamp,
+        })
+    }).collect();
+
+    Json(serde_json::json!({
+        "success": true,
+        "address": address,
+        "count": txs.len(),
+        "transactions": txs
+    })).into_response()
+}
+
 async fn get_pending_nonce(
     Path(address): Path<String>,
     AxumState(state): AxumState<SharedState>,
@@ -246,13 +753,38 @@ async fn get_pending_nonce(
     }
 }
 
+#[derive(Deserialize)]
+struct BlockQuery {
+    /// Set to false to get tx hashes instead of full transactions
+    #[serde(default = "default_include_txs")]
+    include_txs: bool,
+}
+
+fn default_include_txs() -> bool {
+    true
+}
+
+/// A block as JSON; without `include_txs`, `tx_hashes` replaces `transactions`
+fn block_json(block: &crate::chain::Block, include_txs: bool) -> serde_json::Value {
+    let mut json = serde_json::json!(block);
+    if !include_txs {
+        let hashes: Vec<&str> = block.transactions.iter().map(|tx| tx.hash.as_str()).collect();
+        if let Some(obj) = json.as_object_mut() {
+            obj.remove("transactions");
+            obj.insert("tx_hashes".to_string(), serde_json::json!(hashes));
+        }
+    }
+    json
+}
+
 async fn get_block(
     Path(height): Path<u64>,
+    Query(query): Query<BlockQuery>,
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
     let state_guard = state.state.read().await;
     match state_guard.get_block(height) {
-        Ok(Some(block)) => Json(serde_json::json!({ "success": true, "block": block })).into_response(),
+        Ok(Some(block)) => Json(serde_json::json!({ "success": true, "block": block_json(&block, query.include_txs) })).into_response(),
         Ok(None) => (StatusCode::NOT_FOUND, Json(serde_json::json!({ 
             "success": false, 
             "error": "block_not_found",
@@ -266,17 +798,61 @@ async fn get_block(
     }
 }
 
-async fn get_latest_block(
+/// A block's transactions in order with their fees, `?limit=&offset=` paged
+async fn get_block_transactions(
+    Path(height): Path<u64>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
+    let (limit, offset, _) = page_params(&params);
     let state_guard = state.state.read().await;
-    let height = state_guard.get_height().unwrap_or(0);
-    match state_guard.get_block(height) {
-        Ok(Some(block)) => Json(serde_json::json!({ "success": true, "block": block })).into_response(),
-        Ok(None) => (StatusCode::NOT_FOUND, Json(serde_json::json!({ 
+    let tip = state_guard.get_height().unwrap_or(0);
+    let pruned = state_guard.get_pruned_height().unwrap_or(0);
+    // Genesis survives pruning
+    if height > tip || (height != 0 && height <= pruned) {
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({
             "success": false,
             "error": "block_not_found",
-            "message": "Latest block not found"
+            "message": format!("Block {} not found", height)
+        }))).into_response();
+    }
+
+    let hashes = match state_guard.get_block_tx_hashes(height) {
+        Ok(hashes) => hashes,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    };
+    let transactions: Vec<serde_json::Value> = hashes.iter().skip(offset).take(limit)
+        .filter_map(|hash| state_guard.get_transaction(hash).ok().flatten())
+        .map(|tx| tx_with_fee_json(&tx, Some(height)))
+        .collect();
+
+    Json(serde_json::json!({
+        "success": true,
+        "height": height,
+        "total": hashes.len(),
+        "count": transactions.len(),
+        "limit": limit,
+        "offset": offset,
+        "transactions": transactions
+    })).into_response()
+}
+
+async fn get_block_by_hash(
+    Path(hash): Path<String>,
+    Query(query): Query<BlockQuery>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let state_guard = state.state.read().await;
+    match state_guard.get_block_by_hash(&hash) {
+        Ok(Some(block)) => Json(serde_json::json!({ "success": true, "block": block_json(&block, query.include_txs) })).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, Json(serde_json::json!({ 
+            "success": false, 
+            "error": "block_not_found",
+            "message": format!("Block with hash {} not found", hash)
         }))).into_response(),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ 
             "success": false,
@@ -286,15 +862,38 @@ async fn get_latest_block(
     }
 }
 
-async fn get_transaction(
-    Path(hash): Path<String>,
+async fn get_latest_block(
+    Query(query): Query<BlockQuery>,
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
     let state_guard = state.state.read().await;
-    match state_guard.get_transaction(&hash) {
+    let height = state_guard.get_height().unwrap_or(0);
+    match state_guard.get_block(height) {
+        Ok(Some(block)) => Json(serde_json::json!({ "success": true, "block": block_json(&block, query.include_txs) })).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, Json(serde_json::json!({ 
+            "success": false,
+            "error": "block_not_found",
+            "message": "Latest block not found"
+        }))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ 
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string() 
+        }))).into_response(),
+    }
+}
+
+async fn get_transaction(
+    Path(hash): Path<String>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let state_guard = state.state.read().await;
+    match state_guard.get_transaction(&hash) {
         Ok(Some(tx)) => {
             let fee_paid = tx.gas_used * tx.gas_price;
             let block_height = state_guard.get_transaction_block_height(&hash).unwrap_or(None);
+            let confirmations = confirmations_for(&state_guard, block_height);
+            let events = state_guard.get_tx_events(&hash).unwrap_or_default();
             Json(serde_json::json!({
                 "success": true,
                 "transaction": {
@@ -316,7 +915,107 @@ async fn get_transaction(
                     "public_key": tx.public_key,
                     "status": tx.status,
                     "error": tx.error,
-                    "block_height": block_height
+                    "result": tx.result,
+                    "from_delta": tx.from_delta,
+                    "to_delta": tx.to_delta,
+                    "valid_until": tx.valid_until,
+                    "block_height": block_height,
+                    "confirmations": confirmations,
+                    "final": is_final(&state.config, confirmations),
+                    "events": events
+                }
+            })).into_response()
+        },
+        Ok(None) => (StatusCode::NOT_FOUND, Json(serde_json::json!({ 
+            "success": false,
+            "error": "tx_not_found",
+            "message": format!("Transaction {} not found", hash)
+        }))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ 
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string() 
+        }))).into_response(),
+    }
+}
+
+/// Blocks built on top of the one containing the tx (0 while it is the tip)
+fn confirmations_for(state_guard: &State, block_height: Option<u64>) -> Option<u64> {
+    let height = state_guard.get_height().unwrap_or(0);
+    block_height.map(|h| height.saturating_sub(h))
+}
+
+/// Whether a tx with `confirmations` has reached `block.confirmations_for_final`
+fn is_final(config: &Config, confirmations: Option<u64>) -> bool {
+    confirmations.is_some_and(|c| c >= config.block.confirmations_for_final)
+}
+
+/// Where a tx stands: `pending` in the mempool, `included` in a block, or
+/// `final` once it has enough confirmations
+async fn get_transaction_status(
+    Path(hash): Path<String>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let (block_height, confirmations) = {
+        let state_guard = state.state.read().await;
+        let block_height = state_guard.get_transaction_block_height(&hash).unwrap_or(None);
+        (block_height, confirmations_for(&state_guard, block_height))
+    };
+
+    let status = if block_height.is_some() {
+        if is_final(&state.config, confirmations) { "final" } else { "included" }
+    } else if state.blockchain.read().await.is_pending(&hash) {
+        "pending"
+    } else {
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "tx_not_found",
+            "message": format!("Transaction {} not found", hash)
+        }))).into_response();
+    };
+
+    Json(serde_json::json!({
+        "success": true,
+        "hash": hash,
+        "status": status,
+        "block_height": block_height,
+        "confirmations": confirmations,
+        "confirmations_for_final": state.config.block.confirmations_for_final
+    })).into_response()
+}
+
+async fn get_transaction_receipt(
+    Path(hash): Path<String>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let state_guard = state.state.read().await;
+    match state_guard.get_transaction(&hash) {
+        Ok(Some(tx)) => {
+            let fee_paid = tx.gas_used * tx.gas_price;
+            let block_height = state_guard.get_transaction_block_height(&hash).unwrap_or(None);
+            let block_hash = block_height
+                .and_then(|h| state_guard.get_block(h).ok().flatten())
+                .map(|b| b.hash);
+            let confirmations = confirmations_for(&state_guard, block_height);
+            let events = state_guard.get_tx_events(&hash).unwrap_or_default();
+            Json(serde_json::json!({
+                "success": true,
+                "receipt": {
+                    "tx_hash": tx.hash,
+                    "status": tx.status,
+                    "error": tx.error,
+                    "block_height": block_height,
+                    "block_hash": block_hash,
+                    "confirmations": confirmations,
+                    "final": is_final(&state.config, confirmations),
+                    "from": tx.from,
+                    "to": tx.to,
+                    "gas_used": tx.gas_used,
+                    "gas_price": tx.gas_price,
+                    "fee_paid": format_balance(fee_paid),
+                    "fee_paid_raw": fee_paid,
+                    "result": tx.result,
+                    "events": events
                 }
             })).into_response()
         },
@@ -335,6 +1034,7 @@ async fn get_transaction(
 
 async fn get_balance(
     Path(address): Path<String>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
     // Validate address
@@ -348,6 +1048,34 @@ async fn get_balance(
     }
 
     let state_guard = state.state.read().await;
+
+    // ?height=N answers from the balance history instead
+    if let Some(raw) = params.get("height") {
+        let tip = state_guard.get_height().unwrap_or(0);
+        let height = match raw.parse::<u64>() {
+            Ok(h) if h <= tip => h,
+            _ => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "success": false,
+                "error": "invalid_height",
+                "message": format!("height must be a block number between 0 and {}", tip)
+            }))).into_response(),
+        };
+        return match state_guard.get_balance_at(&address, height) {
+            Ok(balance) => Json(serde_json::json!({
+                "success": true,
+                "address": address,
+                "height": height,
+                "balance": format_balance(balance),
+                "balance_raw": balance
+            })).into_response(),
+            Err(e) => (StatusCode::NOT_FOUND, Json(serde_json::json!({
+                "success": false,
+                "error": "history_unavailable",
+                "message": e.to_string()
+            }))).into_response(),
+        };
+    }
+
     let balance = state_guard.get_balance(&address).unwrap_or(0);
     
     Json(serde_json::json!({
@@ -371,13 +1099,16 @@ async fn get_nonce(
         }))).into_response();
     }
 
-    let state_guard = state.state.read().await;
-    let nonce = state_guard.get_nonce(&address).unwrap_or(0);
+    let blockchain = state.blockchain.read().await;
+    let confirmed_nonce = blockchain.get_nonce(&address).await.unwrap_or(0);
+    let pending_nonce = blockchain.mempool().get_pending_nonce(&address, confirmed_nonce);
     
     Json(serde_json::json!({
         "success": true,
         "address": address,
-        "nonce": nonce
+        "nonce": pending_nonce,
+        "confirmed_nonce": confirmed_nonce,
+        "pending_nonce": pending_nonce
     })).into_response()
 }
 
@@ -400,6 +1131,7 @@ async fn get_account(
     let tokens_created = state_guard.get_tokens_by_creator(&address).unwrap_or_default();
     let token_holdings = state_guard.get_token_holdings(&address).unwrap_or_default();
     let recent_txs = state_guard.get_transactions_by_address(&address, 20).unwrap_or_default();
+    let name = state_guard.get_primary_name(&address).unwrap_or(None);
     
     // Calculate total fees paid
     let total_fees_paid: u64 = recent_txs.iter()
@@ -408,31 +1140,14 @@ async fn get_account(
         .sum();
     
     let txs_with_fees: Vec<serde_json::Value> = recent_txs.iter().map(|tx| {
-        let fee_paid = tx.gas_used * tx.gas_price;
-        let block_height = state_guard.get_transaction_block_height(&tx.hash).unwrap_or(None);
-        serde_json::json!({
-            "hash": tx.hash,
-            "tx_type": tx.tx_type.as_str(),
-            "from": tx.from,
-            "to": tx.to,
-            "value": format_balance(tx.value),
-            "value_raw": tx.value,
-            "gas_used": tx.gas_used,
-            "fee_paid": format_balance(fee_paid),
-            "fee_paid_raw": fee_paid,
-            "nonce": tx.nonce,
-            "timestamp": tx.timestamp,
-            "status": tx.status,
-            "error": tx.error,
-            "block_height": block_height,
-            "data": tx.data
-        })
+        tx_with_fee_json(tx, state_guard.get_transaction_block_height(&tx.hash).unwrap_or(None))
     }).collect();
 
     Json(serde_json::json!({
         "success": true,
         "account": {
             "address": address,
+            "name": name,
             "balance": format_balance(balance),
             "balance_raw": balance,
             "nonce": nonce,
@@ -447,13 +1162,148 @@ async fn get_account(
             "contract": h.contract,
             "name": h.name,
             "symbol": h.symbol,
-            "balance": format_balance(h.balance),
+            "balance": crate::standards::format_amount(h.balance, h.decimals),
             "balance_raw": h.balance
         })).collect::<Vec<_>>(),
         "recent_transactions": txs_with_fees
     })).into_response()
 }
 
+/// Address a registered name points to
+async fn resolve_name(
+    Path(name): Path<String>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    if let Err(message) = crate::names::validate_name(&name) {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_name",
+            "message": message
+        }))).into_response();
+    }
+
+    match state.state.read().await.resolve_name(&name) {
+        Ok(Some(address)) => Json(serde_json::json!({
+            "success": true,
+            "name": name,
+            "address": address
+        })).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "name_not_found",
+            "message": format!("Name {} is not registered", name)
+        }))).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    }
+}
+
+/// Primary name of an address (`null` if it has none)
+async fn reverse_resolve(
+    Path(address): Path<String>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    if !Address::new(&address).is_valid() {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_address",
+            "message": format!("Invalid address format: {}", address)
+        }))).into_response();
+    }
+
+    match state.state.read().await.get_primary_name(&address) {
+        Ok(name) => Json(serde_json::json!({
+            "success": true,
+            "address": address,
+            "name": name
+        })).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    }
+}
+
+/// Account dump signed by this node's master key. The signature covers the
+/// compact JSON of `payload` (keys sorted), so clients can re-serialize and
+/// check it against `public_key`.
+async fn export_account(
+    Path(address): Path<String>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let addr = Address::new(&address);
+    if !addr.is_valid() {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_address",
+            "message": format!("Invalid address format: {}", address)
+        }))).into_response();
+    }
+
+    let state_guard = state.state.read().await;
+    let Some(keypair) = state_guard.get_keypair() else {
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
+            "success": false,
+            "error": "keypair_unavailable",
+            "message": "Node keypair not loaded"
+        }))).into_response();
+    };
+    let contracts = state_guard.get_mosh_contracts_by_creator(&address).unwrap_or_default();
+    let payload = serde_json::json!({
+        "chain_id": state.config.chain.chain_id,
+        "height": state_guard.get_height().unwrap_or(0),
+        "exported_at": Utc::now().timestamp(),
+        "address": address,
+        "balance_raw": state_guard.get_balance(&address).unwrap_or(0),
+        "nonce": state_guard.get_nonce(&address).unwrap_or(0),
+        "token_holdings": state_guard.get_token_holdings(&address).unwrap_or_default().iter().map(|h| serde_json::json!({
+            "contract": h.contract,
+            "symbol": h.symbol,
+            "decimals": h.decimals,
+            "balance_raw": h.balance
+        })).collect::<Vec<_>>(),
+        "tokens_created": state_guard.get_tokens_by_creator(&address).unwrap_or_default().iter()
+            .map(|t| t.address.clone())
+            .collect::<Vec<_>>(),
+        "contracts_deployed": contracts.iter().map(|c| c.address.clone()).collect::<Vec<_>>()
+    });
+    let signature = keypair.sign_hex(payload.to_string().as_bytes());
+
+    Json(serde_json::json!({
+        "success": true,
+        "payload": payload,
+        "signature": signature,
+        "public_key": keypair.public_key_hex(),
+        "signer": keypair.address()
+    })).into_response()
+}
+
+/// A transaction with the fee it paid, as listed per account and per block
+fn tx_with_fee_json(tx: &crate::chain::Transaction, block_height: Option<u64>) -> serde_json::Value {
+    let fee_paid = tx.gas_used * tx.gas_price;
+    serde_json::json!({
+        "hash": tx.hash,
+        "tx_type": tx.tx_type.as_str(),
+        "from": tx.from,
+        "to": tx.to,
+        "value": format_balance(tx.value),
+        "value_raw": tx.value,
+        "gas_used": tx.gas_used,
+        "fee_paid": format_balance(fee_paid),
+        "fee_paid_raw": fee_paid,
+        "nonce": tx.nonce,
+        "timestamp": tx.timestamp,
+        "status": tx.status,
+        "error": tx.error,
+        "block_height": block_height,
+        "data": tx.data
+    })
+}
+
 async fn get_address_transactions(
     Path(address): Path<String>,
     AxumState(state): AxumState<SharedState>,
@@ -471,25 +1321,7 @@ async fn get_address_transactions(
     let txs = state_guard.get_transactions_by_address(&address, 100).unwrap_or_default();
     
     let txs_with_fees: Vec<serde_json::Value> = txs.iter().map(|tx| {
-        let fee_paid = tx.gas_used * tx.gas_price;
-        let block_height = state_guard.get_transaction_block_height(&tx.hash).unwrap_or(None);
-        serde_json::json!({
-            "hash": tx.hash,
-            "tx_type": tx.tx_type.as_str(),
-            "from": tx.from,
-            "to": tx.to,
-            "value": format_balance(tx.value),
-            "value_raw": tx.value,
-            "gas_used": tx.gas_used,
-            "fee_paid": format_balance(fee_paid),
-            "fee_paid_raw": fee_paid,
-            "nonce": tx.nonce,
-            "timestamp": tx.timestamp,
-            "status": tx.status,
-            "error": tx.error,
-            "block_height": block_height,
-            "data": tx.data
-        })
+        tx_with_fee_json(tx, state_guard.get_transaction_block_height(&tx.hash).unwrap_or(None))
     }).collect();
 
     Json(serde_json::json!({
@@ -548,7 +1380,7 @@ async fn get_token_holdings(
             "contract": h.contract,
             "name": h.name,
             "symbol": h.symbol,
-            "balance": format_balance(h.balance),
+            "balance": crate::standards::format_amount(h.balance, h.decimals),
             "balance_raw": h.balance
         })).collect::<Vec<_>>()
     })).into_response()
@@ -556,15 +1388,44 @@ async fn get_token_holdings(
 
 // ===== MOSH CONTRACT ENDPOINTS =====
 
+const DEFAULT_PAGE_LIMIT: usize = 50;
+const MAX_PAGE_LIMIT: usize = 200;
+
+/// `?limit=&offset=&after=` for paged listings; `after` takes precedence over `offset`
+fn page_params(params: &std::collections::HashMap<String, String>) -> (usize, usize, Option<&str>) {
+    let limit = params.get("limit")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .clamp(1, MAX_PAGE_LIMIT);
+    let offset = params.get("offset").and_then(|s| s.parse().ok()).unwrap_or(0);
+    let after = params.get("after").map(|s| s.as_str()).filter(|s| !s.is_empty());
+    (limit, offset, after)
+}
+
+/// Cursor for the next page: the last address, if the page was full
+fn next_cursor<'a, T>(items: &'a [T], limit: usize, address: impl Fn(&'a T) -> &'a String) -> Option<&'a String> {
+    if items.len() < limit {
+        return None;
+    }
+    items.last().map(address)
+}
+
 async fn get_contracts(
+    Query(params): Query<std::collections::HashMap<String, String>>,
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
+    let (limit, offset, after) = page_params(&params);
     let state_guard = state.state.read().await;
-    let contracts = state_guard.get_all_mosh_contracts().unwrap_or_default();
+    let contracts = state_guard.get_mosh_contracts_page(after, offset, limit).unwrap_or_default();
+    let total = state_guard.count_mosh_contracts().unwrap_or(0);
     
     Json(serde_json::json!({
         "success": true,
         "count": contracts.len(),
+        "total": total,
+        "limit": limit,
+        "offset": offset,
+        "next": next_cursor(&contracts, limit, |c| &c.address),
         "contracts": contracts.iter().map(|c| serde_json::json!({
             "address": c.address,
             "name": c.name,
@@ -574,7 +1435,8 @@ async fn get_contracts(
             "variables": c.variables.len(),
             "mappings": c.mappings.len(),
             "functions": c.functions.iter().map(|f| &f.name).collect::<Vec<_>>(),
-            "created_at": c.created_at
+            "created_at": c.created_at,
+            "created_block": c.created_block
         })).collect::<Vec<_>>()
     }))
 }
@@ -604,7 +1466,8 @@ async fn get_contracts_by_creator(
             "name": c.name,
             "token": c.token,
             "functions": c.functions.len(),
-            "created_at": c.created_at
+            "created_at": c.created_at,
+            "created_block": c.created_block
         })).collect::<Vec<_>>()
     })).into_response()
 }
@@ -614,6 +1477,7 @@ async fn get_contract(
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
     let state_guard = state.state.read().await;
+    let verified_source = state_guard.get_verified_source(&address).unwrap_or(None);
     
     match state_guard.get_mosh_contract(&address) {
         Ok(Some(c)) => {
@@ -670,29 +1534,52 @@ async fn get_contract(
                     "name": c.name,
                     "creator": c.creator,
                     "owner": c.owner,
+                    "pending_owner": c.pending_owner,
+                    "paused": c.paused,
                     "created_at": c.created_at,
+                    "created_block": c.created_block,
                     "token": c.token,
-                    "token_info": token_info
+                    "token_info": token_info,
+                    "schema_hash": c.schema_hash(),
+                    "verified": verified_source.is_some()
                 },
+                "source": verified_source,
                 "variables": var_values,
                 "mappings": c.mappings.iter().map(|m| serde_json::json!({
                     "name": m.name,
                     "key_type": format!("{:?}", m.key_type),
-                    "value_type": format!("{:?}", m.value_type)
+                    "value_type": format!("{:?}", m.value_type),
+                    "value2_type": m.value2_type.as_ref().map(|t| format!("{:?}", t))
                 })).collect::<Vec<_>>(),
                 "functions": user_functions,
                 "auto_methods": {
                     "getters": getters,
                     "setters": setters,
-                    "reserved": ["get_owner", "set_owner", "get_creator", "get_token", "get_address"]
+                    "reserved": ["get_owner", "set_owner", "get_pending_owner", "set_pending_owner", "accept_ownership", "renounce_ownership", "pause", "unpause", "get_creator", "get_token", "set_token", "get_address"]
                 }
             })).into_response()
         }
-        Ok(None) => (StatusCode::NOT_FOUND, Json(serde_json::json!({
-            "success": false,
-            "error": "contract_not_found",
-            "message": format!("Contract not found: {}", address)
-        }))).into_response(),
+        // Raw code contract from a `deploy` tx
+        Ok(None) => match state_guard.get_code_contract(&address).unwrap_or(None) {
+            Some(c) => Json(serde_json::json!({
+                "success": true,
+                "contract": {
+                    "address": c.address,
+                    "name": c.name,
+                    "creator": c.creator,
+                    "created_at": c.created_at,
+                    "kind": "code",
+                    "code": hex::encode(&c.code),
+                    "code_size": c.code.len(),
+                    "value": state_guard.get_mosh_var(&address, "value").unwrap_or(None)
+                }
+            })).into_response(),
+            None => (StatusCode::NOT_FOUND, Json(serde_json::json!({
+                "success": false,
+                "error": "contract_not_found",
+                "message": format!("Contract not found: {}", address)
+            }))).into_response(),
+        },
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
             "success": false,
             "error": "internal_error",
@@ -750,6 +1637,13 @@ async fn get_contract_mapping(
 #[derive(Deserialize)]
 struct ReadQuery {
     args: Option<String>,  // Comma-separated args
+    from: Option<String>,  // Optional msg.sender for view functions
+    /// Dry-run a write function instead of refusing it
+    #[serde(default)]
+    simulate: bool,
+    amount: Option<u64>,   // msg.amount (tokens) for a simulated payable call
+    native_value: Option<u64>, // msg.value (native MVM) for a simulated payable call
+    gas_limit: Option<u64>,
 }
 
 async fn read_contract(
@@ -786,80 +1680,35 @@ async fn read_contract(
             return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
                 "success": false,
                 "error": "not_view_function",
-                "message": format!("Function '{}' is not a view function. Use /tx endpoint.", method),
+                "message": format!("Function '{}' is not a view function. Use /tx endpoint, or ?simulate=true to dry-run it.", method),
                 "modifiers": func.modifiers.iter().map(|m| format!("{:?}", m)).collect::<Vec<_>>()
             }))).into_response();
         }
         
-        // Execute view function - simple implementation for common patterns
-        // For now, handle simple return operations
-        for op in &func.body {
-            if op.op == "return" {
-                if let Some(ref val) = op.value {
-                    if let Some(s) = val.as_str() {
-                        // Check if it's a mapping access: mapname[key]
-                        if s.contains('[') && s.ends_with(']') {
-                            let parts: Vec<&str> = s.trim_end_matches(']').split('[').collect();
-                            if parts.len() == 2 {
-                                let map_name = parts[0];
-                                let key_expr = parts[1];
-                                
-                                // Resolve key - could be an arg name
-                                let key = if let Some(arg_idx) = func.args.iter().position(|a| a.name == key_expr) {
-                                    args.get(arg_idx).cloned().unwrap_or_default()
-                                } else {
-                                    key_expr.to_string()
-                                };
-                                
-                                let result = state_guard.get_mosh_map(&address, map_name, &key)
-                                    .unwrap_or(None)
-                                    .unwrap_or("0".to_string());
-                                
-                                // Try to parse as number
-                                let typed = if let Ok(n) = result.parse::<u64>() {
-                                    serde_json::json!(n)
-                                } else if result == "true" || result == "false" {
-                                    serde_json::json!(result == "true")
-                                } else {
-                                    serde_json::json!(result)
-                                };
-                                
-                                return Json(serde_json::json!({
-                                    "success": true,
-                                    "method": method,
-                                    "result": typed,
-                                    "gas": 0
-                                })).into_response();
-                            }
-                        }
-                        
-                        // Check if it's a variable
-                        if contract.variables.iter().any(|v| v.name == s) {
-                            let result = state_guard.get_mosh_var(&address, s)
-                                .unwrap_or(None)
-                                .unwrap_or("0".to_string());
-                            let typed = if let Ok(n) = result.parse::<u64>() {
-                                serde_json::json!(n)
-                            } else {
-                                serde_json::json!(result)
-                            };
-                            return Json(serde_json::json!({
-                                "success": true,
-                                "method": method,
-                                "result": typed,
-                                "gas": 0
-                            })).into_response();
-                        }
-                    }
-                }
-            }
+        // Execute view function with the read-only evaluator
+        let caller = query.from.clone().unwrap_or_default();
+        let result = match crate::mvm::MVM::new().view(&state_guard, &caller, &address, &method, args, state.config.block.gas_limit) {
+            Ok(r) => r,
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+                "success": false,
+                "error": "internal_error",
+                "message": e.to_string()
+            }))).into_response(),
+        };
+
+        if !result.success {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "success": false,
+                "error": "view_failed",
+                "method": method,
+                "message": result.error.unwrap_or_default()
+            }))).into_response();
         }
-        
-        // Default response for view functions
+
         return Json(serde_json::json!({
             "success": true,
             "method": method,
-            "result": null,
+            "result": result.data,
             "gas": 0
         })).into_response();
     }
@@ -873,6 +1722,9 @@ async fn read_contract(
             "owner" => return Json(serde_json::json!({
                 "success": true, "method": method, "result": contract.owner, "gas": 0
             })).into_response(),
+            "pending_owner" => return Json(serde_json::json!({
+                "success": true, "method": method, "result": contract.pending_owner, "gas": 0
+            })).into_response(),
             "creator" => return Json(serde_json::json!({
                 "success": true, "method": method, "result": contract.creator, "gas": 0
             })).into_response(),
@@ -909,10 +1761,18 @@ async fn read_contract(
                     "message": "Mapping getter requires key argument: ?args=<key>"
                 }))).into_response();
             }
-            let val = state_guard.get_mosh_map(&address, var_name, &args[0])
+            let val = match (m.is_nested(), args.get(1)) {
+                (true, Some(key2)) => state_guard.get_mosh_map2(&address, var_name, &args[0], key2),
+                (true, None) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                    "success": false,
+                    "error": "missing_key",
+                    "message": "Nested mapping getter requires two keys: ?args=<key>,<key2>"
+                }))).into_response(),
+                (false, _) => state_guard.get_mosh_map(&address, var_name, &args[0]),
+            }
                 .unwrap_or(None)
                 .unwrap_or_default();
-            let typed = match m.value_type {
+            let typed = match m.stored_type() {
                 crate::mvm::VarType::Uint64 => serde_json::json!(val.parse::<u64>().unwrap_or(0)),
                 crate::mvm::VarType::Bool => serde_json::json!(val == "true"),
                 _ => serde_json::json!(val),
@@ -954,39 +1814,219 @@ async fn call_contract_view(
     path: Path<(String, String)>,
     query: Query<ReadQuery>,
     state: AxumState<SharedState>,
-) -> impl IntoResponse {
-    read_contract(path, query, state).await
+) -> Response {
+    if query.simulate {
+        return simulate_contract_call(path, query, state).await.into_response();
+    }
+    read_contract(path, query, state).await.into_response()
 }
 
-// ===== MBI (Mosh Binary Interface) =====
-
-async fn get_contract_mbi(
-    Path(address): Path<String>,
+/// `?simulate=true`: run any function, writes included, on staged state and
+/// report its `CallResult` and the keys it would change. The staged writes
+/// are discarded, so nothing (token transfers included) ever leaves memory.
+async fn simulate_contract_call(
+    Path((address, method)): Path<(String, String)>,
+    Query(query): Query<ReadQuery>,
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
-    let state_guard = state.state.read().await;
-    
+    let args: Vec<String> = query.args
+        .map(|s| s.split(',').map(|x| x.trim().to_string()).collect())
+        .unwrap_or_default();
+    let caller = query.from.unwrap_or_default();
+    let gas_limit = query.gas_limit.unwrap_or(DEFAULT_GAS_LIMIT);
+    if let Err(e) = check_gas_limit(&state.config, gas_limit) {
+        return (StatusCode::BAD_REQUEST, Json(e)).into_response();
+    }
+
+    let mut state_guard = state.state.write().await;
     match state_guard.get_mosh_contract(&address) {
-        Ok(Some(c)) => {
-            // Build getters array
-            let mut getters = Vec::new();
-            for v in &c.variables {
-                getters.push(serde_json::json!({
-                    "method": format!("get_{}", v.name),
-                    "returns": format!("{:?}", v.var_type),
-                    "free": true,
-                    "call": format!("GET /contract/{}/call/get_{}", c.address, v.name)
-                }));
-            }
-            for m in &c.mappings {
-                getters.push(serde_json::json!({
-                    "method": format!("get_{}", m.name),
-                    "args": [{"name": "key", "type": format!("{:?}", m.key_type)}],
-                    "returns": format!("{:?}", m.value_type),
-                    "free": true,
-                    "call": format!("GET /contract/{}/call/get_{}?args={{key}}", c.address, m.name)
-                }));
-            }
+        Ok(Some(_)) => {}
+        Ok(None) => return (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "contract_not_found",
+            "message": format!("Contract not found: {}", address)
+        }))).into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    }
+
+    state_guard.begin_staging();
+    // Attached native MVM moves to the contract first, as it does for a real call
+    let native_value = query.native_value.unwrap_or(0);
+    if let Err(message) = stage_native_value(&mut state_guard, &caller, &address, native_value) {
+        state_guard.discard_staging();
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "insufficient_balance",
+            "message": message
+        }))).into_response();
+    }
+    let result = crate::mvm::MVM::new().call_with_value(
+        &mut state_guard,
+        &caller,
+        &address,
+        &method,
+        args,
+        query.amount.unwrap_or(0),
+        native_value,
+        gas_limit,
+    );
+    let changes = state_guard.staged_changes();
+    state_guard.discard_staging();
+    drop(state_guard);
+
+    let (result, changes) = match (result, changes) {
+        (Ok(r), Ok(c)) => (r, c),
+        (Err(e), _) | (_, Err(e)) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    };
+
+    Json(serde_json::json!({
+        "success": true,
+        "simulated": true,
+        "method": method,
+        "result": result,
+        "changes": changes.iter().map(|c| serde_json::json!({
+            "key": c.key,
+            "before": c.before.as_deref().map(|b| decode_state_value(&c.key, b)),
+            "after": c.after.as_deref().map(|b| decode_state_value(&c.key, b)),
+        })).collect::<Vec<_>>()
+    })).into_response()
+}
+
+/// Move a dry-run call's `native_value` from `from` to `contract` on staged
+/// state, as `call_contract` does before the contract's code runs
+fn stage_native_value(state: &mut State, from: &str, contract: &str, native_value: u64) -> Result<(), String> {
+    if native_value == 0 {
+        return Ok(());
+    }
+    let balance = state.get_balance(from).map_err(|e| e.to_string())?;
+    if balance < native_value {
+        return Err(format!("{} has {} but native_value is {}", from, balance, native_value));
+    }
+    state.set_balance(from, balance - native_value).map_err(|e| e.to_string())?;
+    let contract_balance = state.get_balance(contract).map_err(|e| e.to_string())?;
+    let credited = contract_balance.checked_add(native_value).ok_or("Balance overflow")?;
+    state.set_balance(contract, credited).map_err(|e| e.to_string())
+}
+
+// ===== MBI (Mosh Binary Interface) =====
+
+#[derive(Deserialize)]
+struct VerifySourceRequest {
+    /// Human-readable source, stored as given
+    source: String,
+    /// The contract's `deploy_contract` data, compiled from `source`
+    schema: serde_json::Value,
+    /// Creator's ed25519 signature over SHA-256("verify_source:" + address + source)
+    signature: String,
+    public_key: String,
+}
+
+/// Publish a contract's source. The creator signs it, and the schema it
+/// compiles to must hash the same as the deployed contract.
+async fn verify_contract_source(
+    Path(address): Path<String>,
+    AxumState(state): AxumState<SharedState>,
+    Json(req): Json<VerifySourceRequest>,
+) -> impl IntoResponse {
+    let contract = match state.state.read().await.get_mosh_contract(&address) {
+        Ok(Some(c)) => c,
+        Ok(None) => return (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "contract_not_found",
+            "message": format!("Contract not found: {}", address)
+        }))).into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    };
+
+    let message = {
+        use sha2::{Sha256, Digest};
+        Sha256::digest(format!("verify_source:{}{}", address, req.source).as_bytes()).to_vec()
+    };
+    if !verify_tx_signature(&contract.creator, &message, &req.signature, &req.public_key, SIG_SCHEME_ED25519).unwrap_or(false) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_signature",
+            "message": format!("Signature is not from the contract creator {}", contract.creator)
+        }))).into_response();
+    }
+
+    let submitted = match parse_tx_data(&TxType::DeployContract, None, Some(&req.schema)) {
+        Ok(Some(TxData::DeployContract { name, variables, mappings, functions, .. })) => {
+            crate::mvm::schema_hash(&name, &variables, &mappings, &functions)
+        }
+        Ok(_) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_data",
+            "message": "schema must be deploy_contract data"
+        }))).into_response(),
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(e)).into_response(),
+    };
+    let deployed = contract.schema_hash();
+    if submitted != deployed {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "schema_mismatch",
+            "message": "Submitted schema does not match the deployed contract",
+            "schema_hash": deployed,
+            "submitted_hash": submitted
+        }))).into_response();
+    }
+
+    if let Err(e) = state.state.write().await.save_verified_source(&address, &req.source, &deployed) {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response();
+    }
+
+    Json(serde_json::json!({
+        "success": true,
+        "address": address,
+        "schema_hash": deployed,
+        "verified": true
+    })).into_response()
+}
+
+async fn get_contract_mbi(
+    Path(address): Path<String>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let state_guard = state.state.read().await;
+    
+    match state_guard.get_mosh_contract(&address) {
+        Ok(Some(c)) => {
+            // Build getters array
+            let mut getters = Vec::new();
+            for v in &c.variables {
+                getters.push(serde_json::json!({
+                    "method": format!("get_{}", v.name),
+                    "returns": format!("{:?}", v.var_type),
+                    "free": true,
+                    "call": format!("GET /contract/{}/call/get_{}", c.address, v.name)
+                }));
+            }
+            for m in &c.mappings {
+                getters.push(serde_json::json!({
+                    "method": format!("get_{}", m.name),
+                    "args": [{"name": "key", "type": format!("{:?}", m.key_type)}],
+                    "returns": format!("{:?}", m.value_type),
+                    "free": true,
+                    "call": format!("GET /contract/{}/call/get_{}?args={{key}}", c.address, m.name)
+                }));
+            }
             for name in &["owner", "creator", "token", "address"] {
                 getters.push(serde_json::json!({
                     "method": format!("get_{}", name),
@@ -1037,7 +2077,12 @@ async fn get_contract_mbi(
                 "name": m.name,
                 "key_type": format!("{:?}", m.key_type),
                 "value_type": format!("{:?}", m.value_type),
-                "read": format!("GET /contract/{}/mapping/{}/{{key}}", c.address, m.name),
+                "value2_type": m.value2_type.as_ref().map(|t| format!("{:?}", t)),
+                "read": if m.is_nested() {
+                    format!("GET /contract/{}/mapping/{}/{{key}}/{{key2}}", c.address, m.name)
+                } else {
+                    format!("GET /contract/{}/mapping/{}/{{key}}", c.address, m.name)
+                },
                 "read_all": format!("GET /contract/{}/mapping/{}", c.address, m.name),
                 "write": format!("POST /tx call_contract set_{}", m.name)
             })).collect();
@@ -1120,6 +2165,9 @@ async fn read_contract_var(
         "owner" => return Json(serde_json::json!({
             "success": true, "variable": "owner", "value": contract.owner, "type": "Address"
         })).into_response(),
+        "pending_owner" => return Json(serde_json::json!({
+            "success": true, "variable": "pending_owner", "value": contract.pending_owner, "type": "Option<Address>"
+        })).into_response(),
         "creator" => return Json(serde_json::json!({
             "success": true, "variable": "creator", "value": contract.creator, "type": "Address"
         })).into_response(),
@@ -1140,15 +2188,10 @@ async fn read_contract_var(
         let val = state_guard.get_mosh_var(&address, &var_name)
             .unwrap_or(None)
             .unwrap_or_default();
-        let typed = match v.var_type {
-            crate::mvm::VarType::Uint64 => serde_json::json!(val.parse::<u64>().unwrap_or(0)),
-            crate::mvm::VarType::Bool => serde_json::json!(val == "true"),
-            _ => serde_json::json!(val),
-        };
         return Json(serde_json::json!({
             "success": true,
             "variable": var_name,
-            "value": typed,
+            "value": typed_var_value(&v.var_type, &val),
             "type": format!("{:?}", v.var_type)
         })).into_response();
     }
@@ -1160,6 +2203,86 @@ async fn read_contract_var(
     }))).into_response()
 }
 
+/// A stored variable or mapping value as JSON of its declared type
+fn typed_var_value(var_type: &crate::mvm::VarType, val: &str) -> serde_json::Value {
+    match var_type {
+        crate::mvm::VarType::Uint64 => serde_json::json!(val.parse::<u64>().unwrap_or(0)),
+        crate::mvm::VarType::Bool => serde_json::json!(val == "true"),
+        _ => serde_json::json!(val),
+    }
+}
+
+#[derive(Deserialize)]
+struct ContractStateQuery {
+    /// Set to false to leave out mapping entries, which can be large
+    #[serde(default = "default_include_mappings")]
+    mappings: bool,
+}
+
+fn default_include_mappings() -> bool {
+    true
+}
+
+/// Every variable and (unless `?mappings=false`) every mapping entry of a contract
+async fn get_contract_state(
+    Path(address): Path<String>,
+    Query(query): Query<ContractStateQuery>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let state_guard = state.state.read().await;
+
+    let contract = match state_guard.get_mosh_contract(&address) {
+        Ok(Some(c)) => c,
+        Ok(None) => return (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "contract_not_found",
+            "message": format!("Contract not found: {}", address)
+        }))).into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    };
+
+    let variables: Vec<_> = contract.variables.iter().map(|v| {
+        let val = state_guard.get_mosh_var(&address, &v.name)
+            .unwrap_or(None)
+            .unwrap_or_default();
+        serde_json::json!({
+            "name": v.name,
+            "type": format!("{:?}", v.var_type),
+            "value": typed_var_value(&v.var_type, &val)
+        })
+    }).collect();
+
+    let mut response = serde_json::json!({
+        "success": true,
+        "contract": address,
+        "name": contract.name,
+        "owner": contract.owner,
+        "variables": variables
+    });
+
+    if query.mappings {
+        let mappings: Vec<_> = contract.mappings.iter().map(|m| {
+            let stored_type = m.value2_type.as_ref().unwrap_or(&m.value_type);
+            let entries = state_guard.get_all_mosh_map_entries(&address, &m.name).unwrap_or_default();
+            serde_json::json!({
+                "name": m.name,
+                "count": entries.len(),
+                "entries": entries.iter().map(|(k, v)| serde_json::json!({
+                    "key": k,
+                    "value": typed_var_value(stored_type, v)
+                })).collect::<Vec<_>>()
+            })
+        }).collect();
+        response["mappings"] = serde_json::json!(mappings);
+    }
+
+    Json(response).into_response()
+}
+
 // ===== Direct Mapping Read =====
 
 async fn read_contract_mapping(
@@ -1209,6 +2332,54 @@ async fn read_contract_mapping(
     })).into_response()
 }
 
+async fn read_contract_mapping2(
+    Path((address, map_name, key, key2)): Path<(String, String, String, String)>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let state_guard = state.state.read().await;
+
+    let contract = match state_guard.get_mosh_contract(&address) {
+        Ok(Some(c)) => c,
+        Ok(None) => return (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "contract_not_found"
+        }))).into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": e.to_string()
+        }))).into_response(),
+    };
+
+    let mapping = match contract.mappings.iter().find(|m| m.name == map_name && m.is_nested()) {
+        Some(m) => m,
+        None => return (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "mapping_not_found",
+            "message": format!("Nested mapping '{}' not found", map_name)
+        }))).into_response(),
+    };
+
+    let val = state_guard.get_mosh_map2(&address, &map_name, &key, &key2)
+        .unwrap_or(None)
+        .unwrap_or_default();
+
+    let value_type = mapping.stored_type();
+    let typed = match value_type {
+        crate::mvm::VarType::Uint64 => serde_json::json!(val.parse::<u64>().unwrap_or(0)),
+        crate::mvm::VarType::Bool => serde_json::json!(val == "true"),
+        _ => serde_json::json!(val),
+    };
+
+    Json(serde_json::json!({
+        "success": true,
+        "mapping": map_name,
+        "key": key,
+        "key2": key2,
+        "value": typed,
+        "value_type": format!("{:?}", value_type)
+    })).into_response()
+}
+
 // ===== Get Blocks =====
 
 async fn get_blocks(
@@ -1219,6 +2390,8 @@ async fn get_blocks(
         .and_then(|s| s.parse().ok())
         .unwrap_or(10)
         .min(100);
+    // Headers only by default; `?include_txs=true` adds full transactions
+    let include_txs = params.get("include_txs").map(|s| s == "true").unwrap_or(false);
     
     let state_guard = state.state.read().await;
     let height = state_guard.get_height().unwrap_or(0);
@@ -1228,13 +2401,19 @@ async fn get_blocks(
     
     for h in (start..=height).rev() {
         if let Ok(Some(block)) = state_guard.get_block(h) {
-            blocks.push(serde_json::json!({
+            let hashes: Vec<&str> = block.transactions.iter().map(|tx| tx.hash.as_str()).collect();
+            let mut summary = serde_json::json!({
                 "height": block.height,
                 "hash": block.hash,
                 "timestamp": block.timestamp,
                 "tx_count": block.transactions.len(),
+                "tx_hashes": hashes,
                 "validator": block.validator
-            }));
+            });
+            if include_txs {
+                summary["transactions"] = serde_json::json!(block.transactions);
+            }
+            blocks.push(summary);
         }
     }
     
@@ -1248,6 +2427,9 @@ async fn get_blocks(
 
 // ===== Get Recent Transactions =====
 
+/// Most transactions a single `/txs` page reads; a sparse filter gets a cursor to resume from
+const TXS_SCAN_LIMIT: usize = 10_000;
+
 async fn get_recent_transactions(
     Query(params): Query<std::collections::HashMap<String, String>>,
     AxumState(state): AxumState<SharedState>,
@@ -1255,23 +2437,64 @@ async fn get_recent_transactions(
     let limit: usize = params.get("limit")
         .and_then(|s| s.parse().ok())
         .unwrap_or(20)
-        .min(100);
-    
+        .clamp(1, 100);
+
+    let tx_type = match params.get("type") {
+        Some(raw) => match parse_tx_type(raw) {
+            Some(t) => Some(t),
+            None => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "success": false,
+                "error": "invalid_tx_type",
+                "message": format!("Unknown transaction type: {}", raw)
+            }))).into_response(),
+        },
+        None => None,
+    };
+
+    let parse_height = |name: &str| params.get(name).map(|s| s.parse::<u64>()).transpose();
+    let (from_height, to_height) = match (parse_height("from_height"), parse_height("to_height")) {
+        (Ok(from), Ok(to)) => (from, to),
+        _ => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_height",
+            "message": "from_height and to_height must be block numbers"
+        }))).into_response(),
+    };
+
     let state_guard = state.state.read().await;
     let height = state_guard.get_height().unwrap_or(0);
-    
+    let from_height = from_height.unwrap_or(1).max(1);
+    let to_height = to_height.map_or(height, |h| h.min(height));
+
+    // The cursor is the global index sequence of the last transaction returned
+    let mut before_seq = match params.get("cursor").map(|s| s.parse::<u64>()) {
+        None => None,
+        Some(Ok(seq)) => Some(seq),
+        Some(Err(_)) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_cursor",
+            "message": "cursor must be the next_cursor of a previous page"
+        }))).into_response(),
+    };
+
     let mut txs = Vec::new();
-    
-    // Go through recent blocks
-    for h in (1..=height).rev() {
-        if txs.len() >= limit {
+    let mut next_cursor = None;
+    let mut scanned = 0;
+
+    // Newest first from the global tx index
+    'pages: loop {
+        let page = state_guard.get_recent_transactions(limit, before_seq).unwrap_or_default();
+        if page.is_empty() {
             break;
         }
-        if let Ok(Some(block)) = state_guard.get_block(h) {
-            for tx in &block.transactions {
-                if txs.len() >= limit {
-                    break;
-                }
+        for (seq, tx) in page {
+            before_seq = Some(seq);
+            scanned += 1;
+            let block_height = state_guard.get_transaction_block_height(&tx.hash).ok().flatten().unwrap_or(0);
+            if block_height < from_height {
+                break 'pages;
+            }
+            if block_height <= to_height && tx_type.as_ref().is_none_or(|t| tx.tx_type == *t) {
                 let fee_paid = tx.gas_used * tx.gas_price;
                 txs.push(serde_json::json!({
                     "hash": tx.hash,
@@ -1284,20 +2507,25 @@ async fn get_recent_transactions(
                     "fee_paid": format_balance(fee_paid),
                     "fee_paid_raw": fee_paid,
                     "status": tx.status,
-                    "block_height": h,
+                    "block_height": block_height,
                     "timestamp": tx.timestamp,
                     "nonce": tx.nonce,
                     "error": tx.error
                 }));
             }
+            if txs.len() == limit || scanned == TXS_SCAN_LIMIT {
+                next_cursor = (seq > 0).then_some(seq);
+                break 'pages;
+            }
         }
     }
-    
+
     Json(serde_json::json!({
         "success": true,
         "count": txs.len(),
-        "transactions": txs
-    }))
+        "transactions": txs,
+        "next_cursor": next_cursor
+    })).into_response()
 }
 
 // ===== CONTRACT EVENTS =====
@@ -1321,27 +2549,141 @@ async fn get_contract_events(
     }
 }
 
-// ===== LEADERBOARD =====
-
-async fn get_leaderboard(
+/// Txs that deployed or called a contract, each labelled with the method called
+async fn get_contract_transactions(
+    Path(address): Path<String>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
+    let (limit, _, _) = page_params(&params);
     let state_guard = state.state.read().await;
-    match state_guard.get_leaderboard() {
-        Ok(data) => Json(serde_json::json!({
-            "success": true,
-            "leaderboard": data
-        })),
-        Err(e) => Json(serde_json::json!({
+    let exists = state_guard.get_mosh_contract(&address).ok().flatten().is_some()
+        || state_guard.get_code_contract(&address).ok().flatten().is_some();
+    if !exists {
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({
             "success": false,
-            "error": e.to_string()
-        })),
+            "error": "contract_not_found",
+            "message": format!("Contract {} not found", address)
+        }))).into_response();
     }
-}
 
-async fn create_wallet() -> impl IntoResponse {
-    let keypair = crate::address::Keypair::generate();
-    let address = keypair.address();
+    let txs = state_guard.get_transactions_by_address(&address, limit).unwrap_or_default();
+    let transactions: Vec<serde_json::Value> = txs.iter().map(|tx| {
+        let mut json = tx_with_fee_json(tx, state_guard.get_transaction_block_height(&tx.hash).unwrap_or(None));
+        let method = match &tx.data {
+            Some(TxData::CallContract { method, .. }) | Some(TxData::Call { method, .. }) => Some(method.as_str()),
+            _ => None,
+        };
+        json["method"] = serde_json::json!(method);
+        json
+    }).collect();
+
+    Json(serde_json::json!({
+        "success": true,
+        "contract": address,
+        "count": transactions.len(),
+        "limit": limit,
+        "transactions": transactions
+    })).into_response()
+}
+
+// ===== ADMIN =====
+
+/// Produce a block right away instead of waiting for the next tick, then
+/// broadcast it as the production loop would
+async fn admin_produce_block(
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    if state.config.node.node_type != "master" {
+        return (StatusCode::FORBIDDEN, Json(serde_json::json!({
+            "success": false,
+            "error": "not_master",
+            "message": "Only the master node can force-produce blocks"
+        }))).into_response();
+    }
+
+    let mut blockchain = state.blockchain.write().await;
+    let next_height = state.state.read().await.get_height().unwrap_or(0) + 1;
+    let timestamp = Utc::now().timestamp();
+    if !blockchain.is_validator_for(next_height, timestamp).await.unwrap_or(false) {
+        return (StatusCode::CONFLICT, Json(serde_json::json!({
+            "success": false,
+            "error": "not_validator_turn",
+            "message": format!("Block {} belongs to another validator", next_height)
+        }))).into_response();
+    }
+    let block = match blockchain.produce_block_at(timestamp).await {
+        Ok(block) => block,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    };
+    drop(blockchain);
+
+    tracing::info!(height = block.height, tx_count = block.transactions.len(), hash = %block.hash, "📦 Block force-produced");
+    if let Err(e) = state.network.read().await.broadcast_block(&block).await {
+        tracing::error!("Failed to broadcast block: {}", e);
+    }
+
+    Json(serde_json::json!({
+        "success": true,
+        "height": block.height,
+        "hash": block.hash,
+        "tx_count": block.transactions.len(),
+        "tx_hashes": block.transactions.iter().map(|tx| tx.hash.as_str()).collect::<Vec<_>>()
+    })).into_response()
+}
+
+/// Clear an address's faucet cooldown so tests can claim again right away
+async fn admin_reset_faucet(
+    Path(address): Path<String>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    if !Address::new(&address).is_valid() {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_address",
+            "message": format!("Invalid address format: {}", address)
+        }))).into_response();
+    }
+
+    match state.state.write().await.clear_faucet_claim(&address) {
+        Ok(cleared) => Json(serde_json::json!({
+            "success": true,
+            "address": address,
+            "cleared": cleared
+        })).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    }
+}
+
+// ===== LEADERBOARD =====
+
+async fn get_leaderboard(
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let state_guard = state.state.read().await;
+    match state_guard.get_leaderboard() {
+        Ok(data) => Json(serde_json::json!({
+            "success": true,
+            "leaderboard": data
+        })),
+        Err(e) => Json(serde_json::json!({
+            "success": false,
+            "error": e.to_string()
+        })),
+    }
+}
+
+async fn create_wallet() -> impl IntoResponse {
+    let (keypair, mnemonic) = crate::address::Keypair::generate_with_mnemonic();
+    let address = keypair.address();
     let private_key = hex::encode(keypair.to_bytes());
     let public_key = keypair.public_key_hex();
     
@@ -1350,10 +2692,33 @@ async fn create_wallet() -> impl IntoResponse {
         "address": address.as_str(),
         "public_key": public_key,
         "private_key": private_key,
-        "warning": "Save your private key! It cannot be recovered."
+        "mnemonic": mnemonic,
+        "warning": "Save your mnemonic or private key! They cannot be recovered."
     }))
 }
 
+#[derive(Deserialize)]
+struct ImportWalletRequest {
+    mnemonic: String,
+}
+
+async fn import_wallet(
+    Json(req): Json<ImportWalletRequest>,
+) -> impl IntoResponse {
+    match crate::address::Keypair::from_mnemonic(&req.mnemonic) {
+        Ok(keypair) => Json(serde_json::json!({
+            "success": true,
+            "address": keypair.address().as_str(),
+            "public_key": keypair.public_key_hex()
+        })).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_mnemonic",
+            "message": e.to_string()
+        }))).into_response(),
+    }
+}
+
 async fn faucet(
     Path(address): Path<String>,
     AxumState(state): AxumState<SharedState>,
@@ -1377,7 +2742,13 @@ async fn faucet(
 
     let now = Utc::now().timestamp();
     let cooldown = state.config.faucet.cooldown as i64;
-    let amount = state.config.faucet.amount * 100_000_000;
+    let Some(amount) = state.config.faucet.amount.checked_mul(100_000_000) else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": "Faucet amount overflow"
+        }))).into_response();
+    };
 
     let mut state_guard = state.state.write().await;
     
@@ -1393,8 +2764,22 @@ async fn faucet(
         }
     }
 
-    let current_balance = state_guard.get_balance(&address).unwrap_or(0);
-    if let Err(e) = state_guard.set_balance(&address, current_balance + amount) {
+    let current_balance = match state_guard.get_balance(&address) {
+        Ok(b) => b,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    };
+    let Some(new_balance) = current_balance.checked_add(amount) else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": format!("Balance overflow for {}", address)
+        }))).into_response();
+    };
+    if let Err(e) = state_guard.set_balance(&address, new_balance) {
         return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
             "success": false,
             "error": "internal_error",
@@ -1426,6 +2811,11 @@ async fn faucet(
         public_key: String::new(),
         status: TxStatus::Success,
         error: None,
+        valid_until: 0,
+        sig_scheme: 0,
+        result: None,
+        from_delta: None,
+        to_delta: None,
     };
 
     // Save and index the faucet transaction so it appears in activity
@@ -1439,7 +2829,7 @@ async fn faucet(
         "success": true,
         "address": address,
         "amount": format_balance(amount),
-        "new_balance": format_balance(current_balance + amount),
+        "new_balance": format_balance(new_balance),
         "tx_hash": tx_hash
     })).into_response()
 }
@@ -1451,8 +2841,24 @@ struct SignTxRequest {
     from: String,
     to: Option<String>,
     value: Option<u64>,
+    #[serde(default)]
+    value_decimal: Option<String>,
     nonce: u64,
     data: Option<serde_json::Value>,
+    #[serde(default)]
+    valid_until: i64,
+    #[serde(default = "default_gas_price")]
+    gas_price: u64,
+    #[serde(default = "default_gas_limit")]
+    gas_limit: u64,
+}
+
+fn default_gas_price() -> u64 {
+    DEFAULT_GAS_PRICE
+}
+
+fn default_gas_limit() -> u64 {
+    DEFAULT_GAS_LIMIT
 }
 
 async fn sign_transaction(
@@ -1484,11 +2890,27 @@ async fn sign_transaction(
                 name: d["name"].as_str().unwrap_or("").to_string(),
                 symbol: d["symbol"].as_str().unwrap_or("").to_string(),
                 total_supply: d["total_supply"].as_u64().unwrap_or(0),
+                decimals: d["decimals"].as_u64().map(|n| n.min(u8::MAX as u64) as u8),
+                logo_uri: d["logo_uri"].as_str().map(|s| s.to_string()),
+                description: d["description"].as_str().map(|s| s.to_string()),
+            }),
+            "update_token_metadata" => Some(TxData::UpdateTokenMetadata {
+                contract: d["contract"].as_str().unwrap_or("").to_string(),
+                logo_uri: d["logo_uri"].as_str().map(|s| s.to_string()),
+                description: d["description"].as_str().map(|s| s.to_string()),
+            }),
+            "register_name" => Some(TxData::RegisterName {
+                name: d["name"].as_str().unwrap_or("").to_string(),
             }),
             "transfer_token" => Some(TxData::TransferToken {
                 contract: d["contract"].as_str().unwrap_or("").to_string(),
                 to: d["to"].as_str().unwrap_or("").to_string(),
                 amount: d["amount"].as_u64().unwrap_or(0),
+                amount_decimal: d["amount_decimal"].as_str().map(|s| s.to_string()),
+            }),
+            "deploy" => Some(TxData::Deploy {
+                code: parse_code_hex(d).unwrap_or_default(),
+                name: d["name"].as_str().unwrap_or("").to_string(),
             }),
             "call" => Some(TxData::Call {
                 contract: d["contract"].as_str().unwrap_or("").to_string(),
@@ -1512,6 +2934,7 @@ async fn sign_transaction(
                             name: m["name"].as_str()?.to_string(),
                             key_type: crate::mvm::VarType::from_str(m["key_type"].as_str()?)?,
                             value_type: crate::mvm::VarType::from_str(m["value_type"].as_str()?)?,
+                            value2_type: m["value2_type"].as_str().and_then(crate::mvm::VarType::from_str),
                         })
                     }).collect()).unwrap_or_default();
                 let functions: Vec<crate::mvm::FnDef> = d["functions"].as_array()
@@ -1550,6 +2973,7 @@ async fn sign_transaction(
                     .map(|a| a.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
                     .unwrap_or_default(),
                 amount: d["amount"].as_u64(),
+                native_value: d["native_value"].as_u64(),
             }),
             _ => None
         }
@@ -1557,14 +2981,23 @@ async fn sign_transaction(
         None
     };
 
+    let value = match request_value(req.value, req.value_decimal.as_deref()) {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(e)).into_response(),
+    };
+
     let data_str = tx_data.as_ref().map(|d| serde_json::to_string(d).unwrap_or_default());
     let tx_hash = hash_tx_data(
         &req.tx_type,
         &req.from,
         req.to.as_deref(),
-        req.value.unwrap_or(0) * 100_000_000,
+        value,
         req.nonce,
         data_str.as_deref(),
+        req.valid_until,
+        req.gas_price,
+        req.gas_limit,
+        SIG_SCHEME_ED25519,
     );
 
     let signature = keypair.sign_hex(&tx_hash);
@@ -1584,128 +3017,196 @@ struct SubmitTxRequest {
     tx_type: String,
     from: String,
     to: Option<String>,
+    /// Whole MVM; use `value_decimal` (e.g. `"0.5"`) for fractions
     value: Option<u64>,
+    #[serde(default)]
+    value_decimal: Option<String>,
     nonce: u64,
     data: Option<serde_json::Value>,
     signature: String,
     public_key: String,
+    /// Unix time after which the tx is dropped from the mempool (0 = never)
+    #[serde(default)]
+    valid_until: i64,
+    /// Price per gas unit; raise it to replace a pending tx with the same nonce
+    #[serde(default = "default_gas_price")]
+    gas_price: u64,
+    /// Most gas a contract call may use
+    #[serde(default = "default_gas_limit")]
+    gas_limit: u64,
+    /// Signature scheme of `signature` (0 = ed25519)
+    #[serde(default)]
+    sig_scheme: u8,
 }
 
-async fn submit_transaction(
-    AxumState(state): AxumState<SharedState>,
-    Json(req): Json<SubmitTxRequest>,
-) -> impl IntoResponse {
-    // Validate from address
-    let from_addr = Address::new(&req.from);
-    if !from_addr.is_valid() {
-        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
-            "success": false,
-            "error": "invalid_address",
-            "message": format!("Invalid 'from' address: {}", req.from)
-        }))).into_response();
-    }
-
-    // Validate to address if present
-    if let Some(ref to) = req.to {
-        let to_addr = Address::new(to);
-        if !to_addr.is_valid() {
-            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
-                "success": false,
-                "error": "invalid_address",
-                "message": format!("Invalid 'to' address: {}", to)
-            }))).into_response();
-        }
+/// Base-unit `value` of a tx request: `value` is whole MVM, `value_decimal`
+/// a decimal string like `"0.5"`; at most one may be set
+fn request_value(value: Option<u64>, value_decimal: Option<&str>) -> Result<u64, serde_json::Value> {
+    let invalid = |message: String| serde_json::json!({
+        "success": false,
+        "error": "invalid_value",
+        "message": message
+    });
+    match (value, value_decimal) {
+        (Some(_), Some(_)) => Err(invalid("Set either value or value_decimal, not both".to_string())),
+        (_, Some(text)) => crate::standards::parse_decimal_amount(text, 8).map_err(invalid),
+        (value, None) => value.unwrap_or(0).checked_mul(100_000_000)
+            .ok_or_else(|| invalid("Value is too large".to_string())),
     }
+}
 
-    // Parse tx_type
-    let tx_type = match req.tx_type.as_str() {
-        "transfer" => TxType::Transfer,
-        "deploy" => TxType::Deploy,
-        "call" => TxType::Call,
-        "create_token" => TxType::CreateToken,
-        "transfer_token" => TxType::TransferToken,
-        "deploy_contract" => TxType::DeployContract,
-        "call_contract" => TxType::CallContract,
-        _ => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ 
-            "success": false,
-            "error": "invalid_tx_type",
-            "message": format!("Invalid transaction type: {}. Valid types: transfer, create_token, transfer_token, deploy_contract, call_contract", req.tx_type)
-        }))).into_response(),
-    };
-
-    // Verify nonce (check both confirmed and pending)
-    let (confirmed_nonce, pending_nonce) = {
-        // Get confirmed nonce from state
-        let confirmed = {
-            let state_guard = state.state.read().await;
-            state_guard.get_nonce(&req.from).unwrap_or(0)
-        };
-        
-        // Get pending nonce from mempool
-        let blockchain = state.blockchain.read().await;
-        let pending = blockchain.mempool.get_pending_nonce(&req.from, confirmed);
-        
-        (confirmed, pending)
-    };
-    
-    // Expected nonce is the pending nonce (accounts for mempool TXs)
-    let expected_nonce = pending_nonce;
+/// The `code` of deploy data: hex, with or without a `0x` prefix
+fn parse_code_hex(d: &serde_json::Value) -> Option<Vec<u8>> {
+    let code = d["code"].as_str().unwrap_or("");
+    hex::decode(code.strip_prefix("0x").unwrap_or(code)).ok()
+}
 
-    if req.nonce != expected_nonce {
-        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
-            "success": false,
-            "error": "invalid_nonce",
-            "message": format!("Invalid nonce: expected {}, got {}", expected_nonce, req.nonce),
-            "expected_nonce": expected_nonce,
-            "got_nonce": req.nonce,
-            "confirmed_nonce": confirmed_nonce
-        }))).into_response();
-    }
-    
-    // Also check if this exact sender+nonce is already in mempool
-    {
-        let blockchain = state.blockchain.read().await;
-        if blockchain.mempool.has_pending_nonce(&req.from, req.nonce) {
-            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
-                "success": false,
-                "error": "nonce_already_pending",
-                "message": format!("Transaction with nonce {} is already pending", req.nonce)
-            }))).into_response();
-        }
+fn parse_tx_type(tx_type: &str) -> Option<TxType> {
+    match tx_type {
+        "transfer" => Some(TxType::Transfer),
+        "deploy" => Some(TxType::Deploy),
+        "call" => Some(TxType::Call),
+        "create_token" => Some(TxType::CreateToken),
+        "transfer_token" => Some(TxType::TransferToken),
+        "update_token_metadata" => Some(TxType::UpdateTokenMetadata),
+        "register_name" => Some(TxType::RegisterName),
+        "deploy_contract" => Some(TxType::DeployContract),
+        "call_contract" => Some(TxType::CallContract),
+        _ => None,
     }
+}
 
-    // Parse data first (before signature verification)
-    let data: Option<TxData> = if let Some(ref d) = req.data {
+/// Parse and check the type-specific `data` of a tx request
+fn parse_tx_data(tx_type: &TxType, to: Option<&str>, data: Option<&serde_json::Value>) -> Result<Option<TxData>, serde_json::Value> {
+    let parsed = if let Some(d) = data {
         match tx_type {
             TxType::CreateToken => {
                 let name = d["name"].as_str().unwrap_or("").to_string();
                 let symbol = d["symbol"].as_str().unwrap_or("").to_string();
                 let total_supply = d["total_supply"].as_u64().unwrap_or(0);
+                let decimals = d["decimals"].as_u64();
+                let logo_uri = d["logo_uri"].as_str().map(|s| s.to_string());
+                let description = d["description"].as_str().map(|s| s.to_string());
                 
                 if name.is_empty() || symbol.is_empty() {
-                    return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                    return Err(serde_json::json!({
                         "success": false,
                         "error": "invalid_data",
                         "message": "Token name and symbol are required"
-                    }))).into_response();
+                    }));
+                }
+                if decimals.is_some_and(|n| n > crate::standards::MAX_TOKEN_DECIMALS as u64) {
+                    return Err(serde_json::json!({
+                        "success": false,
+                        "error": "invalid_data",
+                        "message": format!("Token decimals must be at most {}", crate::standards::MAX_TOKEN_DECIMALS)
+                    }));
+                }
+                
+                if let Err(message) = crate::standards::validate_token_metadata(logo_uri.as_deref(), description.as_deref()) {
+                    return Err(serde_json::json!({
+                        "success": false,
+                        "error": "invalid_data",
+                        "message": message
+                    }));
                 }
                 
-                Some(TxData::CreateToken { name, symbol, total_supply })
+                Some(TxData::CreateToken { name, symbol, total_supply, decimals: decimals.map(|n| n as u8), logo_uri, description })
+            }
+            TxType::UpdateTokenMetadata => {
+                let contract = d["contract"].as_str().unwrap_or("").to_string();
+                let logo_uri = d["logo_uri"].as_str().map(|s| s.to_string());
+                let description = d["description"].as_str().map(|s| s.to_string());
+
+                if contract.is_empty() || (logo_uri.is_none() && description.is_none()) {
+                    return Err(serde_json::json!({
+                        "success": false,
+                        "error": "invalid_data",
+                        "message": "Token contract and at least one of logo_uri, description are required"
+                    }));
+                }
+                if let Err(message) = crate::standards::validate_token_metadata(logo_uri.as_deref(), description.as_deref()) {
+                    return Err(serde_json::json!({
+                        "success": false,
+                        "error": "invalid_data",
+                        "message": message
+                    }));
+                }
+
+                Some(TxData::UpdateTokenMetadata { contract, logo_uri, description })
+            }
+            TxType::RegisterName => {
+                let name = d["name"].as_str().unwrap_or("").to_string();
+                if let Err(message) = crate::names::validate_name(&name) {
+                    return Err(serde_json::json!({
+                        "success": false,
+                        "error": "invalid_data",
+                        "message": message
+                    }));
+                }
+
+                Some(TxData::RegisterName { name })
             }
             TxType::TransferToken => {
                 let contract = d["contract"].as_str().unwrap_or("").to_string();
                 let to = d["to"].as_str().unwrap_or("").to_string();
                 let amount = d["amount"].as_u64().unwrap_or(0);
+                let amount_decimal = d["amount_decimal"].as_str().map(|s| s.to_string());
                 
                 if contract.is_empty() || to.is_empty() {
-                    return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                    return Err(serde_json::json!({
                         "success": false,
                         "error": "invalid_data",
                         "message": "Contract address and recipient are required"
-                    }))).into_response();
+                    }));
+                }
+                if let Some(text) = &amount_decimal {
+                    // The token's decimals are only known at execution, so just
+                    // check the number is well formed
+                    let places = text.split_once('.').map_or(0, |(_, f)| f.len())
+                        .min(crate::standards::MAX_TOKEN_DECIMALS as usize) as u8;
+                    let checked = if d.get("amount").is_some() {
+                        Err("Set either amount or amount_decimal, not both".to_string())
+                    } else {
+                        crate::standards::parse_decimal_amount(text, places).map(|_| ())
+                    };
+                    if let Err(message) = checked {
+                        return Err(serde_json::json!({
+                            "success": false,
+                            "error": "invalid_amount",
+                            "message": message
+                        }));
+                    }
                 }
                 
-                Some(TxData::TransferToken { contract, to, amount })
+                Some(TxData::TransferToken { contract, to, amount, amount_decimal })
+            }
+            TxType::Deploy => {
+                let name = d["name"].as_str().unwrap_or("").to_string();
+                let Some(code) = parse_code_hex(d) else {
+                    return Err(serde_json::json!({
+                        "success": false,
+                        "error": "invalid_data",
+                        "message": "code must be a hex string"
+                    }));
+                };
+
+                if name.is_empty() || code.is_empty() {
+                    return Err(serde_json::json!({
+                        "success": false,
+                        "error": "invalid_data",
+                        "message": "Contract name and code are required"
+                    }));
+                }
+                if code.len() > crate::mvm::MAX_CODE_SIZE {
+                    return Err(serde_json::json!({
+                        "success": false,
+                        "error": "invalid_data",
+                        "message": format!("Code is larger than {} bytes", crate::mvm::MAX_CODE_SIZE)
+                    }));
+                }
+
+                Some(TxData::Deploy { code, name })
             }
             TxType::Call => {
                 let contract = d["contract"].as_str().unwrap_or("").to_string();
@@ -1715,11 +3216,11 @@ async fn submit_transaction(
                     .unwrap_or_default();
                 
                 if contract.is_empty() || method.is_empty() {
-                    return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                    return Err(serde_json::json!({
                         "success": false,
                         "error": "invalid_data",
                         "message": "Contract address and method name are required"
-                    }))).into_response();
+                    }));
                 }
                 
                 Some(TxData::Call { contract, method, args })
@@ -1747,7 +3248,8 @@ async fn submit_transaction(
                             let name = m["name"].as_str()?.to_string();
                             let key_type = crate::mvm::VarType::from_str(m["key_type"].as_str()?)?;
                             let value_type = crate::mvm::VarType::from_str(m["value_type"].as_str()?)?;
-                            Some(crate::mvm::MappingDef { name, key_type, value_type })
+                            let value2_type = m["value2_type"].as_str().and_then(crate::mvm::VarType::from_str);
+                            Some(crate::mvm::MappingDef { name, key_type, value_type, value2_type })
                         }).collect()
                     })
                     .unwrap_or_default();
@@ -1794,11 +3296,11 @@ async fn submit_transaction(
                     .unwrap_or_default();
                 
                 if name.is_empty() {
-                    return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                    return Err(serde_json::json!({
                         "success": false,
                         "error": "invalid_data",
                         "message": "Contract name is required"
-                    }))).into_response();
+                    }));
                 }
                 
                 Some(TxData::DeployContract { name, token, variables, mappings, functions })
@@ -1810,73 +3312,151 @@ async fn submit_transaction(
                     .map(|a| a.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
                     .unwrap_or_default();
                 let amount = d["amount"].as_u64();
+                let native_value = d["native_value"].as_u64();
                 
                 if contract.is_empty() || method.is_empty() {
-                    return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                    return Err(serde_json::json!({
                         "success": false,
                         "error": "invalid_data",
                         "message": "Contract address and method name are required"
-                    }))).into_response();
+                    }));
                 }
                 
-                Some(TxData::CallContract { contract, method, args, amount })
+                Some(TxData::CallContract { contract, method, args, amount, native_value })
             }
             TxType::Transfer => {
-                if req.to.is_none() {
-                    return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                if to.is_none() {
+                    return Err(serde_json::json!({
                         "success": false,
                         "error": "invalid_data",
                         "message": "Recipient address required for transfer"
-                    }))).into_response();
+                    }));
                 }
                 None
             }
-            _ => None
         }
     } else {
-        if tx_type == TxType::Transfer && req.to.is_none() {
-            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+        if *tx_type == TxType::Transfer && to.is_none() {
+            return Err(serde_json::json!({
                 "success": false,
                 "error": "invalid_data",
                 "message": "Recipient address required for transfer"
-            }))).into_response();
+            }));
         }
         None
     };
+    Ok(parsed)
+}
 
-    // Verify signature using TxData serialization
-    let data_str = data.as_ref().map(|d| serde_json::to_string(d).unwrap_or_default());
-    let tx_hash = hash_tx_data(
-        &req.tx_type,
-        &req.from,
-        req.to.as_deref(),
-        req.value.unwrap_or(0) * 100_000_000,
-        req.nonce,
-        data_str.as_deref(),
-    );
+/// Check a tx's gas price and limit against the node's fee policy
+fn check_tx_gas(config: &Config, tx: &Transaction) -> Result<(), serde_json::Value> {
+    if tx.gas_price < config.block.min_gas_price {
+        return Err(serde_json::json!({
+            "success": false,
+            "error": "gas_price_too_low",
+            "message": format!("gas_price {} is below the minimum of {}", tx.gas_price, config.block.min_gas_price),
+            "min_gas_price": config.block.min_gas_price
+        }));
+    }
+    check_gas_limit(config, tx.gas_limit)
+}
 
-    match verify_tx_signature(&req.from, &tx_hash, &req.signature, &req.public_key) {
-        Ok(true) => {},
-        Ok(false) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+/// A gas limit is between 1 and `block.gas_limit`, for real txs and dry runs alike
+fn check_gas_limit(config: &Config, gas_limit: u64) -> Result<(), serde_json::Value> {
+    if gas_limit == 0 || gas_limit > config.block.gas_limit {
+        return Err(serde_json::json!({
             "success": false,
-            "error": "invalid_signature",
-            "message": "Signature does not match sender address"
-        }))).into_response(),
-        Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "error": "invalid_gas_limit",
+            "message": format!("gas_limit must be between 1 and the block gas limit of {}", config.block.gas_limit)
+        }));
+    }
+    Ok(())
+}
+
+/// Validate a submitted tx (addresses, type, data, signature) and build it.
+/// Nonce ordering depends on the mempool and is checked by the caller.
+fn build_submitted_tx(req: SubmitTxRequest) -> Result<Transaction, serde_json::Value> {
+    // Validate from address
+    let from_addr = Address::new(&req.from);
+    if !from_addr.is_valid() {
+        return Err(serde_json::json!({
             "success": false,
-            "error": "signature_error",
-            "message": format!("Error verifying signature: {}", e)
-        }))).into_response(),
+            "error": "invalid_address",
+            "message": format!("Invalid 'from' address: {}", req.from)
+        }));
     }
 
-    let mut tx = Transaction {
-        hash: String::new(),
-        tx_type,
-        from: req.from,
+    // Validate to address if present
+    if let Some(ref to) = req.to {
+        let to_addr = Address::new(to);
+        if !to_addr.is_valid() {
+            return Err(serde_json::json!({
+                "success": false,
+                "error": "invalid_address",
+                "message": format!("Invalid 'to' address: {}", to)
+            }));
+        }
+    }
+
+    // Parse tx_type
+    let tx_type = match parse_tx_type(&req.tx_type) {
+        Some(t) => t,
+        None => return Err(serde_json::json!({
+            "success": false,
+            "error": "invalid_tx_type",
+            "message": format!("Invalid transaction type: {}. Valid types: transfer, create_token, transfer_token, update_token_metadata, register_name, deploy_contract, call_contract", req.tx_type)
+        })),
+    };
+
+    // Parse data first (before signature verification)
+    let data = parse_tx_data(&tx_type, req.to.as_deref(), req.data.as_ref())?;
+    let value = request_value(req.value, req.value_decimal.as_deref())?;
+
+    if req.valid_until != 0 && req.valid_until < Utc::now().timestamp() {
+        return Err(serde_json::json!({
+            "success": false,
+            "error": "tx_expired",
+            "message": format!("Transaction expired at {}", req.valid_until)
+        }));
+    }
+
+    // Verify signature using TxData serialization
+    let data_str = data.as_ref().map(|d| serde_json::to_string(d).unwrap_or_default());
+    let tx_hash = hash_tx_data(
+        &req.tx_type,
+        &req.from,
+        req.to.as_deref(),
+        value,
+        req.nonce,
+        data_str.as_deref(),
+        req.valid_until,
+        req.gas_price,
+        req.gas_limit,
+        req.sig_scheme,
+    );
+
+    match verify_tx_signature(&req.from, &tx_hash, &req.signature, &req.public_key, req.sig_scheme) {
+        Ok(true) => {},
+        Ok(false) => return Err(serde_json::json!({
+            "success": false,
+            "error": "invalid_signature",
+            "message": "Signature does not match sender address"
+        })),
+        Err(e) => return Err(serde_json::json!({
+            "success": false,
+            "error": "signature_error",
+            "message": format!("Error verifying signature: {}", e)
+        })),
+    }
+
+    let mut tx = Transaction {
+        hash: String::new(),
+        tx_type,
+        from: req.from,
         to: req.to,
-        value: req.value.unwrap_or(0) * 100_000_000,
-        gas_price: 1000,
-        gas_limit: 100000,
+        value,
+        gas_price: req.gas_price,
+        gas_limit: req.gas_limit,
         gas_used: 0,
         nonce: req.nonce,
         data,
@@ -1885,18 +3465,125 @@ async fn submit_transaction(
         public_key: req.public_key,
         status: TxStatus::Pending,
         error: None,
+        valid_until: req.valid_until,
+        sig_scheme: req.sig_scheme,
+        result: None,
+        from_delta: None,
+        to_delta: None,
     };
     tx.hash = tx.calculate_hash();
+    Ok(tx)
+}
 
-    let mut blockchain = state.blockchain.write().await;
-    match blockchain.add_transaction(tx.clone()) {
-        Ok(hash) => {
+async fn submit_transaction(
+    AxumState(state): AxumState<SharedState>,
+    Json(req): Json<SubmitTxRequest>,
+) -> impl IntoResponse {
+    let tx = match build_submitted_tx(req).and_then(|tx| check_tx_gas(&state.config, &tx).map(|_| tx)) {
+        Ok(tx) => tx,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(e)).into_response(),
+    };
+
+    // Verify nonce (check both confirmed and pending)
+    let (confirmed_nonce, pending_nonce) = {
+        // Get confirmed nonce from state
+        let confirmed = {
+            let state_guard = state.state.read().await;
+            state_guard.get_nonce(&tx.from).unwrap_or(0)
+        };
+        
+        // Get pending nonce from mempool
+        let blockchain = state.blockchain.read().await;
+        let pending = blockchain.mempool().get_pending_nonce(&tx.from, confirmed);
+        
+        (confirmed, pending)
+    };
+    
+    // Expected nonce is the pending nonce (accounts for mempool TXs)
+    let expected_nonce = pending_nonce;
+    // A pending nonce can be reused to replace that tx with a higher gas price
+    let replacing = state.blockchain.read().await.mempool().has_pending_nonce(&tx.from, tx.nonce);
+
+    if tx.nonce != expected_nonce && !replacing {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_nonce",
+            "message": format!("Invalid nonce: expected {}, got {}", expected_nonce, tx.nonce),
+            "expected_nonce": expected_nonce,
+            "got_nonce": tx.nonce,
+            "confirmed_nonce": confirmed_nonce
+        }))).into_response();
+    }
+
+    let hash = tx.hash.clone();
+    let added = {
+        let mut blockchain = state.blockchain.write().await;
+        // The node is busy rather than the tx bad: tell the client to back off
+        if !replacing && blockchain.mempool_is_full() && !blockchain.mempool().would_evict_for(&tx) {
+            let retry_after = state.config.block.block_time;
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
+                "success": false,
+                "error": "mempool_full",
+                "message": format!("Mempool is full, retry in {}s or raise the gas price", retry_after),
+                "retry_after": retry_after,
+                "mempool_size": blockchain.pending_count(),
+                "mempool_cap": blockchain.mempool().max_size()
+            }))).into_response();
+        }
+        blockchain.add_transaction(tx.clone())
+    };
+    let replaced = match &added {
+        Ok(MempoolInsert::Replaced { old_hash }) => Some(old_hash.clone()),
+        _ => None,
+    };
+    match added {
+        // Followers never produce blocks, so hand the tx to the master
+        Ok(_) if state.config.node.node_type != "master" => {
+            let network = state.network.read().await.clone();
+            match network.forward_tx(tx.clone()).await {
+                Ok(_) => {
+                    state.blockchain.read().await.announce_transaction(&tx);
+                    Json(serde_json::json!({
+                        "success": true,
+                        "hash": hash,
+                        "replaced": replaced,
+                        "message": "Transaction accepted by master"
+                    })).into_response()
+                }
+                Err(e) => {
+                    state.blockchain.write().await.remove_transaction(&hash);
+                    (StatusCode::BAD_GATEWAY, Json(serde_json::json!({
+                        "success": false,
+                        "error": "forward_failed",
+                        "message": e.to_string()
+                    }))).into_response()
+                }
+            }
+        }
+        Ok(MempoolInsert::Replaced { old_hash }) => {
+            state.blockchain.read().await.announce_transaction(&tx);
+            Json(serde_json::json!({
+                "success": true,
+                "hash": hash,
+                "replaced": old_hash,
+                "message": "Transaction replaced pending transaction"
+            })).into_response()
+        }
+        Ok(MempoolInsert::Added) => {
+            state.blockchain.read().await.announce_transaction(&tx);
             Json(serde_json::json!({
                 "success": true,
                 "hash": hash,
                 "message": "Transaction submitted successfully"
             })).into_response()
         }
+        Err(e) if replacing => {
+            (StatusCode::BAD_REQUEST, Json(serde_json::json!({ 
+                "success": false,
+                "error": "replacement_underpriced",
+                "message": e.to_string() 
+            }))).into_response()
+        }
         Err(e) => {
             (StatusCode::BAD_REQUEST, Json(serde_json::json!({ 
                 "success": false,
@@ -1907,12 +3594,439 @@ async fn submit_transaction(
     }
 }
 
+// ===== BATCH SUBMISSION =====
+
+#[derive(Deserialize)]
+struct BatchTxRequest {
+    transactions: Vec<SubmitTxRequest>,
+    #[serde(default = "default_all_or_nothing")]
+    all_or_nothing: bool,
+}
+
+fn default_all_or_nothing() -> bool {
+    true
+}
+
+fn batch_error(index: usize, error: &str, message: String) -> serde_json::Value {
+    serde_json::json!({
+        "index": index,
+        "success": false,
+        "error": error,
+        "message": message
+    })
+}
+
+/// Submit an ordered list of signed txs. Nonces must run sequentially per
+/// sender starting from the pending nonce. With `all_or_nothing` (default)
+/// nothing is added unless every tx is valid.
+async fn submit_batch(
+    AxumState(state): AxumState<SharedState>,
+    Json(req): Json<BatchTxRequest>,
+) -> impl IntoResponse {
+    if req.transactions.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "empty_batch",
+            "message": "Batch must contain at least one transaction"
+        }))).into_response();
+    }
+
+    let all_or_nothing = req.all_or_nothing;
+    let total = req.transactions.len();
+    let mut results: Vec<Option<serde_json::Value>> = vec![None; total];
+    let mut valid: Vec<(usize, Transaction)> = Vec::new();
+
+    for (index, tx_req) in req.transactions.into_iter().enumerate() {
+        match build_submitted_tx(tx_req).and_then(|tx| check_tx_gas(&state.config, &tx).map(|_| tx)) {
+            Ok(tx) => valid.push((index, tx)),
+            Err(e) => {
+                results[index] = Some(batch_error(
+                    index,
+                    e["error"].as_str().unwrap_or("invalid_tx"),
+                    e["message"].as_str().unwrap_or_default().to_string(),
+                ));
+            }
+        }
+    }
+
+    // Confirmed nonces first so the state lock isn't held under the chain lock
+    let mut confirmed: HashMap<String, u64> = HashMap::new();
+    {
+        let state_guard = state.state.read().await;
+        for (_, tx) in &valid {
+            confirmed.entry(tx.from.clone())
+                .or_insert_with(|| state_guard.get_nonce(&tx.from).unwrap_or(0));
+        }
+    }
+
+    // Check nonces and add under a single write lock so the batch can't
+    // interleave with other submissions
+    let mut added: Vec<(usize, Transaction)> = Vec::new();
+    {
+        let mut blockchain = state.blockchain.write().await;
+        let mut expected: HashMap<String, u64> = HashMap::new();
+        let mut sequenced: Vec<(usize, Transaction)> = Vec::new();
+
+        for (index, tx) in valid {
+            let next = expected.entry(tx.from.clone()).or_insert_with(|| {
+                blockchain.mempool().get_pending_nonce(&tx.from, confirmed[&tx.from])
+            });
+            if blockchain.mempool().has_pending_nonce(&tx.from, tx.nonce) {
+                results[index] = Some(batch_error(
+                    index,
+                    "nonce_already_pending",
+                    format!("Transaction with nonce {} is already pending", tx.nonce),
+                ));
+            } else if tx.nonce != *next {
+                results[index] = Some(batch_error(
+                    index,
+                    "invalid_nonce",
+                    format!("Invalid nonce: expected {}, got {}", next, tx.nonce),
+                ));
+            } else {
+                *next += 1;
+                sequenced.push((index, tx));
+            }
+        }
+
+        let rejected = results.iter().any(|r| r.is_some());
+        if !(all_or_nothing && rejected) {
+            let mut failed_senders: HashSet<String> = HashSet::new();
+            for (index, tx) in sequenced.drain(..) {
+                if failed_senders.contains(&tx.from) {
+                    results[index] = Some(batch_error(
+                        index,
+                        "invalid_nonce",
+                        "An earlier transaction from this sender was rejected".to_string(),
+                    ));
+                    continue;
+                }
+                match blockchain.add_transaction(tx.clone()) {
+                    Ok(_) => added.push((index, tx)),
+                    Err(e) => {
+                        results[index] = Some(batch_error(index, "tx_failed", e.to_string()));
+                        failed_senders.insert(tx.from.clone());
+                        if all_or_nothing {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            if all_or_nothing && results.iter().any(|r| r.is_some()) {
+                for (index, tx) in added.drain(..) {
+                    blockchain.remove_transaction(&tx.hash);
+                    sequenced.push((index, tx));
+                }
+            }
+        }
+
+        // Anything still here was valid but held back by the rollback
+        for (index, tx) in sequenced {
+            results[index] = Some(serde_json::json!({
+                "index": index,
+                "success": false,
+                "hash": tx.hash,
+                "error": "batch_aborted",
+                "message": "Not submitted: another transaction in the batch was rejected"
+            }));
+        }
+    }
+
+    // Followers hand accepted txs to the master in order. The master can't
+    // take back txs it already accepted, so a failure only drops the rest.
+    if state.config.node.node_type != "master" && !added.is_empty() {
+        let network = state.network.read().await.clone();
+        let mut pending = added.into_iter();
+        added = Vec::new();
+        while let Some((index, tx)) = pending.next() {
+            match network.forward_tx(tx.clone()).await {
+                Ok(_) => added.push((index, tx)),
+                Err(e) => {
+                    let mut blockchain = state.blockchain.write().await;
+                    blockchain.remove_transaction(&tx.hash);
+                    results[index] = Some(batch_error(index, "forward_failed", e.to_string()));
+                    for (index, tx) in pending.by_ref() {
+                        blockchain.remove_transaction(&tx.hash);
+                        results[index] = Some(batch_error(
+                            index,
+                            "forward_failed",
+                            "Not forwarded: an earlier transaction failed to reach the master".to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    // Only now is every remaining tx in for good
+    if !added.is_empty() {
+        let blockchain = state.blockchain.read().await;
+        for (_, tx) in &added {
+            blockchain.announce_transaction(tx);
+        }
+    }
+    for (index, tx) in &added {
+        results[*index] = Some(serde_json::json!({
+            "index": index,
+            "success": true,
+            "hash": tx.hash
+        }));
+    }
+
+    let accepted = added.len();
+    let status = if accepted == 0 { StatusCode::BAD_REQUEST } else { StatusCode::OK };
+    (status, Json(serde_json::json!({
+        "success": accepted == total,
+        "accepted": accepted,
+        "rejected": total - accepted,
+        "all_or_nothing": all_or_nothing,
+        "results": results.into_iter().flatten().collect::<Vec<_>>()
+    }))).into_response()
+}
+
+// ===== GAS ESTIMATION =====
+
+#[derive(Deserialize)]
+struct EstimateRequest {
+    tx_type: String,
+    from: String,
+    data: Option<serde_json::Value>,
+}
+
+async fn estimate_gas(
+    AxumState(state): AxumState<SharedState>,
+    Json(req): Json<EstimateRequest>,
+) -> impl IntoResponse {
+    let tx_type = match parse_tx_type(&req.tx_type) {
+        Some(t) => t,
+        None => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_tx_type",
+            "message": format!("Invalid transaction type: {}", req.tx_type)
+        }))).into_response(),
+    };
+
+    let mut gas_used = tx_type.base_gas();
+    let mut would_succeed = true;
+    let mut error: Option<String> = None;
+
+    if tx_type == TxType::CallContract {
+        let d = req.data.unwrap_or_default();
+        let contract = d["contract"].as_str().unwrap_or("").to_string();
+        let method = d["method"].as_str().unwrap_or("").to_string();
+        let args: Vec<String> = d["args"].as_array()
+            .map(|a| a.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+            .unwrap_or_default();
+        let amount = d["amount"].as_u64().unwrap_or(0);
+        let native_value = d["native_value"].as_u64().unwrap_or(0);
+
+        if contract.is_empty() || method.is_empty() {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+                "success": false,
+                "error": "invalid_data",
+                "message": "Contract address and method name are required"
+            }))).into_response();
+        }
+
+        // Run against staged state so nothing is written to RocksDB. No tx
+        // can use more than the block gas limit, so neither may the estimate.
+        let mut state_guard = state.state.write().await;
+        state_guard.begin_staging();
+        let result = stage_native_value(&mut state_guard, &req.from, &contract, native_value)
+            .map_err(BoxError::from)
+            .and_then(|_| crate::mvm::MVM::new().call_with_value(
+                &mut state_guard, &req.from, &contract, &method, args, amount, native_value, state.config.block.gas_limit,
+            ));
+        state_guard.discard_staging();
+        drop(state_guard);
+
+        match result {
+            Ok(r) => {
+                gas_used = r.gas_used;
+                would_succeed = r.success;
+                error = r.error;
+            }
+            Err(e) => {
+                would_succeed = false;
+                error = Some(e.to_string());
+            }
+        }
+    }
+
+    let fee = gas_used * DEFAULT_GAS_PRICE;
+    // Flat fees charged on top of gas
+    let protocol_fee = match tx_type {
+        TxType::CreateToken => state.config.token.creation_fee,
+        TxType::DeployContract => state.config.contract.deploy_fee,
+        TxType::RegisterName => state.config.names.registration_fee,
+        _ => 0,
+    } * 100_000_000;
+    Json(serde_json::json!({
+        "success": true,
+        "tx_type": tx_type.as_str(),
+        "gas_used": gas_used,
+        "gas_price": DEFAULT_GAS_PRICE,
+        "fee": format_balance(fee),
+        "fee_raw": fee,
+        "protocol_fee": format_balance(protocol_fee),
+        "protocol_fee_raw": protocol_fee,
+        "would_succeed": would_succeed,
+        "error": error
+    })).into_response()
+}
+
+// ===== SIMULATION =====
+
+#[derive(Deserialize)]
+struct SimulateRequest {
+    tx_type: String,
+    from: String,
+    to: Option<String>,
+    value: Option<u64>,
+    #[serde(default)]
+    value_decimal: Option<String>,
+    /// Defaults to the sender's confirmed nonce
+    nonce: Option<u64>,
+    data: Option<serde_json::Value>,
+    gas_limit: Option<u64>,
+}
+
+/// Decode a raw state value for display: u64 counters as numbers, JSON
+/// records as JSON, other text as strings and anything else as hex
+fn decode_state_value(key: &str, bytes: &[u8]) -> serde_json::Value {
+    let numeric = ["balance:", "nonce:", "token_balance:", "meta:"].iter().any(|p| key.starts_with(p));
+    if numeric {
+        if let Ok(raw) = <[u8; 8]>::try_from(bytes) {
+            return serde_json::json!(u64::from_le_bytes(raw));
+        }
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => serde_json::from_str(text).unwrap_or_else(|_| serde_json::json!(text)),
+        Err(_) => serde_json::json!(hex::encode(bytes)),
+    }
+}
+
+/// Execute an unsigned tx against the current state and report the outcome
+/// and every key it would write. Nothing is committed.
+async fn simulate_transaction(
+    AxumState(state): AxumState<SharedState>,
+    Json(req): Json<SimulateRequest>,
+) -> impl IntoResponse {
+    if !Address::new(&req.from).is_valid() {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_address",
+            "message": format!("Invalid 'from' address: {}", req.from)
+        }))).into_response();
+    }
+    let tx_type = match parse_tx_type(&req.tx_type) {
+        Some(t) => t,
+        None => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({
+            "success": false,
+            "error": "invalid_tx_type",
+            "message": format!("Invalid transaction type: {}", req.tx_type)
+        }))).into_response(),
+    };
+    let data = match parse_tx_data(&tx_type, req.to.as_deref(), req.data.as_ref()) {
+        Ok(d) => d,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(e)).into_response(),
+    };
+    let value = match request_value(req.value, req.value_decimal.as_deref()) {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(e)).into_response(),
+    };
+    let gas_limit = req.gas_limit.unwrap_or(DEFAULT_GAS_LIMIT);
+    if let Err(e) = check_gas_limit(&state.config, gas_limit) {
+        return (StatusCode::BAD_REQUEST, Json(e)).into_response();
+    }
+
+    let blockchain = state.blockchain.read().await;
+    let mut state_guard = state.state.write().await;
+    let nonce = match req.nonce {
+        Some(n) => n,
+        None => state_guard.get_nonce(&req.from).unwrap_or(0),
+    };
+
+    let mut tx = Transaction {
+        hash: String::new(),
+        tx_type,
+        from: req.from,
+        to: req.to,
+        value,
+        gas_price: DEFAULT_GAS_PRICE,
+        gas_limit,
+        gas_used: 0,
+        nonce,
+        data,
+        timestamp: Utc::now().timestamp(),
+        signature: String::new(),
+        public_key: String::new(),
+        status: TxStatus::Pending,
+        error: None,
+        valid_until: 0,
+        sig_scheme: SIG_SCHEME_ED25519,
+        result: None,
+        from_delta: None,
+        to_delta: None,
+    };
+    tx.hash = tx.calculate_hash();
+
+    // Same staging trick as /estimate: run for real, then throw the writes away
+    state_guard.begin_staging();
+    let result = blockchain.apply_transaction(&mut state_guard, &mut tx);
+    let changes = state_guard.staged_changes();
+    state_guard.discard_staging();
+    drop(state_guard);
+    drop(blockchain);
+
+    let changes = match changes {
+        Ok(c) => c,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+    };
+
+    let fee = tx.gas_used * tx.gas_price;
+    Json(serde_json::json!({
+        "success": true,
+        "would_succeed": result.is_ok(),
+        "tx_error": result.as_ref().err(),
+        "message": result.as_ref().err().map(|e| e.to_string()),
+        "tx_type": tx.tx_type.as_str(),
+        "nonce": tx.nonce,
+        "to": tx.to,
+        "gas_used": tx.gas_used,
+        "fee": format_balance(fee),
+        "fee_raw": fee,
+        "result": tx.result,
+        "changes": changes.iter().map(|c| serde_json::json!({
+            "key": c.key,
+            "before": c.before.as_deref().map(|b| decode_state_value(&c.key, b)),
+            "after": c.after.as_deref().map(|b| decode_state_value(&c.key, b)),
+        })).collect::<Vec<_>>()
+    })).into_response()
+}
+
 async fn get_tokens(
+    Query(params): Query<std::collections::HashMap<String, String>>,
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
+    let (limit, offset, after) = page_params(&params);
     let state_guard = state.state.read().await;
-    match state_guard.get_all_tokens() {
-        Ok(tokens) => Json(serde_json::json!({ "success": true, "tokens": tokens })).into_response(),
+    let page = state_guard.get_tokens_page(after, offset, limit)
+        .and_then(|tokens| Ok((tokens, state_guard.count_tokens()?)));
+    match page {
+        Ok((tokens, total)) => Json(serde_json::json!({
+            "success": true,
+            "total": total,
+            "limit": limit,
+            "offset": offset,
+            "next": next_cursor(&tokens, limit, |t| &t.address),
+            "tokens": tokens
+        })).into_response(),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ 
             "success": false,
             "error": "internal_error",
@@ -1948,7 +4062,7 @@ async fn get_token_balance(
     let state_guard = state.state.read().await;
     
     // Check if token exists
-    match state_guard.get_token(&contract) {
+    let token = match state_guard.get_token(&contract) {
         Ok(None) => return (StatusCode::NOT_FOUND, Json(serde_json::json!({ 
             "success": false,
             "error": "token_not_found",
@@ -1959,8 +4073,8 @@ async fn get_token_balance(
             "error": "internal_error",
             "message": e.to_string() 
         }))).into_response(),
-        Ok(Some(_)) => {}
-    }
+        Ok(Some(t)) => t,
+    };
 
     let balance = state_guard.get_token_balance(&contract, &address).unwrap_or(0);
     
@@ -1968,19 +4082,56 @@ async fn get_token_balance(
         "success": true,
         "contract": contract,
         "address": address,
-        "balance": format_balance(balance),
+        "balance": crate::standards::format_amount(balance, token.decimals),
         "balance_raw": balance
     })).into_response()
 }
 
+async fn get_token_allowance(
+    Path((contract, owner, spender)): Path<(String, String, String)>,
+    AxumState(state): AxumState<SharedState>,
+) -> impl IntoResponse {
+    let state_guard = state.state.read().await;
+
+    let token = match state_guard.get_token(&contract) {
+        Ok(None) => return (StatusCode::NOT_FOUND, Json(serde_json::json!({
+            "success": false,
+            "error": "token_not_found",
+            "message": format!("Token not found: {}", contract)
+        }))).into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
+            "success": false,
+            "error": "internal_error",
+            "message": e.to_string()
+        }))).into_response(),
+        Ok(Some(t)) => t,
+    };
+
+    let allowance = state_guard.get_token_allowance(&contract, &owner, &spender).unwrap_or(0);
+
+    Json(serde_json::json!({
+        "success": true,
+        "contract": contract,
+        "owner": owner,
+        "spender": spender,
+        "allowance": crate::standards::format_amount(allowance, token.decimals),
+        "allowance_raw": allowance
+    })).into_response()
+}
+
 async fn get_token_holders(
     Path(contract): Path<String>,
+    Query(params): Query<std::collections::HashMap<String, String>>,
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
+    let limit = params.get("limit")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .clamp(1, MAX_PAGE_LIMIT);
     let state_guard = state.state.read().await;
 
     // Verify token exists
-    match state_guard.get_token(&contract) {
+    let token = match state_guard.get_token(&contract) {
         Ok(None) => return (StatusCode::NOT_FOUND, Json(serde_json::json!({
             "success": false,
             "error": "token_not_found",
@@ -1991,23 +4142,31 @@ async fn get_token_holders(
             "error": "internal_error",
             "message": e.to_string()
         }))).into_response(),
-        Ok(Some(_)) => {}
-    }
+        Ok(Some(token)) => token,
+    };
 
     let holders = state_guard.get_token_holders(&contract).unwrap_or_default();
 
-    let holders_json: Vec<serde_json::Value> = holders.iter().map(|(addr, bal)| {
+    let holders_json: Vec<serde_json::Value> = holders.iter().take(limit).map(|(addr, bal)| {
+        let percentage = if token.total_supply > 0 {
+            *bal as f64 * 100.0 / token.total_supply as f64
+        } else {
+            0.0
+        };
         serde_json::json!({
             "address": addr,
-            "balance": format_balance(*bal),
-            "balance_raw": bal
+            "balance": crate::standards::format_amount(*bal, token.decimals),
+            "balance_raw": bal,
+            "percentage": format!("{:.4}", percentage)
         })
     }).collect();
 
     Json(serde_json::json!({
         "success": true,
         "contract": contract,
-        "holder_count": holders_json.len(),
+        "symbol": token.symbol,
+        "total_supply": crate::standards::format_amount(token.total_supply, token.decimals),
+        "holder_count": holders.len(),
         "holders": holders_json
     })).into_response()
 }
@@ -2016,79 +4175,240 @@ async fn ws_handler(
     ws: WebSocketUpgrade,
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
-    let config = state.config.clone();
-    let db_state = state.state.clone();
-    let network = state.network.clone();
-    
-    ws.on_upgrade(move |socket| handle_browser_socket(socket, config, db_state, network))
+    ws.on_upgrade(move |socket| handle_browser_socket(socket, state))
 }
 
-async fn handle_browser_socket(
-    socket: WebSocket,
-    config: Config,
-    state: Arc<RwLock<State>>,
-    network: Arc<RwLock<StarNetwork>>,
-) {
+async fn handle_browser_socket(socket: WebSocket, app: SharedState) {
     let (mut sender, mut receiver) = socket.split();
     
     let browser_id = uuid::Uuid::new_v4().to_string();
     info!("🌐 Browser connected: {}", &browser_id[..8]);
 
-    let mut block_rx = {
-        let net = network.read().await;
-        net.subscribe_blocks()
-    };
+    let (browser_tx, mut browser_rx) = tokio::sync::mpsc::channel::<P2PMessage>(100);
+    app.network.read().await.register_browser(browser_id.clone(), browser_tx).await;
 
     let status = {
-        let state_guard = state.read().await;
+        let state_guard = app.state.read().await;
         let height = state_guard.get_height().unwrap_or(0);
         serde_json::json!({
             "type": "welcome",
             "height": height,
-            "chain_id": config.chain.chain_id
+            "chain_id": app.config.chain.chain_id
         })
     };
     let _ = sender.send(Message::Text(status.to_string())).await;
 
-    let broadcast_task = tokio::spawn(async move {
-        while let Ok(block) = block_rx.recv().await {
-            let msg = serde_json::json!({
-                "type": "new_block",
-                "block": block
-            });
-            if sender.send(Message::Text(msg.to_string())).await.is_err() {
+    // Single writer for block pushes and query replies
+    let (reply_tx, mut reply_rx) = tokio::sync::mpsc::channel::<String>(100);
+    let writer_task = tokio::spawn(async move {
+        loop {
+            let text = tokio::select! {
+                pushed = browser_rx.recv() => match pushed {
+                    Some(P2PMessage::NewBlock(block)) => serde_json::json!({
+                        "type": "new_block",
+                        "block": block
+                    }).to_string(),
+                    Some(_) => continue,
+                    None => break,
+                },
+                reply = reply_rx.recv() => match reply {
+                    Some(text) => text,
+                    None => break,
+                },
+            };
+            if sender.send(Message::Text(text)).await.is_err() {
                 break;
             }
         }
     });
 
+    // Forward new_tx / event frames while the browser is subscribed
+    let mut tx_feed: Option<tokio::task::JoinHandle<()>> = None;
+    let mut event_feed: Option<tokio::task::JoinHandle<()>> = None;
+
     while let Some(Ok(msg)) = receiver.next().await {
-        if let Message::Text(_text) = msg {
-            // TODO: Handle browser queries
+        if let Message::Text(text) = msg {
+            let request: serde_json::Value = serde_json::from_str(&text).unwrap_or_default();
+            let reply = if let Some(topic) = request["subscribe"].as_str() {
+                if topic == "new_tx" {
+                    if tx_feed.is_none() {
+                        let mut tx_rx = app.network.read().await.subscribe_txs();
+                        let feed_tx = reply_tx.clone();
+                        tx_feed = Some(tokio::spawn(async move {
+                            loop {
+                                match tx_rx.recv().await {
+                                    Ok(tx) => {
+                                        let frame = serde_json::json!({ "type": "new_tx", "transaction": tx });
+                                        if feed_tx.send(frame.to_string()).await.is_err() {
+                                            break;
+                                        }
+                                    }
+                                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                                    Err(_) => break,
+                                }
+                            }
+                        }));
+                    }
+                    serde_json::json!({ "type": "subscribed", "topic": topic })
+                } else if topic == "events" {
+                    match request["contract"].as_str() {
+                        Some(contract) if Address::new(contract).is_valid() => {
+                            // One contract per socket; a new subscription replaces the filter
+                            if let Some(feed) = event_feed.take() {
+                                feed.abort();
+                            }
+                            let mut event_rx = app.network.read().await.subscribe_events();
+                            let feed_tx = reply_tx.clone();
+                            let filter = contract.to_string();
+                            event_feed = Some(tokio::spawn(async move {
+                                loop {
+                                    match event_rx.recv().await {
+                                        Ok(event) if event.contract == filter => {
+                                            let frame = serde_json::json!({ "type": "event", "event": event });
+                                            if feed_tx.send(frame.to_string()).await.is_err() {
+                                                break;
+                                            }
+                                        }
+                                        Ok(_) => continue,
+                                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                                        Err(_) => break,
+                                    }
+                                }
+                            }));
+                            serde_json::json!({ "type": "subscribed", "topic": topic, "contract": contract })
+                        }
+                        _ => serde_json::json!({
+                            "type": "error",
+                            "error": "invalid_contract",
+                            "message": "Subscribing to events needs a valid 'contract' address"
+                        }),
+                    }
+                } else {
+                    unknown_topic(topic)
+                }
+            } else if let Some(topic) = request["unsubscribe"].as_str() {
+                let feed = match topic {
+                    "new_tx" => Some(&mut tx_feed),
+                    "events" => Some(&mut event_feed),
+                    _ => None,
+                };
+                if let Some(feed) = feed {
+                    if let Some(feed) = feed.take() {
+                        feed.abort();
+                    }
+                    serde_json::json!({ "type": "unsubscribed", "topic": topic })
+                } else {
+                    unknown_topic(topic)
+                }
+            } else {
+                browser_query(&app, &text).await
+            };
+            if reply_tx.send(reply.to_string()).await.is_err() {
+                break;
+            }
         }
     }
 
-    broadcast_task.abort();
+    for feed in [tx_feed, event_feed].into_iter().flatten() {
+        feed.abort();
+    }
+    writer_task.abort();
+    app.network.read().await.unregister_browser(&browser_id).await;
     info!("🌐 Browser disconnected: {}", &browser_id[..8]);
 }
 
+fn unknown_topic(topic: &str) -> serde_json::Value {
+    serde_json::json!({
+        "type": "error",
+        "error": "unknown_topic",
+        "message": format!("Unknown topic: '{}'. Valid topics: new_tx, events", topic)
+    })
+}
+
+/// Answer a browser socket query with the same payload as the matching REST
+/// endpoint, tagged with `"type": <query>`. An `id` in the request is echoed back.
+async fn browser_query(app: &AppState, text: &str) -> serde_json::Value {
+    let request: serde_json::Value = match serde_json::from_str(text) {
+        Ok(v) => v,
+        Err(e) => return serde_json::json!({
+            "type": "error",
+            "error": "invalid_json",
+            "message": e.to_string()
+        }),
+    };
+    let query = request["query"].as_str().unwrap_or("");
+
+    let mut reply = match query {
+        "status" => serde_json::to_value(build_status(app).await).unwrap_or_default(),
+        "balance" => {
+            let address = request["address"].as_str().unwrap_or("");
+            if !Address::new(address).is_valid() {
+                serde_json::json!({
+                    "type": "error",
+                    "error": "invalid_address",
+                    "message": format!("Invalid address format: {}", address)
+                })
+            } else {
+                let balance = app.state.read().await.get_balance(address).unwrap_or(0);
+                serde_json::json!({
+                    "success": true,
+                    "address": address,
+                    "balance": format_balance(balance),
+                    "balance_raw": balance
+                })
+            }
+        }
+        "block" => match request["height"].as_u64() {
+            None => serde_json::json!({
+                "type": "error",
+                "error": "invalid_height",
+                "message": "Block query requires a numeric 'height'"
+            }),
+            Some(height) => match app.state.read().await.get_block(height) {
+                Ok(Some(block)) => serde_json::json!({ "success": true, "block": block }),
+                Ok(None) => serde_json::json!({
+                    "type": "error",
+                    "error": "block_not_found",
+                    "message": format!("Block {} not found", height)
+                }),
+                Err(e) => serde_json::json!({
+                    "type": "error",
+                    "error": "internal_error",
+                    "message": e.to_string()
+                }),
+            },
+        },
+        _ => serde_json::json!({
+            "type": "error",
+            "error": "unknown_query",
+            "message": format!("Unknown query: '{}'. Valid queries: status, balance, block", query)
+        }),
+    };
+
+    if reply.get("type").is_none() {
+        reply["type"] = serde_json::json!(query);
+    }
+    if let Some(id) = request.get("id") {
+        reply["id"] = id.clone();
+    }
+    reply
+}
+
 async fn p2p_handler(
     ws: WebSocketUpgrade,
     AxumState(state): AxumState<SharedState>,
 ) -> impl IntoResponse {
-    let network = state.network.clone();
+    // StarNetwork only holds shared handles, so a clone serves this connection
+    // without keeping the network lock held for its lifetime
+    let network = Arc::new(state.network.read().await.clone());
     
     ws.on_upgrade(move |socket| async move {
         let peer_id = uuid::Uuid::new_v4().to_string();
-        let (mut _sender, mut receiver) = socket.split();
-        while let Some(Ok(_msg)) = receiver.next().await {
-            // Handle messages
-        }
-        info!("🔌 P2P peer disconnected: {}", &peer_id[..8]);
-        drop(network);
+        network.handle_peer_connection(socket, peer_id).await;
     })
 }
 
+/// Native MVM, 8 decimals; token amounts use `standards::format_amount`
 fn format_balance(raw: u64) -> String {
     let whole = raw / 100_000_000;
     let fraction = raw % 100_000_000;
@@ -2097,4 +4417,171 @@ fn format_balance(raw: u64) -> String {
     } else {
         whole.to_string()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{test_state, TestDir};
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    /// App state for a node at genesis running `config`
+    async fn test_app(name: &str, config: Config) -> (SharedState, TestDir) {
+        let (mut state, dir) = test_state(&format!("api-{}", name));
+        let master = state.get_or_create_master_address().unwrap();
+        let state = Arc::new(RwLock::new(state));
+        let blockchain = Arc::new(RwLock::new(Blockchain::new(config.clone(), state.clone(), master).await.unwrap()));
+        let network = Arc::new(RwLock::new(StarNetwork::new(config.clone(), blockchain.clone(), state.clone())));
+        let rate_limiter = RateLimiter::new(config.ratelimit.requests_per_minute, config.ratelimit.burst);
+        (Arc::new(AppState { config, blockchain, state, network, rate_limiter }), dir)
+    }
+
+    fn test_config() -> Config {
+        toml::from_str(include_str!("../config.toml")).unwrap()
+    }
+
+    /// GET `uri` through the full router
+    async fn get_json(app: &SharedState, uri: &str) -> (StatusCode, serde_json::Value) {
+        send_json(app, "GET", uri).await
+    }
+
+    /// `method` `uri` with an empty body through the full router
+    async fn send_json(app: &SharedState, method: &str, uri: &str) -> (StatusCode, serde_json::Value) {
+        let mut req = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+        req.extensions_mut().insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0))));
+        let resp = router(app.clone()).oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_faucet_overflow_is_an_error_not_a_wrap() {
+        let mut config = test_config();
+        config.faucet.enabled = true;
+        let address = crate::address::Keypair::generate().address().to_string();
+
+        config.faucet.amount = u64::MAX;
+        let (app, _dir) = test_app("faucet-amount", config.clone()).await;
+        let (status, body) = send_json(&app, "POST", &format!("/faucet/{}", address)).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{}", body);
+
+        config.faucet.amount = 1;
+        let (app, _dir) = test_app("faucet-balance", config).await;
+        app.state.write().await.set_balance(&address, u64::MAX).unwrap();
+        let (status, body) = send_json(&app, "POST", &format!("/faucet/{}", address)).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{}", body);
+        assert_eq!(app.state.read().await.get_balance(&address).unwrap(), u64::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_token_allowance_is_zero_when_unset_and_404_for_an_unknown_token() {
+        let (app, _dir) = test_app("allowance", test_config()).await;
+        let token = crate::standards::create_mvm20_token(&mut *app.state.write().await, "mvm1alice", "Gold", "GLD", 1_000, 8, 0, 0).unwrap();
+
+        let (status, body) = get_json(&app, &format!("/token/{}/allowance/mvm1alice/mvm1bob", token)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!((body["allowance_raw"].clone(), body["allowance"].clone()), (serde_json::json!(0), serde_json::json!("0")));
+        assert_eq!((body["owner"].as_str(), body["spender"].as_str()), (Some("mvm1alice"), Some("mvm1bob")));
+
+        let (status, body) = get_json(&app, "/token/mvm1tokenmissing/allowance/mvm1alice/mvm1bob").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "token_not_found");
+    }
+
+    #[tokio::test]
+    async fn test_tx_status_moves_from_pending_to_included_to_final() {
+        let mut config = test_config();
+        config.block.confirmations_for_final = 2;
+        let (app, _dir) = test_app("tx-status", config).await;
+        let sender = crate::address::Keypair::generate();
+        app.state.write().await.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+        let recipient = crate::address::Keypair::generate().address().to_string();
+        let tx = crate::chain::signed_transfer(&sender, &recipient, 100, 0);
+
+        let (status, body) = get_json(&app, &format!("/tx/{}/status", tx.hash)).await;
+        assert_eq!((status, &body["error"]), (StatusCode::NOT_FOUND, &serde_json::json!("tx_not_found")));
+
+        app.blockchain.write().await.add_transaction(tx.clone()).unwrap();
+        let (_, body) = get_json(&app, &format!("/tx/{}/status", tx.hash)).await;
+        assert_eq!((&body["status"], &body["confirmations"]), (&serde_json::json!("pending"), &serde_json::Value::Null));
+
+        // In the tip block a tx has 0 confirmations; it's final once
+        // `confirmations_for_final` blocks are built on top
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            app.blockchain.write().await.produce_block_at(Utc::now().timestamp()).await.unwrap();
+            let (_, body) = get_json(&app, &format!("/tx/{}/status", tx.hash)).await;
+            assert_eq!(body["block_height"], 1);
+            seen.push((body["status"].as_str().unwrap().to_string(), body["confirmations"].as_u64().unwrap()));
+        }
+        let expected = [("included", 0), ("included", 1), ("final", 2)].map(|(s, c)| (s.to_string(), c));
+        assert_eq!(seen, expected);
+
+        let (_, body) = get_json(&app, &format!("/tx/{}/receipt", tx.hash)).await;
+        assert_eq!((&body["receipt"]["final"], &body["receipt"]["confirmations"]), (&serde_json::json!(true), &serde_json::json!(2)));
+    }
+
+    #[tokio::test]
+    async fn test_contract_txs_list_deploy_and_calls_with_their_method() {
+        let (app, _dir) = test_app("contract-txs", test_config()).await;
+        let sender = crate::address::Keypair::generate();
+        app.state.write().await.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+
+        let functions: Vec<crate::mvm::FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "poke", "modifiers": ["Write"], "body": []}
+        ])).unwrap();
+        let deploy = crate::chain::signed_tx(&sender, TxType::DeployContract, 0, Some(TxData::DeployContract {
+            name: "Poker".to_string(),
+            token: None,
+            variables: vec![],
+            mappings: vec![],
+            functions,
+        }));
+        let mut blockchain = app.blockchain.write().await;
+        blockchain.add_transaction(deploy.clone()).unwrap();
+        blockchain.produce_block_at(Utc::now().timestamp()).await.unwrap();
+        let contract = app.state.read().await.get_transaction(&deploy.hash).unwrap().unwrap().to.unwrap();
+        let call = crate::chain::signed_tx(&sender, TxType::CallContract, 1, Some(TxData::CallContract {
+            contract: contract.clone(),
+            method: "poke".to_string(),
+            args: vec![],
+            amount: None,
+            native_value: None,
+        }));
+        blockchain.add_transaction(call.clone()).unwrap();
+        blockchain.produce_block_at(Utc::now().timestamp()).await.unwrap();
+        drop(blockchain);
+
+        let (status, body) = get_json(&app, &format!("/contract/{}/txs?limit=1000", contract)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["limit"], MAX_PAGE_LIMIT);
+        let entries: HashMap<String, serde_json::Value> = body["transactions"].as_array().unwrap().iter()
+            .map(|tx| (tx["hash"].as_str().unwrap().to_string(), tx["method"].clone()))
+            .collect();
+        let expected = HashMap::from([(deploy.hash, serde_json::Value::Null), (call.hash, serde_json::json!("poke"))]);
+        assert_eq!(entries, expected);
+
+        let (_, body) = get_json(&app, &format!("/contract/{}/txs?limit=1", contract)).await;
+        assert_eq!(body["count"], 1);
+        let (status, _) = get_json(&app, "/contract/mvm1nothing/txs").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_replica_serves_no_p2p_endpoint() {
+        let mut config = test_config();
+        config.node.read_only = true;
+        let (replica, _dir) = test_app("p2p-replica", config).await;
+        // Not a websocket upgrade, so a served /p2p answers with a client error other than 404
+        let (status, _) = get_json(&replica, "/p2p").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get_json(&replica, "/status").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (node, _dir) = test_app("p2p-node", test_config()).await;
+        let (status, _) = get_json(&node, "/p2p").await;
+        assert_ne!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -1,16 +1,22 @@
 use crate::config::Config;
 use crate::state::State;
-use crate::address::Address;
-use crate::mvm::MVM;
+use crate::address::{Address, Keypair};
+use crate::mvm::{BlockEnv, ContractEvent, MVM};
 
 use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use chrono::Utc;
 
 pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
+/// Gas price for transactions that don't set one
+pub const DEFAULT_GAS_PRICE: u64 = 1000;
+
+/// Gas limit for transactions that don't set one
+pub const DEFAULT_GAS_LIMIT: u64 = 100_000;
+
 /// Transaction error types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TxError {
@@ -60,6 +66,9 @@ pub struct Block {
     pub gas_limit: u64,
     pub rewards: BlockRewards,
     pub signature: String,
+    /// Validator public key (hex) the signature was made with
+    #[serde(default)]
+    pub public_key: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,6 +87,46 @@ pub struct ServiceReward {
     pub amount: u64,
 }
 
+/// A follower's latest self-reported stats, used to rank service rewards
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceNode {
+    pub node_id: String,
+    /// Where the node's share of the service pool is paid
+    pub address: String,
+    pub browsers: u32,
+    /// Nodes connected to this follower
+    pub peers: u32,
+}
+
+/// Connected service nodes by node id, shared with the network layer
+pub type ServiceNodes = Arc<RwLock<std::collections::HashMap<String, ServiceNode>>>;
+
+/// Split `pool` between the nodes serving the most browsers, rank 1 first.
+/// Nodes without browsers earn nothing; ranks past `rank_3` get 0%.
+pub fn service_rewards(nodes: &[ServiceNode], rewards: &crate::config::RewardsConfig, pool: u64) -> Vec<ServiceReward> {
+    let browsers = |n: &ServiceNode| n.browsers.min(rewards.max_reported_browsers);
+    let mut ranked: Vec<&ServiceNode> = nodes.iter().filter(|n| n.browsers > 0).collect();
+    ranked.sort_by(|a, b| browsers(b).cmp(&browsers(a)).then_with(|| a.node_id.cmp(&b.node_id)));
+    // Several node ids reporting one payout address only hold its best rank
+    let mut seen = std::collections::HashSet::new();
+    ranked.retain(|n| seen.insert(n.address.as_str()));
+
+    let percents = [rewards.rank_1_percent, rewards.rank_2_percent, rewards.rank_3_percent];
+    ranked.into_iter()
+        .take(rewards.top_nodes)
+        .zip(percents)
+        .enumerate()
+        .map(|(i, (node, percent))| ServiceReward {
+            rank: (i + 1) as u8,
+            node_id: node.node_id.clone(),
+            address: node.address.clone(),
+            browsers: browsers(node),
+            // At most `pool`, as the percents are validated to sum to 100
+            amount: (pool as u128 * percent as u128 / 100) as u64,
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub hash: String,
@@ -95,6 +144,48 @@ pub struct Transaction {
     pub public_key: String,
     pub status: TxStatus,
     pub error: Option<String>,
+    /// Unix time after which the tx is dropped from the mempool (0 = no expiry)
+    #[serde(default)]
+    pub valid_until: i64,
+    /// How `signature` was made (see `address::SIG_SCHEME_*`)
+    #[serde(default)]
+    pub sig_scheme: u8,
+    /// Value returned by a successful contract call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    /// Net balance change the tx caused for the sender, captured when it ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_delta: Option<BalanceDelta>,
+    /// Same for the recipient (transfer target, token recipient or called contract)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_delta: Option<BalanceDelta>,
+}
+
+/// Signed change to one account's balances across a tx, in raw units
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BalanceDelta {
+    pub address: String,
+    pub native: i128,
+    /// The token the tx moved, if any, and the change in its balance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_amount: Option<i128>,
+}
+
+/// Native and (when `token` is set) token balance of `address`
+fn balances_of(state: &State, address: &str, token: Option<&str>) -> (u64, Option<u64>) {
+    let native = state.get_balance(address).unwrap_or(0);
+    (native, token.map(|t| state.get_token_balance(t, address).unwrap_or(0)))
+}
+
+fn balance_delta(address: &str, token: Option<&str>, before: (u64, Option<u64>), after: (u64, Option<u64>)) -> BalanceDelta {
+    BalanceDelta {
+        address: address.to_string(),
+        native: after.0 as i128 - before.0 as i128,
+        token: token.map(str::to_string),
+        token_amount: before.1.zip(after.1).map(|(b, a)| a as i128 - b as i128),
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -106,9 +197,23 @@ pub enum TxType {
     TransferToken,
     DeployContract,
     CallContract,
+    UpdateTokenMetadata,
+    RegisterName,
 }
 
 impl TxType {
+    pub const ALL: [TxType; 9] = [
+        TxType::Transfer,
+        TxType::Deploy,
+        TxType::Call,
+        TxType::CreateToken,
+        TxType::TransferToken,
+        TxType::DeployContract,
+        TxType::CallContract,
+        TxType::UpdateTokenMetadata,
+        TxType::RegisterName,
+    ];
+
     pub fn as_str(&self) -> &str {
         match self {
             TxType::Transfer => "transfer",
@@ -118,6 +223,23 @@ impl TxType {
             TxType::TransferToken => "transfer_token",
             TxType::DeployContract => "deploy_contract",
             TxType::CallContract => "call_contract",
+            TxType::UpdateTokenMetadata => "update_token_metadata",
+            TxType::RegisterName => "register_name",
+        }
+    }
+
+    /// Fixed gas charged per tx type
+    pub fn base_gas(&self) -> u64 {
+        match self {
+            TxType::Transfer => 21000,
+            TxType::Deploy => 200000,
+            TxType::Call => 50000,
+            TxType::CreateToken => 100000,
+            TxType::TransferToken => 65000,
+            TxType::DeployContract => 150000,
+            TxType::CallContract => 50000,  // Base, actual depends on method
+            TxType::UpdateTokenMetadata => 30000,
+            TxType::RegisterName => 40000,
         }
     }
 }
@@ -126,8 +248,28 @@ impl TxType {
 pub enum TxData {
     Deploy { code: Vec<u8>, name: String },
     Call { contract: String, method: String, args: Vec<String> },
-    CreateToken { name: String, symbol: String, total_supply: u64 },
-    TransferToken { contract: String, to: String, amount: u64 },
+    CreateToken {
+        name: String,
+        symbol: String,
+        total_supply: u64,
+        /// Defaults to 8; left out of the signed JSON when unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        decimals: Option<u8>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        logo_uri: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+    },
+    TransferToken {
+        contract: String,
+        to: String,
+        /// Base units; ignored when `amount_decimal` is set
+        amount: u64,
+        /// Whole tokens as a decimal string, scaled by the token's decimals
+        /// when the tx executes
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        amount_decimal: Option<String>,
+    },
     // Mosh Contract Deployment
     DeployContract { 
         name: String, 
@@ -146,9 +288,24 @@ pub enum TxData {
         method: String, 
         #[serde(default)]
         args: Vec<String>,
+        /// Token amount for a payable function (`msg.amount`)
         #[serde(default, skip_serializing_if = "Option::is_none")]
         amount: Option<u64>,
+        /// Native MVM (base units) moved to the contract before the call (`msg.value`)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        native_value: Option<u64>,
     },
+    /// Creator-only update of a token's logo and description; unset fields
+    /// are left as is, empty strings clear them
+    UpdateTokenMetadata {
+        contract: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        logo_uri: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+    },
+    /// Claim `name` for the sender in the name registry (see `crate::names`)
+    RegisterName { name: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -167,9 +324,26 @@ impl Transaction {
         hasher.update(self.value.to_le_bytes());
         hasher.update(self.nonce.to_le_bytes());
         hasher.update(self.timestamp.to_le_bytes());
+        if self.valid_until != 0 {
+            hasher.update(self.valid_until.to_le_bytes());
+        }
+        if self.gas_price != DEFAULT_GAS_PRICE {
+            hasher.update(self.gas_price.to_le_bytes());
+        }
+        if self.gas_limit != DEFAULT_GAS_LIMIT {
+            hasher.update(self.gas_limit.to_le_bytes());
+        }
+        if self.sig_scheme != crate::address::SIG_SCHEME_ED25519 {
+            hasher.update([self.sig_scheme]);
+        }
         hex::encode(hasher.finalize())
     }
 
+    /// Whether the tx's `valid_until` has passed at unix time `now`
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.valid_until != 0 && self.valid_until < now
+    }
+
     /// Get the message that needs to be signed
     pub fn get_sign_message(&self) -> Vec<u8> {
         let data_str = self.data.as_ref().map(|d| serde_json::to_string(d).unwrap_or_default());
@@ -180,6 +354,10 @@ impl Transaction {
             self.value,
             self.nonce,
             data_str.as_deref(),
+            self.valid_until,
+            self.gas_price,
+            self.gas_limit,
+            self.sig_scheme,
         )
     }
 
@@ -191,12 +369,14 @@ impl Transaction {
             &message,
             &self.signature,
             &self.public_key,
+            self.sig_scheme,
         )
     }
 }
 
 impl Block {
-    pub fn genesis(master_address: &str, master_balance: u64) -> Self {
+    /// `allocated` is the total pre-funded to genesis allocation accounts
+    pub fn genesis(master_address: &str, master_balance: u64, allocated: u64) -> Self {
         let timestamp = Utc::now().timestamp();
         let mut block = Block {
             height: 0,
@@ -211,9 +391,10 @@ impl Block {
             rewards: BlockRewards {
                 validator_reward: master_balance,
                 service_rewards: vec![],
-                total_minted: master_balance,
+                total_minted: master_balance + allocated,
             },
             signature: String::new(),
+            public_key: String::new(),
         };
         block.hash = block.calculate_hash();
         block
@@ -243,6 +424,7 @@ impl Block {
             gas_limit,
             rewards,
             signature: String::new(),
+            public_key: String::new(),
         };
         block.hash = block.calculate_hash();
         block
@@ -263,6 +445,27 @@ impl Block {
     pub fn is_valid(&self) -> bool {
         self.hash == self.calculate_hash()
     }
+
+    /// Sign the block hash with the validator's keypair
+    pub fn sign(&mut self, keypair: &Keypair) {
+        self.signature = keypair.sign_hex(self.calculate_hash().as_bytes());
+        self.public_key = keypair.public_key_hex();
+    }
+
+    /// Verify the signature was made by `validator_pubkey` and that the key
+    /// belongs to the block's validator address
+    pub fn verify_signature(&self, validator_pubkey: &str) -> Result<bool, BoxError> {
+        if self.signature.is_empty() {
+            return Ok(false);
+        }
+        crate::address::verify_tx_signature(
+            &self.validator,
+            self.calculate_hash().as_bytes(),
+            &self.signature,
+            validator_pubkey,
+            crate::address::SIG_SCHEME_ED25519,
+        )
+    }
 }
 
 /// Transaction pool with nonce ordering and deduplication
@@ -274,6 +477,18 @@ pub struct Mempool {
     pub by_sender: std::collections::HashMap<String, std::collections::BTreeMap<u64, String>>,
     /// Total count
     pub count: usize,
+    /// Maximum number of pending transactions (0 = unbounded)
+    pub max_size: usize,
+    /// Minimum gas price increase, in percent, for a tx to replace a pending one
+    pub replace_bump_percent: u64,
+}
+
+/// How `Blockchain::add_transaction` placed a tx in the mempool
+#[derive(Debug, Clone, PartialEq)]
+pub enum MempoolInsert {
+    Added,
+    /// Took the sender+nonce slot of the pending tx `old_hash`
+    Replaced { old_hash: String },
 }
 
 impl Mempool {
@@ -282,8 +497,18 @@ impl Mempool {
             by_hash: std::collections::HashMap::new(),
             by_sender: std::collections::HashMap::new(),
             count: 0,
+            max_size: 0,
+            replace_bump_percent: 0,
         }
     }
+
+    pub fn with_max_size(max_size: usize) -> Self {
+        Mempool { max_size, ..Mempool::new() }
+    }
+
+    pub fn with_replace_bump(self, replace_bump_percent: u64) -> Self {
+        Mempool { replace_bump_percent, ..self }
+    }
     
     /// Add transaction to mempool
     /// Returns Ok(true) if added, Ok(false) if duplicate hash, Err if same sender+nonce exists
@@ -304,6 +529,14 @@ impl Mempool {
             }
         }
         
+        // Full: make room by evicting a cheaper tx, or reject this one
+        if self.is_full() {
+            if !self.would_evict_for(&tx) {
+                return Err(format!("Mempool full ({} txs) and gas price {} is too low", self.count, tx.gas_price));
+            }
+            self.evict_lowest_fee(&sender);
+        }
+        
         // Add to by_hash
         self.by_hash.insert(hash.clone(), tx);
         
@@ -317,6 +550,36 @@ impl Mempool {
         Ok(true)
     }
     
+    /// Replace the pending tx with the same sender+nonce if `tx` pays at least
+    /// `replace_bump_percent` more gas price, returning the replaced tx
+    pub fn replace(&mut self, tx: Transaction) -> Result<Transaction, String> {
+        if self.by_hash.contains_key(&tx.hash) {
+            return Err("Transaction already in mempool (duplicate hash)".to_string());
+        }
+        let old_hash = self.by_sender.get(&tx.from)
+            .and_then(|txs| txs.get(&tx.nonce))
+            .cloned()
+            .ok_or_else(|| format!("No pending transaction with nonce {} for {}", tx.nonce, tx.from))?;
+        let old_price = self.by_hash[&old_hash].gas_price;
+
+        // Always strictly higher, even with a 0% bump
+        let bump = (old_price.saturating_mul(self.replace_bump_percent)).div_ceil(100).max(1);
+        let min_price = old_price.saturating_add(bump);
+        if tx.gas_price < min_price {
+            return Err(format!(
+                "Replacement gas price {} too low: must be at least {} ({}% above {})",
+                tx.gas_price, min_price, self.replace_bump_percent, old_price
+            ));
+        }
+
+        let old = self.by_hash.remove(&old_hash).expect("indexed tx is pending");
+        if let Some(sender_txs) = self.by_sender.get_mut(&tx.from) {
+            sender_txs.insert(tx.nonce, tx.hash.clone());
+        }
+        self.by_hash.insert(tx.hash.clone(), tx);
+        Ok(old)
+    }
+
     /// Remove transaction by hash
     pub fn remove(&mut self, hash: &str) -> Option<Transaction> {
         if let Some(tx) = self.by_hash.remove(hash) {
@@ -333,29 +596,91 @@ impl Mempool {
         }
     }
     
-    /// Get transactions ready for block (sorted by sender, then nonce)
+    /// Hash of the cheapest evictable tx. Only each sender's highest-nonce tx
+    /// is a candidate so eviction never leaves a nonce gap. `protect` is skipped.
+    fn lowest_fee_candidate(&self, protect: &str) -> Option<String> {
+        self.by_sender.iter()
+            .filter(|(sender, _)| sender.as_str() != protect)
+            .filter_map(|(_, txs)| txs.values().next_back())
+            .filter_map(|hash| self.by_hash.get(hash))
+            .min_by_key(|tx| tx.gas_price)
+            .map(|tx| tx.hash.clone())
+    }
+
+    /// At `max_size` (never, without a cap)
+    pub fn is_full(&self) -> bool {
+        self.max_size > 0 && self.count >= self.max_size
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Whether a full pool would make room for `tx` by evicting a cheaper
+    /// pending tx from another sender
+    pub fn would_evict_for(&self, tx: &Transaction) -> bool {
+        self.lowest_fee_candidate(&tx.from)
+            .and_then(|h| self.by_hash.get(&h))
+            .is_some_and(|lowest| tx.gas_price > lowest.gas_price)
+    }
+
+    /// Evict the lowest gas_price transaction (never one from `protect`)
+    pub fn evict_lowest_fee(&mut self, protect: &str) -> Option<Transaction> {
+        let hash = self.lowest_fee_candidate(protect)?;
+        self.remove(&hash)
+    }
+    
+    /// Remove every tx whose `valid_until` has passed, returning how many
+    pub fn remove_expired(&mut self, now: i64) -> usize {
+        let expired: Vec<String> = self.by_hash.values()
+            .filter(|tx| tx.is_expired(now))
+            .map(|tx| tx.hash.clone())
+            .collect();
+        for hash in &expired {
+            self.remove(hash);
+        }
+        expired.len()
+    }
+
+    /// Get transactions ready for block, highest gas_price first while keeping
+    /// each sender's transactions in nonce order. A sender's queue stops at its
+    /// first expired tx since the later nonces can't apply without it.
     pub fn get_pending(&self, max: usize) -> Vec<Transaction> {
         let mut result = Vec::new();
+        let now = Utc::now().timestamp();
         
-        // Collect all transactions
-        for tx in self.by_hash.values() {
-            result.push(tx.clone());
-        }
+        // One nonce-ordered queue per sender
+        let mut queues: Vec<std::collections::VecDeque<&Transaction>> = self.by_sender.values()
+            .map(|txs| txs.values()
+                .filter_map(|h| self.by_hash.get(h))
+                .take_while(|tx| !tx.is_expired(now))
+                .collect())
+            .filter(|q: &std::collections::VecDeque<&Transaction>| !q.is_empty())
+            .collect();
+        // Deterministic tie-break between senders
+        queues.sort_by(|a, b| a[0].from.cmp(&b[0].from));
         
-        // Sort by (sender, nonce) to ensure correct ordering
-        result.sort_by(|a, b| {
-            match a.from.cmp(&b.from) {
-                std::cmp::Ordering::Equal => a.nonce.cmp(&b.nonce),
-                other => other,
+        // Repeatedly take the best-paying head of any sender's queue
+        while result.len() < max {
+            let best = queues.iter()
+                .enumerate()
+                .filter_map(|(i, q)| q.front().map(|tx| (i, tx.gas_price)))
+                .fold(None, |best: Option<(usize, u64)>, (i, price)| match best {
+                    Some((_, best_price)) if best_price >= price => best,
+                    _ => Some((i, price)),
+                });
+            match best {
+                Some((i, _)) => result.push(queues[i].pop_front().unwrap().clone()),
+                None => break,
             }
-        });
+        }
         
-        result.truncate(max);
         result
     }
     
-    /// Drain transactions for block (removes them from mempool)
+    /// Drain transactions for block (removes them, and any expired ones, from mempool)
     pub fn drain_for_block(&mut self, max: usize) -> Vec<Transaction> {
+        self.remove_expired(Utc::now().timestamp());
         let txs = self.get_pending(max);
         for tx in &txs {
             self.remove(&tx.hash);
@@ -418,9 +743,100 @@ impl Mempool {
 pub struct Blockchain {
     pub config: Config,
     pub state: Arc<RwLock<State>>,
-    pub mempool: Mempool,
+    mempool: Mempool,
     pub master_address: Address,
     pub mvm: MVM,
+    /// Consecutive block intervals skipped because the mempool was empty
+    idle_ticks: u64,
+    /// Receives every tx accepted into the mempool (for live subscribers)
+    tx_notifier: Option<broadcast::Sender<Transaction>>,
+    /// Receives every contract event as its tx's events are stored
+    event_notifier: Option<broadcast::Sender<ContractEvent>>,
+    /// Followers eligible for the service pool, kept current by the network
+    service_nodes: ServiceNodes,
+    /// Unix time of the last produced block (startup time until the first one)
+    pub last_block_produced_at: i64,
+    /// Unix time of the last block interval, produced or skipped
+    pub last_tick_at: i64,
+}
+
+/// Take `amount` from `address`, reading and writing under the caller's write lock
+fn debit_balance(state: &mut State, address: &str, amount: u64) -> Result<(), TxError> {
+    let balance = state.get_balance(address).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+    let remaining = balance.checked_sub(amount)
+        .ok_or(TxError::InsufficientBalance { required: amount, available: balance })?;
+    state.set_balance(address, remaining).map_err(|e| TxError::InternalError { message: e.to_string() })
+}
+
+/// Take gas plus a protocol fee (whole MVM) from `from` in one step, paying the
+/// fee to the treasury, or burning it when there is none
+fn debit_with_fee(state: &mut State, from: &str, gas_fee: u64, fee: u64, treasury: Option<&str>) -> Result<(), TxError> {
+    let fee = fee.checked_mul(100_000_000)
+        .ok_or_else(|| TxError::InternalError { message: "Fee overflow".to_string() })?;
+    let total = gas_fee.checked_add(fee)
+        .ok_or_else(|| TxError::InternalError { message: "Fee overflow".to_string() })?;
+    debit_balance(state, from, total)?;
+    if fee == 0 {
+        return Ok(());
+    }
+    let internal = |e: BoxError| TxError::InternalError { message: e.to_string() };
+    match treasury {
+        Some(treasury) => {
+            credit_balance(state, treasury, fee)?;
+            state.add_treasury_fees(fee).map_err(internal)?;
+        }
+        // No treasury: the fee leaves circulation
+        None => {
+            let supply = state.get_total_supply().map_err(internal)?;
+            state.set_total_supply(supply.saturating_sub(fee)).map_err(internal)?;
+        }
+    }
+    Ok(())
+}
+
+/// Add `amount` to `address`, failing instead of wrapping on overflow
+fn credit_balance(state: &mut State, address: &str, amount: u64) -> Result<(), TxError> {
+    let balance = state.get_balance(address).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+    let updated = balance.checked_add(amount)
+        .ok_or_else(|| TxError::InternalError { message: format!("Balance overflow for {}", address) })?;
+    state.set_balance(address, updated).map_err(|e| TxError::InternalError { message: e.to_string() })
+}
+
+/// Store `block` as the new tip along with its tx index entries, and mint its
+/// rewards. Its txs must already have been applied to `st`.
+fn store_block(st: &mut State, block: &Block) -> Result<(), BoxError> {
+    st.save_block(block)?;
+    st.set_height(block.height)?;
+
+    // Index transactions for address lookup
+    for tx in &block.transactions {
+        st.index_transaction(tx, block.height)?;
+    }
+
+    let rewards = &block.rewards;
+    let current_balance = st.get_balance(&block.validator)?;
+    st.set_balance(
+        &block.validator,
+        current_balance.checked_add(rewards.validator_reward).ok_or("Validator balance overflow")?,
+    )?;
+    for reward in &rewards.service_rewards {
+        let balance = st.get_balance(&reward.address)?;
+        st.set_balance(&reward.address, balance.checked_add(reward.amount).ok_or("Service reward overflow")?)?;
+    }
+
+    // Everything the block's txs and reward moved, for get_balance_at
+    st.record_balance_history(block.height)?;
+
+    let current_supply = st.get_total_supply()?;
+    st.set_total_supply(current_supply.checked_add(rewards.total_minted).ok_or("Total supply overflow")?)
+}
+
+/// Decide whether a block interval should produce a block
+fn should_produce(config: &crate::config::BlockConfig, pending: usize, idle_ticks: u64) -> bool {
+    if config.produce_when_empty || pending > 0 {
+        return true;
+    }
+    config.max_idle_blocks > 0 && idle_ticks >= config.max_idle_blocks
 }
 
 impl Blockchain {
@@ -437,111 +853,331 @@ impl Blockchain {
         };
 
         if needs_genesis {
-            let genesis = Block::genesis(
+            for alloc in &config.genesis.allocations {
+                if !Address::new(&alloc.address).is_valid() {
+                    return Err(format!("Invalid genesis allocation address: {}", alloc.address).into());
+                }
+            }
+            // Whole MVM in the config; refuse to start rather than wrap
+            let to_base = |mvm: u64, what: &str| mvm.checked_mul(100_000_000)
+                .ok_or_else(|| BoxError::from(format!("Genesis {} of {} MVM overflows u64", what, mvm)));
+            let master_balance = to_base(config.genesis.master_balance, "master_balance")?;
+            let allocations = config.genesis.allocations.iter()
+                .map(|a| Ok((a.address.as_str(), to_base(a.balance, "allocation")?)))
+                .collect::<Result<Vec<_>, BoxError>>()?;
+            let allocated = allocations.iter()
+                .try_fold(0u64, |sum, (_, amount)| sum.checked_add(*amount))
+                .ok_or("Genesis allocations overflow u64")?;
+            // The genesis block mints both
+            master_balance.checked_add(allocated).ok_or("Genesis supply overflows u64")?;
+
+            let mut genesis = Block::genesis(
                 master_address.as_str(),
-                config.genesis.master_balance * 100_000_000,
+                master_balance,
+                allocated,
             );
             
             let mut state_guard = state.write().await;
+            if let Some(keypair) = state_guard.get_keypair() {
+                genesis.sign(keypair);
+            }
             state_guard.save_block(&genesis)?;
-            state_guard.set_balance(
-                master_address.as_str(),
-                config.genesis.master_balance * 100_000_000,
-            )?;
+            state_guard.set_balance(master_address.as_str(), master_balance)?;
+            for (address, amount) in &allocations {
+                let balance = state_guard.get_balance(address)?.checked_add(*amount)
+                    .ok_or_else(|| BoxError::from(format!("Genesis balance of {} overflows u64", address)))?;
+                state_guard.set_balance(address, balance)?;
+            }
+            let supply = state_guard.get_total_supply()?.checked_add(allocated)
+                .ok_or("Genesis supply overflows u64")?;
+            state_guard.set_total_supply(supply)?;
             state_guard.set_height(0)?;
+            state_guard.record_balance_history(0)?;
             
             tracing::info!("🌍 Genesis block created");
             tracing::info!("💰 Master balance: {} MVM", config.genesis.master_balance);
+            if !config.genesis.allocations.is_empty() {
+                tracing::info!("💰 Genesis allocations: {} accounts, {} MVM", config.genesis.allocations.len(), allocated / 100_000_000);
+            }
         }
 
         Ok(Blockchain {
+            mempool: Mempool::with_max_size(config.block.mempool_size)
+                .with_replace_bump(config.block.replace_bump_percent),
             config,
             state,
-            mempool: Mempool::new(),
             master_address,
             mvm,
+            idle_ticks: 0,
+            tx_notifier: None,
+            event_notifier: None,
+            service_nodes: ServiceNodes::default(),
+            last_block_produced_at: Utc::now().timestamp(),
+            last_tick_at: Utc::now().timestamp(),
         })
     }
 
-    pub async fn produce_block(&mut self) -> Result<Block, BoxError> {
+    /// Walk the stored chain from genesis (or the oldest unpruned block) to the
+    /// tip, checking each block's hash and its link to the previous one. A
+    /// break is an error unless `allow_truncate`, in which case the chain is
+    /// truncated back to the last good block. The removed blocks' effects on
+    /// account and contract state are not undone. Returns the verified height.
+    pub async fn verify_chain(&self, allow_truncate: bool) -> Result<u64, BoxError> {
+        let mut state_guard = self.state.write().await;
+        let tip = state_guard.get_height()?;
+        let pruned = state_guard.get_pruned_height()?;
+
+        // Pruned blocks are gone, so the walk can't link across that gap
+        let first = if pruned > 0 { pruned + 1 } else { 0 };
+        let mut prev: Option<Block> = None;
+        let mut broken_at = None;
+        for height in first..=tip {
+            let block = match state_guard.get_block(height) {
+                Ok(Some(b)) => b,
+                _ => { broken_at = Some((height, "missing or unreadable")); break; }
+            };
+            if block.height != height || !block.is_valid() {
+                broken_at = Some((height, "hash mismatch"));
+                break;
+            }
+            if let Some(ref p) = prev {
+                if block.prev_hash != p.hash {
+                    broken_at = Some((height, "prev_hash does not link"));
+                    break;
+                }
+            }
+            prev = Some(block);
+        }
+
+        match broken_at {
+            None => Ok(tip),
+            Some((0, reason)) => Err(format!("Genesis block is invalid: {}", reason).into()),
+            Some((height, reason)) if height == first => {
+                Err(format!("Oldest stored block #{} is invalid: {}", height, reason).into())
+            }
+            Some((height, reason)) if !allow_truncate => {
+                tracing::error!("❌ Chain broken at block #{} ({})", height, reason);
+                Err(format!(
+                    "Chain broken at block #{} ({}). Pass --allow-truncate to drop blocks above #{}; \
+                     their effects on account and contract state will remain",
+                    height, reason, height - 1,
+                ).into())
+            }
+            Some((height, reason)) => {
+                let good = height - 1;
+                let removed = state_guard.truncate_blocks(good)?;
+                tracing::error!("❌ Chain broken at block #{} ({}); truncated {} blocks back to #{}. \
+                    Their effects on account and contract state remain", height, reason, removed, good);
+                Ok(good)
+            }
+        }
+    }
+
+    /// Called once per block interval. Produces a block unless the mempool is
+    /// empty and empty blocks are disabled (heartbeat blocks still apply).
+    pub async fn try_produce_block(&mut self) -> Result<Option<Block>, BoxError> {
+        self.last_tick_at = Utc::now().timestamp();
+        let expired = self.mempool.remove_expired(self.last_tick_at);
+        if expired > 0 {
+            tracing::info!("🗑️ Dropped {} expired txs from mempool", expired);
+        }
+        let next_height = self.state.read().await.get_height()? + 1;
+        let timestamp = Utc::now().timestamp();
+        if !self.is_validator_for(next_height, timestamp).await? {
+            return Ok(None);
+        }
+        if !should_produce(&self.config.block, self.mempool.len(), self.idle_ticks) {
+            self.idle_ticks += 1;
+            return Ok(None);
+        }
+        self.idle_ticks = 0;
+        self.produce_block_at(timestamp).await.map(Some)
+    }
+
+    /// Whether this node's key may produce the block at `height` stamped
+    /// `timestamp`: always without a validator set, otherwise on its
+    /// round-robin turn or one passed to it by missed turns
+    pub async fn is_validator_for(&self, height: u64, timestamp: i64) -> Result<bool, BoxError> {
+        if self.config.validators.addresses.is_empty() {
+            return Ok(true);
+        }
+        let prev = self.state.read().await.get_block(height.saturating_sub(1))?
+            .ok_or("previous block missing")?;
+        let elapsed = timestamp.saturating_sub(prev.timestamp).max(0) as u64;
+        Ok(self.config.validators.validator_at(height, elapsed) == Some(self.master_address.as_str()))
+    }
+
+    /// Produce the next block stamped `timestamp`, the time its turn was checked at
+    pub async fn produce_block_at(&mut self, timestamp: i64) -> Result<Block, BoxError> {
         let state_guard = self.state.read().await;
         let current_height = state_guard.get_height()?;
         let prev_block = state_guard.get_block(current_height)?.unwrap();
         drop(state_guard);
 
+        let (validator_reward, service_pool) = self.reward_split()?;
+        let nodes: Vec<ServiceNode> = self.service_nodes.read().await.values().cloned().collect();
+        let service_rewards = service_rewards(&nodes, &self.config.rewards, service_pool);
+        
+        // Unclaimed ranks aren't minted
+        let rewards = BlockRewards {
+            validator_reward,
+            total_minted: service_rewards.iter()
+                .try_fold(validator_reward, |sum, r| sum.checked_add(r.amount))
+                .ok_or("Block reward overflow")?,
+            service_rewards,
+        };
+
         // Get transactions from mempool (properly ordered by sender+nonce)
         let txs = self.mempool.drain_for_block(self.config.block.max_txs_per_block);
         
         tracing::debug!("📦 Processing {} transactions from mempool", txs.len());
 
-        let mut executed_txs = Vec::new();
-        for mut tx in txs {
-            match self.execute_transaction(&mut tx).await {
-                Ok(_) => {
-                    tx.status = TxStatus::Success;
-                    tracing::debug!("✅ TX {} success", &tx.hash[..8]);
-                }
-                Err(e) => {
-                    tx.status = TxStatus::Failed;
-                    tx.error = Some(e.to_string());
-                    tracing::debug!("❌ TX {} failed: {}", &tx.hash[..8], e);
+        // Contracts see the new block's height and timestamp, not call-time values
+        let new_height = current_height + 1;
+        self.mvm.set_block(Some(BlockEnv { height: new_height, timestamp: timestamp as u64 }));
+
+        // The txs' effects, the block, its indexes, height and reward land in
+        // one write, so a crash can't leave state the chain doesn't record
+        let mut state_guard = self.state.write().await;
+        let result = state_guard.write_atomically(|st| {
+            let mut executed_txs = Vec::new();
+            for mut tx in txs {
+                match self.execute_transaction_in(st, &mut tx) {
+                    Ok(_) => {
+                        tx.status = TxStatus::Success;
+                        tracing::debug!(height = new_height, hash = %tx.hash, "✅ TX success");
+                    }
+                    Err(e) => {
+                        tx.status = TxStatus::Failed;
+                        tx.error = Some(e.to_string());
+                        tracing::debug!(height = new_height, hash = %tx.hash, error = %e, "❌ TX failed");
+                    }
                 }
+                executed_txs.push(tx);
             }
-            executed_txs.push(tx);
+
+            let mut block = Block::new(
+                new_height,
+                &prev_block.hash,
+                self.master_address.as_str(),
+                executed_txs,
+                rewards,
+                self.config.block.gas_limit,
+            );
+            block.timestamp = timestamp;
+            block.hash = block.calculate_hash();
+            block.sign(st.get_keypair().ok_or("Master keypair not loaded")?);
+
+            store_block(st, &block)?;
+            Ok(block)
+        });
+        self.mvm.set_block(None);
+        let block = result?;
+        self.publish_events(&state_guard, &block);
+
+        let pruned = state_guard.prune(new_height, self.config.pruning.keep_blocks)?;
+        if pruned > 0 {
+            tracing::debug!("🧹 Pruned {} old blocks", pruned);
         }
 
-        let block_reward = self.config.rewards.block_reward * 100_000_000;
-        let validator_reward = (block_reward * self.config.rewards.validator_percent) / 100;
-        
-        let rewards = BlockRewards {
-            validator_reward,
-            service_rewards: vec![],
-            total_minted: block_reward,
-        };
+        self.last_block_produced_at = Utc::now().timestamp();
 
-        let new_height = current_height + 1;
-        let block = Block::new(
-            new_height,
-            &prev_block.hash,
-            self.master_address.as_str(),
-            executed_txs,
-            rewards.clone(),
-            self.config.block.gas_limit,
-        );
+        Ok(block)
+    }
+
+    /// Apply a block produced by another node on top of the local tip. Every
+    /// tx is replayed through `apply_transaction` and must end the way the
+    /// block records it; only then are the block and its rewards stored, all
+    /// in one write. A mismatch anywhere leaves local state untouched.
+    pub async fn import_block(&mut self, block: &Block) -> Result<(), BoxError> {
+        self.check_rewards(&block.rewards)?;
 
+        self.mvm.set_block(Some(BlockEnv { height: block.height, timestamp: block.timestamp as u64 }));
         let mut state_guard = self.state.write().await;
-        state_guard.save_block(&block)?;
-        state_guard.set_height(new_height)?;
-        
-        // Index transactions for address lookup
-        for tx in &block.transactions {
-            state_guard.index_transaction(tx, new_height)?;
+        let result = state_guard.write_atomically(|st| {
+            let tip = st.get_block(st.get_height()?)?.ok_or("local tip missing")?;
+            if block.height != tip.height + 1 || block.prev_hash != tip.hash {
+                return Err(format!("block #{} does not extend local tip #{}", block.height, tip.height).into());
+            }
+            for recorded in &block.transactions {
+                self.replay_transaction(st, recorded)?;
+            }
+            store_block(st, block)
+        });
+        if result.is_ok() {
+            self.publish_events(&state_guard, block);
         }
-        
-        let current_balance = state_guard.get_balance(self.master_address.as_str())?;
-        state_guard.set_balance(
-            self.master_address.as_str(),
-            current_balance + validator_reward,
-        )?;
+        drop(state_guard);
+        self.mvm.set_block(None);
+        result
+    }
 
-        let current_supply = state_guard.get_total_supply()?;
-        state_guard.set_total_supply(current_supply + rewards.total_minted)?;
+    /// Send the contract events of a committed block's txs to the event notifier
+    fn publish_events(&self, state: &State, block: &Block) {
+        let Some(notifier) = &self.event_notifier else { return };
+        for tx in block.transactions.iter().filter(|tx| tx.status == TxStatus::Success) {
+            for event in state.get_tx_events(&tx.hash).unwrap_or_default() {
+                let _ = notifier.send(event);
+            }
+        }
+    }
 
-        Ok(block)
+    /// Run a tx from a peer block and check it ends as recorded
+    fn replay_transaction(&self, st: &mut State, recorded: &Transaction) -> Result<(), BoxError> {
+        let mut tx = recorded.clone();
+        tx.status = TxStatus::Pending;
+        tx.error = None;
+        tx.result = None;
+        let outcome = match tx.verify_signature() {
+            Ok(true) => self.apply_transaction(st, &mut tx),
+            Ok(false) => Err(TxError::InvalidSignature { message: "Signature does not match sender address".to_string() }),
+            Err(e) => Err(TxError::InvalidSignature { message: e.to_string() }),
+        };
+        let status = if outcome.is_ok() { TxStatus::Success } else { TxStatus::Failed };
+        if status != recorded.status || tx.gas_used != recorded.gas_used || tx.to != recorded.to || tx.result != recorded.result {
+            return Err(format!("tx {} replays as {:?} using {} gas, but the block records {:?} using {}",
+                recorded.hash, status, tx.gas_used, recorded.status, recorded.gas_used).into());
+        }
+        Ok(())
+    }
+
+    /// Base units each block pays its validator, and the pool its service
+    /// rewards share
+    fn reward_split(&self) -> Result<(u64, u64), BoxError> {
+        let rewards = &self.config.rewards;
+        let block_reward = rewards.block_reward.checked_mul(100_000_000).ok_or("Block reward overflow")?;
+        let share = |percent: u64| block_reward.checked_mul(percent).map(|v| v / 100).ok_or("Block reward overflow");
+        Ok((share(rewards.validator_percent)?, share(rewards.service_pool_percent)?))
+    }
+
+    /// A peer block may mint no more than this node would: the configured
+    /// validator reward, and service rewards within the service pool
+    fn check_rewards(&self, rewards: &BlockRewards) -> Result<(), BoxError> {
+        let (validator_reward, service_pool) = self.reward_split()?;
+        let service_total = rewards.service_rewards.iter()
+            .try_fold(0u64, |sum, r| sum.checked_add(r.amount))
+            .ok_or("Service reward overflow")?;
+        if rewards.validator_reward != validator_reward {
+            return Err(format!("validator reward {} is not the configured {}", rewards.validator_reward, validator_reward).into());
+        }
+        if service_total > service_pool {
+            return Err(format!("service rewards {} exceed the pool of {}", service_total, service_pool).into());
+        }
+        if validator_reward.checked_add(service_total) != Some(rewards.total_minted) {
+            return Err(format!("total_minted {} does not add up", rewards.total_minted).into());
+        }
+        Ok(())
     }
 
+    /// `execute_transaction_in` under its own state write lock
+    #[cfg(test)]
     async fn execute_transaction(&mut self, tx: &mut Transaction) -> Result<(), TxError> {
-        // Set gas based on tx type
-        tx.gas_used = match &tx.tx_type {
-            TxType::Transfer => 21000,
-            TxType::Deploy => 200000,
-            TxType::Call => 50000,
-            TxType::CreateToken => 100000,
-            TxType::TransferToken => 65000,
-            TxType::DeployContract => 150000,
-            TxType::CallContract => 50000,  // Base, actual depends on method
-        };
+        let mut state_guard = self.state.write().await;
+        self.execute_transaction_in(&mut state_guard, tx)
+    }
 
+    /// Check `tx`'s signature, apply it to `state` and record the balances it moved
+    fn execute_transaction_in(&self, state: &mut State, tx: &mut Transaction) -> Result<(), TxError> {
         // Verify signature
         match tx.verify_signature() {
             Ok(true) => {},
@@ -553,39 +1189,118 @@ impl Blockchain {
             }),
         }
 
-        // Verify nonce
-        let expected_nonce = {
-            let state_guard = self.state.read().await;
-            state_guard.get_nonce(&tx.from).unwrap_or(0)
+        // Snapshot both sides so the stored tx records what it actually moved,
+        // including fees kept by a tx that fails partway
+        let (recipient, token) = match &tx.data {
+            Some(TxData::TransferToken { contract, to, .. }) => (Some(to.clone()), Some(contract.clone())),
+            Some(TxData::CallContract { contract, .. }) => {
+                let token = state.get_mosh_contract(contract).ok().flatten().and_then(|c| c.token);
+                (Some(contract.clone()), token)
+            }
+            _ if tx.tx_type == TxType::Transfer => (tx.to.clone(), None),
+            _ => (None, None),
         };
+        let from_before = balances_of(state, &tx.from, token.as_deref());
+        let to_before = recipient.as_deref().map(|to| balances_of(state, to, token.as_deref()));
+
+        let result = self.apply_transaction(state, tx);
+
+        let from_after = balances_of(state, &tx.from, token.as_deref());
+        tx.from_delta = Some(balance_delta(&tx.from, token.as_deref(), from_before, from_after));
+        tx.to_delta = recipient.as_deref().zip(to_before).map(|(to, before)| {
+            balance_delta(to, token.as_deref(), before, balances_of(state, to, token.as_deref()))
+        });
+        result
+    }
+
+    /// Run `tx` against `state`: nonce check, fees and the type-specific
+    /// effects, without checking its signature. A tx that fails is rolled
+    /// back to just its gas fee. Writes go straight to `state`, so dry runs
+    /// stage them and discard afterwards.
+    pub fn apply_transaction(&self, state: &mut State, tx: &mut Transaction) -> Result<(), TxError> {
+        // Set gas based on tx type
+        tx.gas_used = tx.tx_type.base_gas();
+
+        // Verify nonce
+        let expected_nonce = state.get_nonce(&tx.from).unwrap_or(0);
 
         if tx.nonce != expected_nonce {
             return Err(TxError::InvalidNonce { expected: expected_nonce, got: tx.nonce });
         }
 
         // Calculate gas fee
-        let gas_fee = tx.gas_used * tx.gas_price;
+        let gas_fee = tx.gas_used.checked_mul(tx.gas_price)
+            .ok_or_else(|| TxError::InternalError { message: "Gas fee overflow".to_string() })?;
 
-        // Check balance for gas fee (+ value for transfers)
-        let total_cost = match &tx.tx_type {
-            TxType::Transfer => tx.value + gas_fee,
-            _ => gas_fee,
-        };
+        // A failed tx keeps none of its effects, only the gas it paid for
+        let mut gas_paid = 0;
+        state.savepoint();
+        let result = self.apply_effects(state, tx, gas_fee, &mut gas_paid);
+        if result.is_ok() {
+            return state.release_savepoint().map_err(|e| TxError::InternalError { message: e.to_string() });
+        }
+        state.rollback_savepoint();
+        if gas_paid > 0 {
+            debit_balance(state, &tx.from, gas_paid)?;
+        }
+        result
+    }
 
-        {
-            let state_guard = self.state.read().await;
-            let from_balance = state_guard.get_balance(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
-            if from_balance < total_cost {
-                return Err(TxError::InsufficientBalance { required: total_cost, available: from_balance });
+    /// Move `native_value` from `from` to `contract` and run the call, under
+    /// one savepoint that is released only if the call succeeds
+    #[allow(clippy::too_many_arguments)]
+    fn call_with_native(
+        &self,
+        state: &mut State,
+        from: &str,
+        contract: &str,
+        method: &str,
+        args: &[String],
+        amount: u64,
+        native_value: u64,
+        gas_limit: u64,
+    ) -> Result<crate::mvm::CallResult, TxError> {
+        state.savepoint();
+        let result = self.run_call_with_native(state, from, contract, method, args, amount, native_value, gas_limit);
+        match &result {
+            Ok(r) if r.success => state.release_savepoint().map_err(|e| TxError::InternalError { message: e.to_string() })?,
+            _ => state.rollback_savepoint(),
+        }
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn run_call_with_native(
+        &self,
+        state: &mut State,
+        from: &str,
+        contract: &str,
+        method: &str,
+        args: &[String],
+        amount: u64,
+        native_value: u64,
+        gas_limit: u64,
+    ) -> Result<crate::mvm::CallResult, TxError> {
+        // Attached native MVM reaches the contract before its code runs
+        if native_value > 0 {
+            if state.get_mosh_contract(contract).map_err(|e| TxError::InternalError { message: e.to_string() })?.is_none() {
+                return Err(TxError::ContractError { message: "Contract not found".to_string() });
             }
+            debit_balance(state, from, native_value)?;
+            credit_balance(state, contract, native_value)?;
         }
+        self.mvm.call_with_value(state, from, contract, method, args.to_vec(), amount, native_value, gas_limit)
+            .map_err(|e| TxError::ContractError { message: e.to_string() })
+    }
 
-        // Execute transaction based on type
+    /// The type-specific part of `apply_transaction`. Sets `gas_paid` to the
+    /// gas fee taken from the sender once it has been debited.
+    fn apply_effects(&self, state: &mut State, tx: &mut Transaction, gas_fee: u64, gas_paid: &mut u64) -> Result<(), TxError> {
+        // Execute transaction based on type. The caller holds the state write
+        // lock throughout, so nothing can change a balance between its check
+        // and its debit.
         match &tx.tx_type {
             TxType::Transfer => {
-                let mut state_guard = self.state.write().await;
-                let from_balance = state_guard.get_balance(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
-
                 let to = tx.to.as_ref().ok_or_else(|| TxError::InvalidRecipient { 
                     message: "Missing recipient address".to_string() 
                 })?;
@@ -595,73 +1310,96 @@ impl Blockchain {
                     return Err(TxError::InvalidAddress { address: to.clone() });
                 }
 
-                let to_balance = state_guard.get_balance(to).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                let total_cost = tx.value.checked_add(gas_fee)
+                    .ok_or_else(|| TxError::InternalError { message: "Transfer amount overflow".to_string() })?;
+                // Refuse up front if the recipient's balance would overflow
+                let to_balance = state.get_balance(to).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                if to != &tx.from && to_balance.checked_add(tx.value).is_none() {
+                    return Err(TxError::InternalError { message: format!("Balance overflow for {}", to) });
+                }
                 
                 // Deduct value + gas fee from sender
-                state_guard.set_balance(&tx.from, from_balance - total_cost).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                debit_balance(state, &tx.from, total_cost)?;
+                *gas_paid = gas_fee;
                 // Add value to recipient
-                state_guard.set_balance(to, to_balance + tx.value).map_err(|e| TxError::InternalError { message: e.to_string() })?;
-                state_guard.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                credit_balance(state, to, tx.value)?;
+                state.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
             }
             TxType::Deploy => {
-                let mut state_guard = self.state.write().await;
-                let from_balance = state_guard.get_balance(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
-                
-                // Deduct gas fee
-                state_guard.set_balance(&tx.from, from_balance - gas_fee).map_err(|e| TxError::InternalError { message: e.to_string() })?;
-                state_guard.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                if let Some(TxData::Deploy { code, name }) = &tx.data {
+                    // Deduct gas fee
+                    debit_balance(state, &tx.from, gas_fee)?;
+                    *gas_paid = gas_fee;
+
+                    // Store the raw code contract
+                    let contract_addr = self.mvm.deploy_code(state, &tx.from, name, code, tx.nonce)
+                        .map_err(|e| TxError::ContractError { message: e.to_string() })?;
+
+                    tx.to = Some(contract_addr);
+                    state.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                } else {
+                    return Err(TxError::InvalidTxType { tx_type: "deploy without code".to_string() });
+                }
             }
             TxType::Call => {
                 if let Some(TxData::Call { contract, method, args }) = &tx.data {
-                    let mut state_guard = self.state.write().await;
-                    let from_balance = state_guard.get_balance(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
-                    
                     // Deduct gas fee
-                    state_guard.set_balance(&tx.from, from_balance - gas_fee).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                    debit_balance(state, &tx.from, gas_fee)?;
+                    *gas_paid = gas_fee;
                     
-                    self.mvm.execute_call(&mut state_guard, contract, method, args)
+                    self.mvm.execute_call(state, contract, method, args, tx.gas_limit)
                         .map_err(|e| TxError::ContractError { message: e.to_string() })?;
-                    state_guard.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                    state.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
                 }
             }
             TxType::CreateToken => {
-                if let Some(TxData::CreateToken { name, symbol, total_supply }) = &tx.data {
-                    let mut state_guard = self.state.write().await;
-                    let from_balance = state_guard.get_balance(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
-                    
-                    // Deduct gas fee
-                    state_guard.set_balance(&tx.from, from_balance - gas_fee).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                if let Some(TxData::CreateToken { name, symbol, total_supply, decimals, logo_uri, description }) = &tx.data {
+                    // Deduct gas fee and the token creation fee
+                    debit_with_fee(state, &tx.from, gas_fee, self.config.token.creation_fee, self.config.treasury.address.as_deref())?;
+                    *gas_paid = gas_fee;
+                    crate::standards::validate_token_metadata(logo_uri.as_deref(), description.as_deref())
+                        .map_err(|message| TxError::ContractError { message })?;
                     
                     let contract_address = crate::standards::create_mvm20_token(
-                        &mut state_guard,
+                        state,
                         &tx.from,
                         name,
                         symbol,
                         *total_supply,
+                        decimals.unwrap_or(crate::standards::DEFAULT_TOKEN_DECIMALS),
+                        tx.nonce,
+                        self.mvm.block_env(state).height,
                     ).map_err(|e| TxError::ContractError { message: e.to_string() })?;
+                    if logo_uri.is_some() || description.is_some() {
+                        crate::standards::update_token_metadata(state, &contract_address, &tx.from, logo_uri.as_deref(), description.as_deref())
+                            .map_err(|e| TxError::ContractError { message: e.to_string() })?;
+                    }
                     tx.to = Some(contract_address);
-                    state_guard.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                    state.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
                 }
             }
             TxType::TransferToken => {
-                if let Some(TxData::TransferToken { contract, to, amount }) = &tx.data {
-                    let mut state_guard = self.state.write().await;
-                    let from_balance = state_guard.get_balance(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
-                    
+                if let Some(TxData::TransferToken { contract, to, amount, amount_decimal }) = &tx.data {
                     // Deduct gas fee
-                    state_guard.set_balance(&tx.from, from_balance - gas_fee).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                    debit_balance(state, &tx.from, gas_fee)?;
+                    *gas_paid = gas_fee;
                     
                     // Check token exists
-                    let token = state_guard.get_token(contract)
+                    let token = state.get_token(contract)
                         .map_err(|e| TxError::InternalError { message: e.to_string() })?
                         .ok_or_else(|| TxError::TokenNotFound { contract: contract.clone() })?;
+                    let amount = match amount_decimal {
+                        Some(text) => crate::standards::parse_decimal_amount(text, token.decimals)
+                            .map_err(|message| TxError::ContractError { message })?,
+                        None => *amount,
+                    };
                     
                     // Check token balance
-                    let token_balance = state_guard.get_token_balance(contract, &tx.from)
+                    let token_balance = state.get_token_balance(contract, &tx.from)
                         .map_err(|e| TxError::InternalError { message: e.to_string() })?;
                     
-                    if token_balance < *amount {
-                        return Err(TxError::InsufficientTokenBalance { required: *amount, available: token_balance });
+                    if token_balance < amount {
+                        return Err(TxError::InsufficientTokenBalance { required: amount, available: token_balance });
                     }
                     
                     // Validate recipient
@@ -671,60 +1409,75 @@ impl Blockchain {
                     }
 
                     crate::standards::transfer_mvm20(
-                        &mut state_guard,
+                        state,
                         contract,
                         &tx.from,
                         to,
-                        *amount,
+                        amount,
                     ).map_err(|e| TxError::ContractError { message: e.to_string() })?;
                     
-                    state_guard.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                    state.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
                     
                     drop(token);
                 }
             }
             TxType::DeployContract => {
                 if let Some(TxData::DeployContract { name, token, variables, mappings, functions }) = &tx.data {
-                    let mut state_guard = self.state.write().await;
-                    let from_balance = state_guard.get_balance(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
-                    
-                    // Deduct gas fee
-                    state_guard.set_balance(&tx.from, from_balance - gas_fee).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                    // Deduct gas fee and the deploy fee
+                    debit_with_fee(state, &tx.from, gas_fee, self.config.contract.deploy_fee, self.config.treasury.address.as_deref())?;
+                    *gas_paid = gas_fee;
                     
                     // Deploy Mosh contract
                     let contract_addr = self.mvm.deploy(
-                        &mut state_guard,
+                        state,
                         &tx.from,
                         name,
                         token.clone(),
                         variables.clone(),
                         mappings.clone(),
                         functions.clone(),
+                        tx.nonce,
                     ).map_err(|e| TxError::ContractError { message: e.to_string() })?;
                     
                     tx.to = Some(contract_addr);
-                    state_guard.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                    state.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
                 }
             }
             TxType::CallContract => {
-                if let Some(TxData::CallContract { contract, method, args, amount }) = &tx.data {
-                    let mut state_guard = self.state.write().await;
-                    let from_balance = state_guard.get_balance(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                if let Some(TxData::CallContract { contract, method, args, amount, native_value }) = &tx.data {
+                    // Pre-charge the base gas fee, settled once the VM reports real usage
+                    debit_balance(state, &tx.from, gas_fee)?;
+                    *gas_paid = gas_fee;
                     
-                    // Deduct base gas fee
-                    state_guard.set_balance(&tx.from, from_balance - gas_fee).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                    // The attached value and the call commit or roll back together,
+                    // so a failed call leaves no trace but its gas
+                    let result = self.call_with_native(state, &tx.from, contract, method, args, amount.unwrap_or(0), native_value.unwrap_or(0), tx.gas_limit)?;
                     
-                    // Call Mosh contract
-                    let result = self.mvm.call(
-                        &mut state_guard,
-                        &tx.from,
-                        contract,
-                        method,
-                        args.clone(),
-                        amount.unwrap_or(0),
-                    ).map_err(|e| TxError::ContractError { message: e.to_string() })?;
+                    // An out-of-gas call still pays for the full limit
+                    let out_of_gas = result.error.as_deref() == Some(crate::mvm::OUT_OF_GAS);
+                    let charged_gas = if out_of_gas { tx.gas_limit } else { result.gas_used };
+                    
+                    // The base fee was only an estimate: charge the rest of the
+                    // real cost, or refund what wasn't used
+                    let actual_fee = charged_gas.checked_mul(tx.gas_price)
+                        .ok_or_else(|| TxError::InternalError { message: "Gas fee overflow".to_string() })?;
+                    if actual_fee > gas_fee {
+                        debit_balance(state, &tx.from, actual_fee - gas_fee).map_err(|e| match e {
+                            TxError::InsufficientBalance { available, .. } => TxError::InsufficientBalance {
+                                required: actual_fee,
+                                available: available.saturating_add(gas_fee),
+                            },
+                            other => other,
+                        })?;
+                    } else {
+                        credit_balance(state, &tx.from, gas_fee - actual_fee)?;
+                    }
+                    *gas_paid = actual_fee;
+                    tx.gas_used = charged_gas;
                     
-                    tx.gas_used = result.gas_used;
+                    if out_of_gas {
+                        return Err(TxError::GasExceeded { limit: tx.gas_limit, used: result.gas_used });
+                    }
                     
                     if !result.success {
                         return Err(TxError::ContractError { 
@@ -732,8 +1485,53 @@ impl Blockchain {
                         });
                     }
                     
+                    tx.result = result.data.clone();
+                    
+                    // Published once the block holding this tx is committed
+                    if !result.events.is_empty() {
+                        state.save_tx_events(&tx.hash, &result.events).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                    }
+                    
                     tx.to = Some(contract.clone());
-                    state_guard.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                    state.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                }
+            }
+            TxType::UpdateTokenMetadata => {
+                if let Some(TxData::UpdateTokenMetadata { contract, logo_uri, description }) = &tx.data {
+                    // Deduct gas fee
+                    debit_balance(state, &tx.from, gas_fee)?;
+                    *gas_paid = gas_fee;
+
+                    if state.get_token(contract).map_err(|e| TxError::InternalError { message: e.to_string() })?.is_none() {
+                        return Err(TxError::TokenNotFound { contract: contract.clone() });
+                    }
+                    crate::standards::update_token_metadata(state, contract, &tx.from, logo_uri.as_deref(), description.as_deref())
+                        .map_err(|e| TxError::ContractError { message: e.to_string() })?;
+
+                    tx.to = Some(contract.clone());
+                    state.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                } else {
+                    return Err(TxError::InvalidTxType { tx_type: "update_token_metadata without data".to_string() });
+                }
+            }
+            TxType::RegisterName => {
+                if let Some(TxData::RegisterName { name }) = &tx.data {
+                    // Deduct gas fee
+                    debit_balance(state, &tx.from, gas_fee)?;
+                    *gas_paid = gas_fee;
+
+                    crate::names::validate_name(name).map_err(|message| TxError::ContractError { message })?;
+                    if let Some(owner) = state.resolve_name(name).map_err(|e| TxError::InternalError { message: e.to_string() })? {
+                        return Err(TxError::ContractError { message: format!("Name \"{}\" is already registered to {}", name, owner) });
+                    }
+                    // The registration fee is only taken once the name is known to be free
+                    debit_with_fee(state, &tx.from, 0, self.config.names.registration_fee, self.config.treasury.address.as_deref())?;
+                    crate::names::register_name(state, name, &tx.from)
+                        .map_err(|e| TxError::ContractError { message: e.to_string() })?;
+
+                    state.increment_nonce(&tx.from).map_err(|e| TxError::InternalError { message: e.to_string() })?;
+                } else {
+                    return Err(TxError::InvalidTxType { tx_type: "register_name without data".to_string() });
                 }
             }
         }
@@ -741,29 +1539,86 @@ impl Blockchain {
         Ok(())
     }
 
-    pub fn add_transaction(&mut self, tx: Transaction) -> Result<String, BoxError> {
+    /// Add a tx to the mempool, replacing a pending tx with the same sender
+    /// and nonce if it bids enough gas price (see `Mempool::replace`)
+    pub fn add_transaction(&mut self, tx: Transaction) -> Result<MempoolInsert, BoxError> {
         let hash = tx.hash.clone();
+        if tx.gas_price < self.config.block.min_gas_price {
+            return Err(format!("Gas price {} is below the minimum of {}", tx.gas_price, self.config.block.min_gas_price).into());
+        }
         
-        // Add to mempool (handles duplicate checking)
-        match self.mempool.add(tx) {
-            Ok(true) => {
-                tracing::debug!("📥 TX {} added to mempool (total: {})", &hash[..8], self.mempool.len());
-                Ok(hash)
-            }
-            Ok(false) => {
-                Err("Transaction already in mempool (duplicate hash)".into())
-            }
-            Err(e) => {
-                Err(e.into())
+        let inserted = if self.mempool.has_pending_nonce(&tx.from, tx.nonce) {
+            let old = self.mempool.replace(tx.clone())?;
+            tracing::debug!("🔁 TX {} replaced {} in mempool", &hash[..8], &old.hash[..8]);
+            MempoolInsert::Replaced { old_hash: old.hash }
+        } else {
+            // Add to mempool (handles duplicate checking)
+            match self.mempool.add(tx.clone()) {
+                Ok(true) => {
+                    tracing::debug!("📥 TX {} added to mempool (total: {})", &hash[..8], self.mempool.len());
+                    MempoolInsert::Added
+                }
+                Ok(false) => {
+                    return Err("Transaction already in mempool (duplicate hash)".into());
+                }
+                Err(e) => {
+                    return Err(e.into());
+                }
             }
+        };
+        Ok(inserted)
+    }
+
+    /// Tell mempool subscribers about `tx`. Callers announce a tx only once
+    /// its submission can no longer be rolled back, so the feed never shows
+    /// a tx that was taken out again.
+    pub fn announce_transaction(&self, tx: &Transaction) {
+        if let Some(notifier) = &self.tx_notifier {
+            let _ = notifier.send(tx.clone());
         }
     }
     
+    /// Publish newly accepted mempool txs on `notifier` (see `announce_transaction`)
+    pub fn set_tx_notifier(&mut self, notifier: broadcast::Sender<Transaction>) {
+        self.tx_notifier = Some(notifier);
+    }
+
+    /// Publish contract events on `notifier` as the blocks holding them are committed
+    pub fn set_event_notifier(&mut self, notifier: broadcast::Sender<ContractEvent>) {
+        self.event_notifier = Some(notifier);
+    }
+
+    /// Pay the service pool to the nodes in `nodes` (see `StarNetwork::peer_stats`)
+    pub fn set_service_nodes(&mut self, nodes: ServiceNodes) {
+        self.service_nodes = nodes;
+    }
+
+    /// Drop a tx from the mempool (e.g. once a synced block includes it)
+    pub fn remove_transaction(&mut self, hash: &str) -> Option<Transaction> {
+        self.mempool.remove(hash)
+    }
+    
+    /// Read-only view of the mempool
+    pub fn mempool(&self) -> &Mempool {
+        &self.mempool
+    }
+
+    /// The mempool is at `block.mempool_size`; new txs only get in by
+    /// outbidding a pending one (see `Mempool::would_evict_for`)
+    pub fn mempool_is_full(&self) -> bool {
+        self.mempool.is_full()
+    }
+
     /// Get pending transactions count
     pub fn pending_count(&self) -> usize {
         self.mempool.len()
     }
     
+    /// Whether `hash` is waiting in the mempool
+    pub fn is_pending(&self, hash: &str) -> bool {
+        self.mempool.contains(hash)
+    }
+
     /// Get pending transactions for address
     pub fn get_pending_txs(&self, address: &str) -> Vec<Transaction> {
         self.mempool.get_by_sender(address)
@@ -797,4 +1652,666 @@ impl Blockchain {
         let state_guard = self.state.read().await;
         Ok(state_guard.get_block(height)?)
     }
-}
\ No newline at end of file
+}
+
+/// An unsigned transfer from `from`, with no recipient or value
+#[cfg(test)]
+pub(crate) fn pending_tx(from: &str, nonce: u64, gas_price: u64) -> Transaction {
+    let mut tx = Transaction {
+        hash: String::new(),
+        tx_type: TxType::Transfer,
+        from: from.to_string(),
+        to: None,
+        value: 0,
+        gas_price,
+        gas_limit: 0,
+        gas_used: 0,
+        nonce,
+        data: None,
+        timestamp: 0,
+        signature: String::new(),
+        public_key: String::new(),
+        status: TxStatus::Pending,
+        error: None,
+        valid_until: 0,
+        sig_scheme: 0,
+        result: None,
+        from_delta: None,
+        to_delta: None,
+    };
+    tx.hash = tx.calculate_hash();
+    tx
+}
+
+/// `pending_tx` of `tx_type` carrying `data`, signed by `sender`
+#[cfg(test)]
+pub(crate) fn signed_tx(sender: &Keypair, tx_type: TxType, nonce: u64, data: Option<TxData>) -> Transaction {
+    let mut tx = pending_tx(sender.address().as_str(), nonce, DEFAULT_GAS_PRICE);
+    tx.tx_type = tx_type;
+    tx.gas_limit = DEFAULT_GAS_LIMIT;
+    tx.data = data;
+    tx.public_key = sender.public_key_hex();
+    tx.signature = sender.sign_hex(&tx.get_sign_message());
+    tx.hash = tx.calculate_hash();
+    tx
+}
+
+/// A signed transfer of `value` to `to`
+#[cfg(test)]
+pub(crate) fn signed_transfer(sender: &Keypair, to: &str, value: u64, nonce: u64) -> Transaction {
+    let mut tx = pending_tx(sender.address().as_str(), nonce, DEFAULT_GAS_PRICE);
+    tx.to = Some(to.to_string());
+    tx.value = value;
+    tx.gas_limit = DEFAULT_GAS_LIMIT;
+    tx.public_key = sender.public_key_hex();
+    tx.signature = sender.sign_hex(&tx.get_sign_message());
+    tx.hash = tx.calculate_hash();
+    tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BlockConfig;
+    use crate::state::test_state;
+
+    fn block_config(produce_when_empty: bool, max_idle_blocks: u64) -> BlockConfig {
+        BlockConfig {
+            block_time: 3,
+            gas_limit: 1_000_000,
+            max_txs_per_block: 100,
+            produce_when_empty,
+            max_idle_blocks,
+            mempool_size: 10_000,
+            replace_bump_percent: 10,
+            min_gas_price: DEFAULT_GAS_PRICE,
+            confirmations_for_final: 6,
+        }
+    }
+
+    #[test]
+    fn test_should_produce() {
+        // Always produce when empty blocks are allowed
+        assert!(should_produce(&block_config(true, 0), 0, 0));
+
+        // Skip idle intervals, but produce when txs are pending
+        let cfg = block_config(false, 0);
+        assert!(!should_produce(&cfg, 0, 100));
+        assert!(should_produce(&cfg, 1, 0));
+
+        // Heartbeat after max_idle_blocks skipped intervals
+        let cfg = block_config(false, 5);
+        assert!(!should_produce(&cfg, 0, 4));
+        assert!(should_produce(&cfg, 0, 5));
+    }
+
+    #[test]
+    fn test_service_rewards_rank_by_browsers() {
+        let config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        let node = |id: &str, browsers| ServiceNode {
+            node_id: id.to_string(),
+            address: format!("mvm1{}", id),
+            browsers,
+            peers: 0,
+        };
+        let nodes = vec![node("a", 5), node("b", 9), node("c", 0), node("d", 5), node("e", 1)];
+
+        // 50/33/17 of the pool; ties go to the lower node id, idle nodes earn nothing
+        let paid: Vec<(u8, String, u64)> = service_rewards(&nodes, &config.rewards, 1000).into_iter()
+            .map(|r| (r.rank, r.node_id, r.amount))
+            .collect();
+        assert_eq!(paid, vec![
+            (1, "b".to_string(), 500),
+            (2, "a".to_string(), 330),
+            (3, "d".to_string(), 170),
+        ]);
+        assert!(service_rewards(&[node("c", 0)], &config.rewards, 1000).is_empty());
+    }
+
+    #[test]
+    fn test_service_rewards_cap_reports_and_pay_an_address_once() {
+        let mut config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        config.rewards.max_reported_browsers = 10;
+        let node = |id: &str, address: &str, browsers| ServiceNode {
+            node_id: id.to_string(),
+            address: format!("mvm1{}", address),
+            browsers,
+            peers: 0,
+        };
+        // "z" claims far more browsers than it could serve; "a" and "b" share an address
+        let nodes = vec![node("z", "z", u32::MAX), node("a", "shared", 8), node("b", "shared", 9), node("c", "c", 10)];
+
+        let paid: Vec<(String, u32, u64)> = service_rewards(&nodes, &config.rewards, u64::MAX).into_iter()
+            .map(|r| (r.node_id, r.browsers, r.amount))
+            .collect();
+        assert_eq!(paid, vec![
+            ("c".to_string(), 10, u64::MAX / 2),
+            ("z".to_string(), 10, (u64::MAX as u128 * 33 / 100) as u64),
+            ("b".to_string(), 9, (u64::MAX as u128 * 17 / 100) as u64),
+        ]);
+    }
+
+    #[test]
+    fn test_mempool_evicts_lowest_fee() {
+        let mut pool = Mempool::with_max_size(2);
+        assert_eq!(pool.add(pending_tx("alice", 0, 10)), Ok(true));
+        assert_eq!(pool.add(pending_tx("bob", 0, 5)), Ok(true));
+
+        // Too cheap to displace anything
+        assert!(pool.is_full());
+        assert!(!pool.would_evict_for(&pending_tx("carol", 0, 5)));
+        assert!(pool.add(pending_tx("carol", 0, 5)).is_err());
+        assert!(pool.would_evict_for(&pending_tx("carol", 0, 20)));
+
+        // Higher fee evicts bob's tx, indices stay consistent
+        assert_eq!(pool.add(pending_tx("carol", 0, 20)), Ok(true));
+        assert_eq!(pool.len(), 2);
+        assert!(pool.get_by_sender("bob").is_empty());
+        assert!(!pool.by_sender.contains_key("bob"));
+    }
+
+    #[test]
+    fn test_mempool_replace_requires_fee_bump() {
+        let mut pool = Mempool::new().with_replace_bump(10);
+        let original = pending_tx("alice", 0, 100);
+        pool.add(original.clone()).unwrap();
+        pool.add(pending_tx("alice", 1, 100)).unwrap();
+
+        // Below the 10% bump, and no pending tx at nonce 5
+        assert!(pool.replace(pending_tx("alice", 0, 109)).is_err());
+        assert!(pool.replace(pending_tx("alice", 5, 500)).is_err());
+
+        let bumped = pending_tx("alice", 0, 110);
+        assert_eq!(pool.replace(bumped.clone()).unwrap().hash, original.hash);
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.contains(&original.hash));
+        assert!(pool.contains(&bumped.hash));
+        assert_eq!(pool.by_sender["alice"][&0], bumped.hash);
+    }
+
+    #[test]
+    fn test_get_pending_orders_by_fee_then_nonce() {
+        let mut pool = Mempool::new();
+        pool.add(pending_tx("alice", 0, 1)).unwrap();
+        pool.add(pending_tx("alice", 1, 50)).unwrap();
+        pool.add(pending_tx("bob", 0, 10)).unwrap();
+
+        let order: Vec<(String, u64)> = pool.get_pending(10).iter()
+            .map(|tx| (tx.from.clone(), tx.nonce))
+            .collect();
+        assert_eq!(order, vec![
+            ("bob".to_string(), 0),
+            ("alice".to_string(), 0),
+            ("alice".to_string(), 1),
+        ]);
+    }
+
+    #[test]
+    fn test_expired_txs_are_skipped_and_removed() {
+        let mut pool = Mempool::new();
+        let mut stale = pending_tx("alice", 0, 10);
+        stale.valid_until = 1;
+        pool.add(stale).unwrap();
+        pool.add(pending_tx("alice", 1, 10)).unwrap();
+        let mut fresh = pending_tx("bob", 0, 10);
+        fresh.valid_until = i64::MAX;
+        pool.add(fresh).unwrap();
+
+        // alice's later nonce waits behind the expired one
+        let order: Vec<(String, u64)> = pool.get_pending(10).iter()
+            .map(|tx| (tx.from.clone(), tx.nonce))
+            .collect();
+        assert_eq!(order, vec![("bob".to_string(), 0)]);
+
+        // Removing it frees nonce 0 for a resubmission
+        assert_eq!(pool.remove_expired(Utc::now().timestamp()), 1);
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.has_pending_nonce("alice", 0));
+    }
+
+    #[tokio::test]
+    async fn test_transfer_overflow_errors_instead_of_wrapping() {
+        let (state, _dir) = test_state("overflow");
+        let state = Arc::new(RwLock::new(state));
+        let config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        let master = Keypair::generate();
+        let mut chain = Blockchain::new(config, state.clone(), master.address()).await.unwrap();
+
+        let sender = Keypair::generate();
+        let recipient = Keypair::generate().address();
+        {
+            let mut guard = state.write().await;
+            guard.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+            guard.set_balance(recipient.as_str(), u64::MAX - 10).unwrap();
+        }
+
+        let mut tx = signed_transfer(&sender, recipient.as_str(), 100, 0);
+
+        let result = chain.execute_transaction(&mut tx).await;
+        assert!(matches!(result, Err(TxError::InternalError { .. })), "got {:?}", result.err());
+
+        // Neither side was touched
+        let guard = state.read().await;
+        assert_eq!(guard.get_balance(sender.address().as_str()).unwrap(), 1_000_000_000);
+        assert_eq!(guard.get_balance(recipient.as_str()).unwrap(), u64::MAX - 10);
+        drop(guard);
+    }
+
+    #[tokio::test]
+    async fn test_genesis_amounts_that_overflow_fail_startup() {
+        let started = |master_balance: u64, allocations: Vec<u64>, name: &str| {
+            let (state, dir) = test_state(&format!("genesis-{}", name));
+            let mut config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+            config.genesis.master_balance = master_balance;
+            config.genesis.allocations = allocations.into_iter()
+                .map(|balance| crate::config::GenesisAllocation { address: Keypair::generate().address().to_string(), balance })
+                .collect();
+            async move {
+                let state = Arc::new(RwLock::new(state));
+                let result = Blockchain::new(config, state, Keypair::generate().address()).await.map(|_| ());
+                drop(dir);
+                result.map_err(|e| e.to_string())
+            }
+        };
+
+        assert!(started(1_000_000, vec![5], "ok").await.is_ok());
+        let err = started(u64::MAX / 1_000, vec![], "master").await.unwrap_err();
+        assert!(err.contains("master_balance"), "{}", err);
+        assert!(started(1, vec![u64::MAX / 100_000_000, u64::MAX / 100_000_000], "sum").await.is_err());
+        assert!(started(u64::MAX / 100_000_000, vec![1], "supply").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_executed_tx_records_balance_deltas() {
+        let (state, _dir) = test_state("deltas");
+        let state = Arc::new(RwLock::new(state));
+        let config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        let mut chain = Blockchain::new(config, state.clone(), Keypair::generate().address()).await.unwrap();
+
+        let sender = Keypair::generate();
+        let recipient = Keypair::generate().address().to_string();
+        state.write().await.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+
+        let mut tx = signed_transfer(&sender, &recipient, 100, 0);
+        chain.execute_transaction(&mut tx).await.unwrap();
+
+        let fee = (tx.gas_used * tx.gas_price) as i128;
+        let from = tx.from_delta.unwrap();
+        assert_eq!((from.address, from.native, from.token), (sender.address().to_string(), -100 - fee, None));
+        let to = tx.to_delta.unwrap();
+        assert_eq!((to.address, to.native, to.token_amount), (recipient, 100, None));
+    }
+
+    #[tokio::test]
+    async fn test_creation_fee_goes_to_treasury_or_is_burned() {
+        let create_token = |treasury: Option<String>, tag: &str| {
+            let (state, dir) = test_state(&format!("treasury-{}", tag));
+            async move {
+                let state = Arc::new(RwLock::new(state));
+                let mut config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+                config.token.creation_fee = 1;
+                config.treasury.address = treasury;
+                let mut chain = Blockchain::new(config, state.clone(), Keypair::generate().address()).await.unwrap();
+
+                let sender = Keypair::generate();
+                {
+                    let mut guard = state.write().await;
+                    guard.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+                    guard.set_total_supply(1_000_000_000).unwrap();
+                }
+                let mut tx = signed_tx(&sender, TxType::CreateToken, 0, Some(TxData::CreateToken {
+                    name: "Gold".to_string(),
+                    symbol: "GLD".to_string(),
+                    total_supply: 1_000,
+                    decimals: None,
+                    logo_uri: None,
+                    description: None,
+                }));
+
+                chain.execute_transaction(&mut tx).await.unwrap();
+                (state, dir)
+            }
+        };
+
+        let treasury = Keypair::generate().address().to_string();
+        let (state, _dir) = create_token(Some(treasury.clone()), "paid").await;
+        let guard = state.read().await;
+        assert_eq!(guard.get_balance(&treasury).unwrap(), 100_000_000);
+        assert_eq!(guard.get_treasury_fees().unwrap(), 100_000_000);
+        assert_eq!(guard.get_total_supply().unwrap(), 1_000_000_000);
+        drop(guard);
+
+        let (state, _dir) = create_token(None, "burned").await;
+        let guard = state.read().await;
+        assert_eq!(guard.get_treasury_fees().unwrap(), 0);
+        assert_eq!(guard.get_total_supply().unwrap(), 900_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_failed_tx_keeps_only_its_gas_fee() {
+        let (state, _dir) = test_state("failed-tx");
+        let state = Arc::new(RwLock::new(state));
+        let mut config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        let treasury = Keypair::generate().address().to_string();
+        config.token.creation_fee = 1;
+        config.treasury.address = Some(treasury.clone());
+        let mut chain = Blockchain::new(config, state.clone(), Keypair::generate().address()).await.unwrap();
+
+        let sender = Keypair::generate();
+        state.write().await.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+
+        // The creation fee is taken before the metadata check fails
+        let mut tx = signed_tx(&sender, TxType::CreateToken, 0, Some(TxData::CreateToken {
+            name: "Gold".to_string(),
+            symbol: "GLD".to_string(),
+            total_supply: 1_000,
+            decimals: None,
+            logo_uri: Some("ftp://gold.png".to_string()),
+            description: None,
+        }));
+        assert!(chain.execute_transaction(&mut tx).await.is_err());
+
+        let guard = state.read().await;
+        let fee = tx.gas_used * tx.gas_price;
+        assert_eq!(guard.get_balance(sender.address().as_str()).unwrap(), 1_000_000_000 - fee);
+        assert_eq!(guard.get_balance(&treasury).unwrap(), 0);
+        assert_eq!(guard.get_treasury_fees().unwrap(), 0);
+        assert_eq!(guard.get_nonce(sender.address().as_str()).unwrap(), 0);
+        assert!(guard.get_all_tokens().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_native_value_reaches_contract_as_msg_value() {
+        let (state, _dir) = test_state("native-value");
+        let state = Arc::new(RwLock::new(state));
+        let config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        let mut chain = Blockchain::new(config, state.clone(), Keypair::generate().address()).await.unwrap();
+
+        let sender = Keypair::generate();
+        let functions: Vec<crate::mvm::FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "deposit", "modifiers": ["Payable"], "body": [
+                {"op": "map_add", "map": "deposits", "key": "msg.sender", "value": "msg.value"}
+            ]},
+            {"name": "poke", "modifiers": ["Write"], "body": []}
+        ])).unwrap();
+        let mappings: Vec<crate::mvm::MappingDef> = serde_json::from_value(serde_json::json!([
+            {"name": "deposits", "key_type": "Address", "value_type": "Uint64"}
+        ])).unwrap();
+        let contract = {
+            let mut guard = state.write().await;
+            guard.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+            chain.mvm.deploy(&mut guard, sender.address().as_str(), "Vault", None, vec![], mappings, functions, 0).unwrap()
+        };
+
+        let call = |method: &str, nonce: u64| signed_tx(&sender, TxType::CallContract, nonce, Some(TxData::CallContract {
+            contract: contract.clone(),
+            method: method.to_string(),
+            args: vec![],
+            amount: None,
+            native_value: Some(5_000),
+        }));
+
+        let mut deposit = call("deposit", 0);
+        chain.execute_transaction(&mut deposit).await.unwrap();
+        {
+            let guard = state.read().await;
+            assert_eq!(guard.get_balance(&contract).unwrap(), 5_000);
+            let stored = guard.get_mosh_map(&contract, "deposits", sender.address().as_str()).unwrap();
+            assert_eq!(stored.as_deref(), Some("5000"));
+        }
+        assert_eq!(deposit.to_delta.unwrap().native, 5_000);
+
+        // Not payable: the call fails and the value goes back
+        let mut poke = call("poke", 1);
+        assert!(chain.execute_transaction(&mut poke).await.is_err());
+        assert_eq!(state.read().await.get_balance(&contract).unwrap(), 5_000);
+    }
+
+    #[tokio::test]
+    async fn test_failed_call_undoes_native_payouts() {
+        let (state, _dir) = test_state("native-undo");
+        let state = Arc::new(RwLock::new(state));
+        let config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        let mut chain = Blockchain::new(config, state.clone(), Keypair::generate().address()).await.unwrap();
+
+        let sender = Keypair::generate();
+        let functions: Vec<crate::mvm::FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "bounce", "modifiers": ["Payable"], "body": [
+                {"op": "transfer_native", "to": "msg.sender", "amount": "msg.value"},
+                {"op": "require", "left": 1, "cmp": "==", "right": 2, "message": "nope"}
+            ]}
+        ])).unwrap();
+        let contract = {
+            let mut guard = state.write().await;
+            guard.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+            let contract = chain.mvm.deploy(&mut guard, sender.address().as_str(), "Bouncer", None, vec![], vec![], functions, 0).unwrap();
+            // Other users' deposits, which the failed call must not pay out
+            guard.set_balance(&contract, 10_000).unwrap();
+            contract
+        };
+
+        let mut tx = signed_tx(&sender, TxType::CallContract, 0, Some(TxData::CallContract {
+            contract: contract.clone(),
+            method: "bounce".to_string(),
+            args: vec![],
+            amount: None,
+            native_value: Some(5_000),
+        }));
+        let err = chain.execute_transaction(&mut tx).await.unwrap_err();
+        assert!(err.to_string().contains("Require failed"), "{}", err);
+
+        let guard = state.read().await;
+        let fee = tx.gas_used * tx.gas_price;
+        assert!(fee > 0);
+        assert_eq!(guard.get_balance(&contract).unwrap(), 10_000);
+        assert_eq!(guard.get_balance(sender.address().as_str()).unwrap(), 1_000_000_000 - fee);
+    }
+
+    #[tokio::test]
+    async fn test_event_notifier_receives_emitted_events() {
+        let (state, _dir) = test_state("event-feed");
+        let state = Arc::new(RwLock::new(state));
+        let config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        let master = state.write().await.get_or_create_master_address().unwrap();
+        let mut chain = Blockchain::new(config, state.clone(), master).await.unwrap();
+        let (notifier, mut events) = broadcast::channel(16);
+        chain.set_event_notifier(notifier);
+
+        let sender = Keypair::generate();
+        let functions: Vec<crate::mvm::FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "ping", "modifiers": ["Write"], "body": [
+                {"op": "emit", "event_name": "Pinged", "event_args": ["msg.sender"]}
+            ]}
+        ])).unwrap();
+        let contract = {
+            let mut guard = state.write().await;
+            guard.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+            chain.mvm.deploy(&mut guard, sender.address().as_str(), "Pinger", None, vec![], vec![], functions, 0).unwrap()
+        };
+        let tx = signed_tx(&sender, TxType::CallContract, 0, Some(TxData::CallContract {
+            contract: contract.clone(),
+            method: "ping".to_string(),
+            args: vec![],
+            amount: None,
+            native_value: None,
+        }));
+
+        // A dry run emits the event but publishes nothing
+        {
+            let mut guard = state.write().await;
+            guard.begin_staging();
+            chain.apply_transaction(&mut guard, &mut tx.clone()).unwrap();
+            guard.discard_staging();
+        }
+        assert!(events.try_recv().is_err());
+
+        chain.add_transaction(tx).unwrap();
+        chain.produce_block_at(Utc::now().timestamp()).await.unwrap();
+
+        let event = events.try_recv().unwrap();
+        assert_eq!((event.name.as_str(), event.contract.as_str()), ("Pinged", contract.as_str()));
+        assert_eq!(event.args, vec![serde_json::json!(sender.address().to_string())]);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_call_return_value_is_stored_on_the_tx() {
+        let (state, _dir) = test_state("call-result");
+        let state = Arc::new(RwLock::new(state));
+        let config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        let master = state.write().await.get_or_create_master_address().unwrap();
+        let mut chain = Blockchain::new(config, state.clone(), master).await.unwrap();
+
+        let sender = Keypair::generate();
+        let variables: Vec<crate::mvm::VarDef> = serde_json::from_value(serde_json::json!([
+            {"name": "count", "var_type": "Uint64", "default": "7"}
+        ])).unwrap();
+        let functions: Vec<crate::mvm::FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "bump", "modifiers": ["Write"], "body": [
+                {"op": "add", "var": "count", "value": 1},
+                {"op": "return", "value": "count"}
+            ]},
+            {"name": "fail", "modifiers": ["Write"], "body": [
+                {"op": "require", "left": "count", "cmp": "==", "right": "0"}
+            ]}
+        ])).unwrap();
+        let contract = {
+            let mut guard = state.write().await;
+            guard.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+            chain.mvm.deploy(&mut guard, sender.address().as_str(), "Counter", None, variables, vec![], functions, 0).unwrap()
+        };
+        let call = |method: &str, nonce: u64| signed_tx(&sender, TxType::CallContract, nonce, Some(TxData::CallContract {
+            contract: contract.clone(),
+            method: method.to_string(),
+            args: vec![],
+            amount: None,
+            native_value: None,
+        }));
+        let (bump, fail) = (call("bump", 0), call("fail", 1));
+        chain.add_transaction(bump.clone()).unwrap();
+        chain.add_transaction(fail.clone()).unwrap();
+        chain.produce_block_at(Utc::now().timestamp()).await.unwrap();
+
+        let guard = state.read().await;
+        let bumped = guard.get_transaction(&bump.hash).unwrap().unwrap();
+        assert_eq!((bumped.status, bumped.result), (TxStatus::Success, Some(serde_json::json!("8"))));
+        let failed = guard.get_transaction(&fail.hash).unwrap().unwrap();
+        assert_eq!((failed.status, failed.result), (TxStatus::Failed, None));
+    }
+
+    #[tokio::test]
+    async fn test_legacy_call_runs_a_mosh_function_under_the_tx_gas_limit() {
+        let (state, _dir) = test_state("legacy-call");
+        let state = Arc::new(RwLock::new(state));
+        let config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        let master = state.write().await.get_or_create_master_address().unwrap();
+        let mut chain = Blockchain::new(config, state.clone(), master).await.unwrap();
+
+        let sender = Keypair::generate();
+        let variables: Vec<crate::mvm::VarDef> = serde_json::from_value(serde_json::json!([
+            {"name": "count", "var_type": "Uint64", "default": "7"}
+        ])).unwrap();
+        let functions: Vec<crate::mvm::FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "bump", "modifiers": ["Write"], "body": [
+                {"op": "add", "var": "count", "value": 1}
+            ]}
+        ])).unwrap();
+        let contract = {
+            let mut guard = state.write().await;
+            guard.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+            chain.mvm.deploy(&mut guard, sender.address().as_str(), "Counter", None, variables, vec![], functions, 0).unwrap()
+        };
+        let call = signed_tx(&sender, TxType::Call, 0, Some(TxData::Call {
+            contract: contract.clone(),
+            method: "bump".to_string(),
+            args: vec![],
+        }));
+        chain.add_transaction(call.clone()).unwrap();
+        chain.produce_block_at(Utc::now().timestamp()).await.unwrap();
+
+        let guard = state.read().await;
+        assert_eq!(guard.get_transaction(&call.hash).unwrap().unwrap().status, TxStatus::Success);
+        assert_eq!(guard.get_mosh_var(&contract, "count").unwrap().as_deref(), Some("8"));
+    }
+
+    #[tokio::test]
+    async fn test_block_that_fails_to_store_keeps_none_of_its_txs() {
+        let (state, _dir) = test_state("atomic-block");
+        let state = Arc::new(RwLock::new(state));
+        let config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        let master = state.write().await.get_or_create_master_address().unwrap();
+        let mut chain = Blockchain::new(config, state.clone(), master.clone()).await.unwrap();
+
+        let sender = Keypair::generate();
+        let to = Keypair::generate().address().to_string();
+        {
+            let mut guard = state.write().await;
+            guard.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+            // The validator reward can't be credited, so storing the block fails
+            guard.set_balance(master.as_str(), u64::MAX).unwrap();
+        }
+        let tx = signed_transfer(&sender, &to, 5, 0);
+        chain.add_transaction(tx.clone()).unwrap();
+        assert!(chain.produce_block_at(Utc::now().timestamp()).await.is_err());
+
+        let guard = state.read().await;
+        assert_eq!(guard.get_height().unwrap(), 0);
+        assert_eq!(guard.get_balance(sender.address().as_str()).unwrap(), 1_000_000_000);
+        assert_eq!(guard.get_nonce(sender.address().as_str()).unwrap(), 0);
+        assert_eq!(guard.get_balance(&to).unwrap(), 0);
+        assert!(guard.get_transaction(&tx.hash).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_verify_chain_truncates_at_break() {
+        let (state, _dir) = test_state("verify");
+        let state = Arc::new(RwLock::new(state));
+        let master = state.write().await.get_or_create_master_address().unwrap();
+        let config: Config = toml::from_str(include_str!("../config.toml")).unwrap();
+        let mut chain = Blockchain::new(config, state.clone(), master).await.unwrap();
+        for _ in 0..3 {
+            chain.produce_block_at(Utc::now().timestamp()).await.unwrap();
+        }
+        assert_eq!(chain.verify_chain(false).await.unwrap(), 3);
+
+        // Block 2 no longer links to block 1
+        {
+            let mut guard = state.write().await;
+            let mut block = guard.get_block(2).unwrap().unwrap();
+            block.prev_hash = "0".repeat(64);
+            block.hash = block.calculate_hash();
+            guard.save_block(&block).unwrap();
+        }
+
+        // Refused without the opt-in, leaving every block in place
+        assert!(chain.verify_chain(false).await.is_err());
+        assert!(state.read().await.get_block(3).unwrap().is_some());
+
+        assert_eq!(chain.verify_chain(true).await.unwrap(), 1);
+        let guard = state.read().await;
+        assert_eq!(guard.get_height().unwrap(), 1);
+        assert!(guard.get_block(2).unwrap().is_none());
+        assert!(guard.get_block(3).unwrap().is_none());
+        drop(guard);
+    }
+
+    #[test]
+    fn test_block_sign_and_verify() {
+        let keypair = Keypair::generate();
+        let rewards = BlockRewards { validator_reward: 0, service_rewards: vec![], total_minted: 0 };
+        let mut block = Block::new(1, "prev", keypair.address().as_str(), vec![], rewards, 1_000_000);
+        assert!(!block.verify_signature(&keypair.public_key_hex()).unwrap());
+
+        block.sign(&keypair);
+        assert!(block.verify_signature(&block.public_key).unwrap());
+
+        // Tampering with signed fields breaks the signature
+        block.height = 2;
+        assert!(!block.verify_signature(&block.public_key).unwrap());
+
+        // A key that doesn't match the validator address is rejected
+        block.height = 1;
+        let other = Keypair::generate();
+        assert!(!block.verify_signature(&other.public_key_hex()).unwrap());
+    }
+}
@@ -16,6 +16,18 @@ pub struct Config {
     pub validators: ValidatorsConfig,
     pub pruning: PruningConfig,
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub ratelimit: RateLimitConfig,
+    #[serde(default)]
+    pub contract: ContractConfig,
+    #[serde(default)]
+    pub supply: SupplyConfig,
+    #[serde(default)]
+    pub treasury: TreasuryConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub names: NamesConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +41,45 @@ pub struct BlockConfig {
     pub block_time: u64,
     pub gas_limit: u64,
     pub max_txs_per_block: usize,
+    /// Produce a block every interval even when the mempool is empty
+    #[serde(default = "default_true")]
+    pub produce_when_empty: bool,
+    /// When skipping empty blocks, force a heartbeat block after this many idle intervals (0 = never)
+    #[serde(default)]
+    pub max_idle_blocks: u64,
+    /// Maximum pending transactions; the lowest-fee tx is evicted when full
+    #[serde(default = "default_mempool_size")]
+    pub mempool_size: usize,
+    /// Gas price increase (percent) needed to replace a pending tx with the same nonce
+    #[serde(default = "default_replace_bump_percent")]
+    pub replace_bump_percent: u64,
+    /// Lowest gas price a submitted tx may offer
+    #[serde(default = "default_min_gas_price")]
+    pub min_gas_price: u64,
+    /// Confirmations after which a tx is reported as final. A tx's
+    /// confirmations are the blocks built on top of its block (0 in the tip).
+    #[serde(default = "default_confirmations_for_final")]
+    pub confirmations_for_final: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_mempool_size() -> usize {
+    10_000
+}
+
+fn default_replace_bump_percent() -> u64 {
+    10
+}
+
+fn default_min_gas_price() -> u64 {
+    crate::chain::DEFAULT_GAS_PRICE
+}
+
+fn default_confirmations_for_final() -> u64 {
+    6
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,12 +91,30 @@ pub struct RewardsConfig {
     pub rank_1_percent: u64,
     pub rank_2_percent: u64,
     pub rank_3_percent: u64,
+    /// Followers report their own browser counts, so ranking counts no
+    /// more than this many per node
+    #[serde(default = "default_max_reported_browsers")]
+    pub max_reported_browsers: u32,
+}
+
+fn default_max_reported_browsers() -> u32 {
+    10_000
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GenesisConfig {
     pub master_address: String,
     pub master_balance: u64,
+    /// Extra accounts funded at genesis, on top of the master balance
+    #[serde(default)]
+    pub allocations: Vec<GenesisAllocation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenesisAllocation {
+    pub address: String,
+    /// Whole MVM, like `master_balance`
+    pub balance: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,6 +129,9 @@ pub struct TokenConfig {
     pub name: String,
     pub symbol: String,
     pub decimals: u8,
+    /// Whole MVM charged on top of gas to create a token, paid to the treasury
+    #[serde(default)]
+    pub creation_fee: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -68,6 +140,17 @@ pub struct NodeConfig {
     #[serde(rename = "type")]
     pub node_type: String,
     pub data_dir: String,
+    /// Account balances/nonces kept in memory in front of RocksDB (0 = no cache)
+    #[serde(default = "default_account_cache_size")]
+    pub account_cache_size: usize,
+    /// Serve reads from an existing database without producing blocks,
+    /// joining the network or accepting writes
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+fn default_account_cache_size() -> usize {
+    crate::state::DEFAULT_ACCOUNT_CACHE_SIZE
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -85,33 +168,463 @@ pub struct StarConfig {
     pub master_url: String,
 }
 
+/// Round-robin producer set. Empty means the master produces every block.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidatorsConfig {
     pub addresses: Vec<String>,
     pub max_validators: usize,
+    /// Seconds after the previous block before a validator's missed turn
+    /// passes to the next one in line; 0 never skips a turn
+    #[serde(default = "default_turn_timeout_secs")]
+    pub turn_timeout_secs: u64,
+}
+
+fn default_turn_timeout_secs() -> u64 {
+    30
+}
+
+impl ValidatorsConfig {
+    /// The validator that may produce the block at `height` once `elapsed`
+    /// seconds have passed since the previous block, or None when no
+    /// validator set is configured. The turn moves one validator along for
+    /// every full `turn_timeout_secs`.
+    pub fn validator_at(&self, height: u64, elapsed: u64) -> Option<&str> {
+        if self.addresses.is_empty() {
+            return None;
+        }
+        let skipped = elapsed.checked_div(self.turn_timeout_secs).unwrap_or(0);
+        let index = (height.wrapping_add(skipped) % self.addresses.len() as u64) as usize;
+        Some(self.addresses[index].as_str())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PruningConfig {
+    /// Newest blocks whose history (txs, indexes, balance changes) is kept;
+    /// 0 keeps everything. Transactions go with their block.
     pub keep_blocks: u64,
-    pub keep_txs: u64,
+}
+
+/// Per-IP limit on the faucet and tx submission endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_requests_per_minute")]
+    pub requests_per_minute: u32,
+    /// Requests allowed back-to-back before the per-minute rate applies
+    #[serde(default = "default_burst")]
+    pub burst: u32,
+}
+
+fn default_requests_per_minute() -> u32 {
+    30
+}
+
+fn default_burst() -> u32 {
+    10
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            enabled: true,
+            requests_per_minute: default_requests_per_minute(),
+            burst: default_burst(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContractConfig {
+    /// Whole MVM charged on top of gas to deploy a contract, paid to the treasury
+    #[serde(default)]
+    pub deploy_fee: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NamesConfig {
+    /// Whole MVM charged on top of gas to register a name, paid to the treasury
+    #[serde(default)]
+    pub registration_fee: u64,
+}
+
+/// Addresses whose balances `/supply` leaves out of circulating supply (the
+/// master is always excluded)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SupplyConfig {
+    #[serde(default)]
+    pub excluded_addresses: Vec<String>,
+    /// Serve `GET /debug/supply-check`, which scans every balance
+    #[serde(default)]
+    pub debug_check: bool,
+}
+
+/// Where the token creation, contract deploy and name registration fees go.
+/// Without an address they are burned.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TreasuryConfig {
+    #[serde(default)]
+    pub address: Option<String>,
+}
+
+/// `/admin/*` endpoints for tests and CI. Off by default; every request
+/// needs `Authorization: Bearer <token>`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdminConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub token: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoggingConfig {
     pub level: String,
+    /// `pretty` (multi-line, for terminals), `compact` (one line) or `json`
+    /// (one object per line, for log aggregators)
+    #[serde(default = "default_log_format")]
+    pub format: String,
+}
+
+fn default_log_format() -> String {
+    "pretty".to_string()
 }
 
 impl Config {
+    /// Load `path`, then apply `MVM_*` environment overrides (env wins over TOML)
     pub fn load(path: &str) -> Result<Self, BoxError> {
         let content = fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+        let mut config: Config = toml::from_str(&content)?;
+        config.apply_overrides(|name| std::env::var(name).ok())?;
         Ok(config)
     }
 
+    /// Override per-node settings from `lookup` (the environment in `load`)
+    fn apply_overrides(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<(), BoxError> {
+        let parse_port = |name: &str, value: String| -> Result<u16, BoxError> {
+            value.trim().parse::<u16>()
+                .map_err(|_| format!("{} must be a port number (0-65535), got \"{}\"", name, value).into())
+        };
+
+        if let Some(id) = lookup("MVM_NODE_ID") {
+            self.node.id = id;
+        }
+        if let Some(port) = lookup("MVM_API_PORT") {
+            self.network.api_port = parse_port("MVM_API_PORT", port)?;
+        }
+        if let Some(port) = lookup("MVM_P2P_PORT") {
+            self.network.p2p_port = parse_port("MVM_P2P_PORT", port)?;
+        }
+        if let Some(dir) = lookup("MVM_DATA_DIR") {
+            self.node.data_dir = dir;
+        }
+        if let Some(url) = lookup("MVM_MASTER_URL") {
+            self.network.star.master_url = url;
+        }
+        if let Some(token) = lookup("MVM_ADMIN_TOKEN") {
+            self.admin.token = token;
+        }
+        Ok(())
+    }
+
+    /// Reject values that would only fail confusingly at runtime
+    pub fn validate(&self) -> Result<(), BoxError> {
+        if self.chain.chain_id.trim().is_empty() {
+            return Err("chain.chain_id must not be empty".into());
+        }
+        if self.block.block_time == 0 {
+            return Err("block.block_time must be greater than 0".into());
+        }
+        if self.block.max_txs_per_block == 0 {
+            return Err("block.max_txs_per_block must be greater than 0".into());
+        }
+
+        let rewards = &self.rewards;
+        if rewards.validator_percent > 100 {
+            return Err(format!("rewards.validator_percent must be at most 100, got {}", rewards.validator_percent).into());
+        }
+        if rewards.validator_percent + rewards.service_pool_percent != 100 {
+            return Err(format!(
+                "rewards.validator_percent + rewards.service_pool_percent must equal 100, got {}",
+                rewards.validator_percent + rewards.service_pool_percent
+            ).into());
+        }
+        let rank_total = rewards.rank_1_percent + rewards.rank_2_percent + rewards.rank_3_percent;
+        if rank_total != 100 {
+            return Err(format!("rewards.rank_1/2/3_percent must sum to 100, got {}", rank_total).into());
+        }
+
+        let net = &self.network;
+        let ports = [("p2p_port", net.p2p_port), ("ws_port", net.ws_port), ("api_port", net.api_port)];
+        for (name, port) in ports {
+            if port == 0 {
+                return Err(format!("network.{} must be non-zero", name).into());
+            }
+        }
+        for (i, (name, port)) in ports.iter().enumerate() {
+            if let Some((other, _)) = ports[i + 1..].iter().find(|(_, p)| p == port) {
+                return Err(format!("network.{} and network.{} must differ (both {})", name, other, port).into());
+            }
+        }
+
+        if self.ratelimit.enabled && (self.ratelimit.requests_per_minute == 0 || self.ratelimit.burst == 0) {
+            return Err("ratelimit.requests_per_minute and ratelimit.burst must be greater than 0".into());
+        }
+
+        if let Some(bad) = self.supply.excluded_addresses.iter().find(|a| !crate::address::Address::new(a).is_valid()) {
+            return Err(format!("supply.excluded_addresses contains an invalid address: {}", bad).into());
+        }
+
+        if self.admin.enabled && self.admin.token.trim().is_empty() {
+            return Err("admin.token must be set when admin.enabled is true".into());
+        }
+
+        if let Some(ref treasury) = self.treasury.address {
+            if !crate::address::Address::new(treasury).is_valid() {
+                return Err(format!("treasury.address is not a valid address: {}", treasury).into());
+            }
+        }
+
+        let validators = &self.validators.addresses;
+        if validators.len() > self.validators.max_validators {
+            return Err(format!(
+                "validators.addresses lists {} validators, more than max_validators ({})",
+                validators.len(), self.validators.max_validators
+            ).into());
+        }
+        if let Some(bad) = validators.iter().find(|a| !crate::address::Address::new(a).is_valid()) {
+            return Err(format!("validators.addresses contains an invalid address: {}", bad).into());
+        }
+        if let Some((i, dup)) = validators.iter().enumerate().find(|(i, a)| validators[..*i].contains(a)) {
+            return Err(format!("validators.addresses lists {} twice (entry {})", dup, i).into());
+        }
+        let turn_timeout = self.validators.turn_timeout_secs;
+        if turn_timeout != 0 && turn_timeout <= self.block.block_time {
+            return Err(format!(
+                "validators.turn_timeout_secs ({}) must be 0 or longer than block.block_time ({})",
+                turn_timeout, self.block.block_time
+            ).into());
+        }
+
+        if !matches!(self.node.node_type.as_str(), "master" | "follower") {
+            return Err(format!("node.type must be \"master\" or \"follower\", got \"{}\"", self.node.node_type).into());
+        }
+
+        if !matches!(self.logging.format.as_str(), "pretty" | "json" | "compact") {
+            return Err(format!("logging.format must be \"pretty\", \"json\" or \"compact\", got \"{}\"", self.logging.format).into());
+        }
+
+        Ok(())
+    }
+
     pub fn save(&self, path: &str) -> Result<(), BoxError> {
         let content = toml::to_string_pretty(self)?;
         fs::write(path, content)?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl Default for Config {
+    /// The settings shipped in the repo's `config.toml` (a single master node)
+    fn default() -> Self {
+        toml::from_str(include_str!("../config.toml")).expect("bundled config.toml is valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn valid_config() -> Config {
+        toml::from_str(include_str!("../config.toml")).unwrap()
+    }
+
+    fn assert_invalid(config: Config, needle: &str) {
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains(needle), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_default_config_is_valid() {
+        assert!(valid_config().validate().is_ok());
+
+        let mut follower = valid_config();
+        follower.node.node_type = "follower".to_string();
+        assert!(follower.validate().is_ok());
+    }
+
+    #[test]
+    fn test_config_with_the_old_keep_txs_still_loads() {
+        let old = include_str!("../config.toml").replace("keep_blocks = 1000", "keep_blocks = 1000\nkeep_txs = 100");
+        let config: Config = toml::from_str(&old).unwrap();
+        assert_eq!(config.pruning.keep_blocks, 1000);
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_chain_and_block() {
+        let mut c = valid_config();
+        c.chain.chain_id = " ".to_string();
+        assert_invalid(c, "chain_id");
+
+        let mut c = valid_config();
+        c.block.block_time = 0;
+        assert_invalid(c, "block_time");
+
+        let mut c = valid_config();
+        c.block.max_txs_per_block = 0;
+        assert_invalid(c, "max_txs_per_block");
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_percentages() {
+        let mut c = valid_config();
+        c.rewards.validator_percent = 120;
+        assert_invalid(c, "at most 100");
+
+        let mut c = valid_config();
+        c.rewards.service_pool_percent = 40;
+        assert_invalid(c, "must equal 100");
+
+        let mut c = valid_config();
+        c.rewards.rank_3_percent = 20;
+        assert_invalid(c, "sum to 100");
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_ports() {
+        let mut c = valid_config();
+        c.network.ws_port = 0;
+        assert_invalid(c, "ws_port must be non-zero");
+
+        let mut c = valid_config();
+        c.network.api_port = c.network.p2p_port;
+        assert_invalid(c, "must differ");
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let env: HashMap<&str, &str> = [
+            ("MVM_NODE_ID", "node-9"),
+            ("MVM_API_PORT", "8600"),
+            ("MVM_DATA_DIR", "/tmp/node9"),
+            ("MVM_MASTER_URL", "ws://master:8545/p2p"),
+            ("MVM_ADMIN_TOKEN", "s3cret"),
+        ].into_iter().collect();
+
+        let mut c = valid_config();
+        let p2p_port = c.network.p2p_port;
+        c.apply_overrides(|name| env.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(c.node.id, "node-9");
+        assert_eq!(c.network.api_port, 8600);
+        assert_eq!(c.network.p2p_port, p2p_port);
+        assert_eq!(c.node.data_dir, "/tmp/node9");
+        assert_eq!(c.network.star.master_url, "ws://master:8545/p2p");
+        assert_eq!(c.admin.token, "s3cret");
+
+        let mut c = valid_config();
+        let err = c.apply_overrides(|name| (name == "MVM_P2P_PORT").then(|| "70000".to_string())).unwrap_err();
+        assert!(err.to_string().contains("MVM_P2P_PORT"));
+    }
+
+    #[test]
+    fn test_validators_take_turns_by_height() {
+        let a = crate::address::Keypair::generate().address().to_string();
+        let b = crate::address::Keypair::generate().address().to_string();
+
+        let mut c = valid_config();
+        assert_eq!(c.validators.validator_at(5, 0), None);
+
+        c.validators.addresses = vec![a.clone(), b.clone()];
+        assert!(c.validate().is_ok());
+        assert_eq!(c.validators.validator_at(1, 0), Some(b.as_str()));
+        assert_eq!(c.validators.validator_at(2, 0), Some(a.as_str()));
+
+        let mut dup = c.clone();
+        dup.validators.addresses.push(a.clone());
+        assert_invalid(dup, "twice");
+
+        let mut c = valid_config();
+        c.validators.addresses = vec!["mvm1nobody".to_string()];
+        assert_invalid(c, "invalid address");
+
+        let mut c = valid_config();
+        c.validators.max_validators = 1;
+        c.validators.addresses = vec![a, b];
+        assert_invalid(c, "more than max_validators");
+    }
+
+    #[test]
+    fn test_missed_turns_pass_to_the_next_validator() {
+        let a = crate::address::Keypair::generate().address().to_string();
+        let b = crate::address::Keypair::generate().address().to_string();
+        let c_addr = crate::address::Keypair::generate().address().to_string();
+
+        let mut c = valid_config();
+        c.validators.addresses = vec![a.clone(), b.clone(), c_addr.clone()];
+        c.validators.turn_timeout_secs = 30;
+        assert_eq!(c.validators.validator_at(1, 29), Some(b.as_str()));
+        assert_eq!(c.validators.validator_at(1, 30), Some(c_addr.as_str()));
+        assert_eq!(c.validators.validator_at(1, 65), Some(a.as_str()));
+
+        c.validators.turn_timeout_secs = 0;
+        assert_eq!(c.validators.validator_at(1, 600), Some(b.as_str()));
+
+        c.validators.turn_timeout_secs = c.block.block_time;
+        assert_invalid(c, "turn_timeout_secs");
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_node_type() {
+        let mut c = valid_config();
+        c.node.node_type = "full".to_string();
+        assert_invalid(c, "node.type");
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_log_format() {
+        let mut c = valid_config();
+        assert_eq!(c.logging.format, "pretty");
+        c.logging.format = "xml".to_string();
+        assert_invalid(c, "logging.format");
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_supply_address() {
+        let mut c = valid_config();
+        c.supply.excluded_addresses = vec!["mvm1nope".to_string()];
+        assert_invalid(c, "supply.excluded_addresses");
+    }
+
+    #[test]
+    fn test_validate_requires_admin_token_when_enabled() {
+        let mut c = valid_config();
+        c.admin.enabled = true;
+        assert_invalid(c.clone(), "admin.token");
+        c.admin.token = "s3cret".to_string();
+        assert!(c.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_treasury_address() {
+        let mut c = valid_config();
+        c.treasury.address = Some("mvm1nope".to_string());
+        assert_invalid(c, "treasury.address");
+    }
+
+    #[test]
+    fn test_saved_default_config_loads_back() {
+        let path = std::env::temp_dir().join(format!("mvm-config-{}.toml", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut c = Config::default();
+        c.genesis.master_address = "mvm1abc".to_string();
+        c.save(path).unwrap();
+
+        let loaded = Config::load(path).unwrap();
+        fs::remove_file(path).unwrap();
+        loaded.validate().unwrap();
+        assert_eq!(loaded.genesis.master_address, "mvm1abc");
+        assert_eq!(loaded.chain.chain_id, c.chain.chain_id);
+    }
+}
@@ -6,10 +6,14 @@ mod address;
 mod state;
 mod network;
 mod api;
+mod ratelimit;
+mod cache;
+mod names;
 
 use crate::config::Config;
 use crate::chain::Blockchain;
 use crate::state::State;
+use crate::address::Keypair;
 use crate::network::{Network, StarNetwork};
 use crate::api::start_api_server;
 
@@ -24,18 +28,32 @@ type BoxError = Box<dyn std::error::Error + Send + Sync>;
 async fn main() -> Result<(), BoxError> {
     // Parse command line args
     let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("init") => return init_config(args.get(2).map(String::as_str).unwrap_or("config.toml")),
+        Some("keygen") => {
+            print_keypair(&Keypair::generate());
+            return Ok(());
+        }
+        _ => {}
+    }
     
-    let config_path = if args.len() > 2 && args[1] == "--config" {
-        args[2].clone()
-    } else {
-        "config.toml".to_string()
-    };
+    let config_path = args.iter().position(|a| a == "--config")
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+        .unwrap_or_else(|| "config.toml".to_string());
+    // Check the stored chain links up before serving it
+    let verify = args.iter().any(|a| a == "--verify");
+    // Let --verify drop the blocks above a break instead of refusing to start
+    let allow_truncate = args.iter().any(|a| a == "--allow-truncate");
 
     // Load config
     let config = Config::load(&config_path)?;
+    if let Err(e) = config.validate() {
+        return Err(format!("Invalid config {}: {}", config_path, e).into());
+    }
     
     // Setup logging
-    let _subscriber = FmtSubscriber::builder()
+    let subscriber = FmtSubscriber::builder()
         .with_max_level(match config.logging.level.as_str() {
             "debug" => Level::DEBUG,
             "info" => Level::INFO,
@@ -43,9 +61,12 @@ async fn main() -> Result<(), BoxError> {
             "error" => Level::ERROR,
             _ => Level::INFO,
         })
-        .with_target(false)
-        .pretty()
-        .init();
+        .with_target(false);
+    match config.logging.format.as_str() {
+        "json" => subscriber.json().init(),
+        "compact" => subscriber.compact().init(),
+        _ => subscriber.pretty().init(),
+    }
 
     print_banner();
     
@@ -55,28 +76,57 @@ async fn main() -> Result<(), BoxError> {
     info!("Node Type: {}", config.node.node_type);
 
     // Initialize state (RocksDB)
-    let state = Arc::new(RwLock::new(State::new(&config.node.data_dir)?));
+    let read_only = config.node.read_only;
+    let state = if read_only {
+        let state = State::open_read_only(&config.node.data_dir)?;
+        if state.get_block(0)?.is_none() {
+            return Err(format!("node.read_only needs an existing chain in {}", config.node.data_dir).into());
+        }
+        info!("📖 Read-only replica: no block production, P2P or writes");
+        state
+    } else {
+        State::new(&config.node.data_dir)?
+    };
+    let state = Arc::new(RwLock::new(state.with_account_cache(config.node.account_cache_size)));
     
     // Generate or load master address
     let master_address = {
         let mut state_guard = state.write().await;
         let addr = state_guard.get_or_create_master_address()?;
         info!("Master Address: {}", addr);
+        // Followers name the master they follow here, which is not their own key
+        let configured = &config.genesis.master_address;
+        if config.node.node_type == "master" && !configured.is_empty() && configured != addr.as_str() {
+            tracing::warn!("genesis.master_address {} does not match the master key in {}", configured, config.node.data_dir);
+        }
         addr
     };
 
     // Initialize blockchain
-    let blockchain = Arc::new(RwLock::new(
-        Blockchain::new(config.clone(), state.clone(), master_address.clone()).await?
-    ));
+    let blockchain = Blockchain::new(config.clone(), state.clone(), master_address.clone()).await?;
+    if verify {
+        let height = blockchain.verify_chain(allow_truncate).await?;
+        info!("✅ Chain verified up to block #{}", height);
+    }
+    let blockchain = Arc::new(RwLock::new(blockchain));
 
     // Initialize network
     let network = Arc::new(RwLock::new(
         StarNetwork::new(config.clone(), blockchain.clone(), state.clone())
     ));
 
-    // Start network
+    // Publish mempool arrivals and contract events to the network's
+    // subscribers, and pay the service pool to the followers it tracks
     {
+        let net = network.read().await;
+        let mut bc = blockchain.write().await;
+        bc.set_tx_notifier(net.tx_sender());
+        bc.set_event_notifier(net.event_sender());
+        bc.set_service_nodes(net.peer_stats());
+    }
+
+    // Start network; a read-only replica stays off it, as applying blocks would write
+    if !read_only {
         let mut net = network.write().await;
         net.start().await?;
     }
@@ -89,8 +139,10 @@ async fn main() -> Result<(), BoxError> {
         network.clone(),
     ));
 
-    // If master, start block production
-    if config.node.node_type == "master" {
+    // The master produces blocks, as does any node whose key is in the
+    // validator set (on its turns only, see `Blockchain::is_validator_for`)
+    let is_validator = config.validators.addresses.iter().any(|a| a == master_address.as_str());
+    if (config.node.node_type == "master" || is_validator) && !read_only {
         let bc = blockchain.clone();
         let net = network.clone();
         let block_time = config.block.block_time;
@@ -100,12 +152,14 @@ async fn main() -> Result<(), BoxError> {
                 tokio::time::sleep(tokio::time::Duration::from_secs(block_time)).await;
                 
                 let mut blockchain = bc.write().await;
-                match blockchain.produce_block().await {
-                    Ok(block) => {
-                        info!("📦 Block #{} produced | {} txs | hash: {}",
-                            block.height,
-                            block.transactions.len(),
-                            &block.hash[..16]
+                match blockchain.try_produce_block().await {
+                    Ok(None) => {}
+                    Ok(Some(block)) => {
+                        info!(
+                            height = block.height,
+                            tx_count = block.transactions.len(),
+                            hash = %block.hash,
+                            "📦 Block produced"
                         );
                         
                         // Broadcast to connected nodes
@@ -137,6 +191,32 @@ async fn main() -> Result<(), BoxError> {
     Ok(())
 }
 
+/// `init [path]`: write the default config, with the master key created in its data dir
+fn init_config(path: &str) -> Result<(), BoxError> {
+    if std::path::Path::new(path).exists() {
+        return Err(format!("{} already exists, not overwriting it", path).into());
+    }
+
+    let mut config = Config::default();
+    let mut state = State::new(&config.node.data_dir)?;
+    let master = state.get_or_create_master_address()?;
+    config.genesis.master_address = master.to_string();
+    config.save(path)?;
+
+    println!("Wrote {}", path);
+    if let Some(keypair) = state.get_keypair() {
+        print_keypair(keypair);
+    }
+    println!("Master key stored in {}", config.node.data_dir);
+    Ok(())
+}
+
+fn print_keypair(keypair: &Keypair) {
+    println!("Address:     {}", keypair.address());
+    println!("Public key:  {}", keypair.public_key_hex());
+    println!("Private key: {}", hex::encode(keypair.to_bytes()));
+}
+
 fn print_banner() {
     println!(r#"
 ╔═══════════════════════════════════════════════════════════════╗
@@ -1,6 +1,6 @@
 use crate::state::State;
+use crate::address::Address;
 use serde::{Deserialize, Serialize};
-use sha2::{Sha256, Digest};
 use chrono::Utc;
 use std::collections::HashMap;
 
@@ -14,6 +14,49 @@ pub const MAX_OPS_PER_FUNCTION: usize = 20;
 pub const MAX_STRING_LENGTH: usize = 256;
 pub const MAX_NAME_LENGTH: usize = 32;
 pub const MAX_NESTING_DEPTH: usize = 5;
+/// Most contract calls a `call` op may nest below the outermost call
+pub const MAX_CALL_DEPTH: usize = 4;
+/// Error a `call` op fails with when it targets a contract already executing
+/// in the same transaction
+pub const REENTRANCY: &str = "reentrancy";
+pub const MAX_REPEAT_COUNT: u64 = 100;
+/// Largest raw code blob a `deploy` tx may store
+pub const MAX_CODE_SIZE: usize = 24 * 1024;
+
+/// Error a call fails with when it runs past its gas limit
+pub const OUT_OF_GAS: &str = "Out of gas";
+/// Owner of a contract after `renounce_ownership`; no key can sign for it
+pub const RENOUNCED_OWNER: &str = "mvm1burn";
+/// Error a setter or non-view function fails with while its contract is paused
+pub const CONTRACT_PAUSED: &str = "Contract is paused";
+
+// ==================== GAS ====================
+
+/// Gas the MVM charges while running a contract call, on top of the tx's base gas
+#[derive(Debug, Clone, Serialize)]
+pub struct GasSchedule {
+    /// Every call
+    pub call: u64,
+    /// Auto-generated `get_<var>` getters
+    pub getter: u64,
+    /// Auto-generated `set_<var>` setters
+    pub setter: u64,
+    /// `accept_ownership` / `renounce_ownership` / `pause` / `unpause`
+    pub ownership: u64,
+    /// User-defined functions
+    pub function: u64,
+    /// Each operation a function body executes
+    pub op: u64,
+}
+
+pub const GAS_SCHEDULE: GasSchedule = GasSchedule {
+    call: 5000,
+    getter: 1000,
+    setter: 5000,
+    ownership: 5000,
+    function: 10000,
+    op: 1000,
+};
 
 // ==================== TYPES ====================
 
@@ -40,6 +83,15 @@ impl VarType {
             _ => None,
         }
     }
+
+    /// Value a variable of this type holds when unset (deploy without a default, `del`)
+    pub fn zero_value(&self) -> &'static str {
+        match self {
+            VarType::Uint64 => "0",
+            VarType::String | VarType::Address => "",
+            VarType::Bool => "false",
+        }
+    }
 }
 
 // ==================== CONTRACT SCHEMA ====================
@@ -57,6 +109,21 @@ pub struct MappingDef {
     pub name: String,
     pub key_type: VarType,
     pub value_type: VarType,
+    /// Set for nested mappings: `value_type` is then the inner key type and
+    /// this is the type of the stored value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value2_type: Option<VarType>,
+}
+
+impl MappingDef {
+    pub fn is_nested(&self) -> bool {
+        self.value2_type.is_some()
+    }
+
+    /// Type of the values actually stored in the mapping
+    pub fn stored_type(&self) -> &VarType {
+        self.value2_type.as_ref().unwrap_or(&self.value_type)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -98,6 +165,8 @@ pub struct Operation {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub key: Option<serde_json::Value>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key2: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub left: Option<serde_json::Value>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub right: Option<serde_json::Value>,
@@ -105,6 +174,11 @@ pub struct Operation {
     pub cmp: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub msg: Option<String>,
+    // Extra require conditions: all of `and` must also hold, or any of `or` suffices
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub and: Option<Vec<ConditionExpr>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub or: Option<Vec<ConditionExpr>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub to: Option<serde_json::Value>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -116,11 +190,23 @@ pub struct Operation {
     pub then_body: Option<Vec<Operation>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub else_body: Option<Vec<Operation>>,
+    // Bounded loop
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Vec<Operation>>,
     // Event emit/signal
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub event_name: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub event_args: Option<Vec<serde_json::Value>>,
+    // Contract-to-contract call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<serde_json::Value>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -161,7 +247,16 @@ pub struct MoshContract {
     pub name: String,
     pub creator: String,
     pub owner: String,
+    /// Set by `set_pending_owner`, promoted by `accept_ownership`
+    #[serde(default)]
+    pub pending_owner: Option<String>,
+    /// Set by `pause`, cleared by `unpause`; blocks setters and non-view functions
+    #[serde(default)]
+    pub paused: bool,
     pub created_at: i64,
+    /// Height of the block that deployed the contract (0 for contracts stored before this was tracked)
+    #[serde(default)]
+    pub created_block: u64,
     
     // Token (optional)
     pub token: Option<String>,
@@ -172,16 +267,44 @@ pub struct MoshContract {
     pub functions: Vec<FnDef>,
 }
 
+impl MoshContract {
+    pub fn schema_hash(&self) -> String {
+        schema_hash(&self.name, &self.variables, &self.mappings, &self.functions)
+    }
+}
+
+/// SHA-256 (hex) of a contract's name and schema, as deployed
+pub fn schema_hash(name: &str, variables: &[VarDef], mappings: &[MappingDef], functions: &[FnDef]) -> String {
+    use sha2::{Digest, Sha256};
+    let schema = serde_json::to_string(&(name, variables, mappings, functions)).unwrap_or_default();
+    hex::encode(Sha256::digest(schema.as_bytes()))
+}
+
+/// A raw code contract from a `deploy` tx. The blob is stored as is; calls to
+/// it run the stub executor in `MVM::execute_call`, not the code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeContract {
+    pub address: String,
+    pub name: String,
+    pub creator: String,
+    pub code: Vec<u8>,
+    pub created_at: i64,
+}
+
 // ==================== EXECUTION CONTEXT ====================
 
 #[derive(Debug, Clone)]
 pub struct ExecContext {
     pub caller: String,
-    pub amount: u64,          // For payable
+    pub amount: u64,          // Token amount for payable (msg.amount)
+    pub native_value: u64,    // Native MVM sent with the call (msg.value)
     pub block_height: u64,
     pub block_timestamp: u64,
     pub args: HashMap<String, String>,  // Function arguments
     pub locals: HashMap<String, String>, // Local variables during execution
+    pub call_depth: usize,    // Nested contract calls above this one
+    pub call_stack: Vec<String>, // Contracts executing in this tx, outermost first, this one last
+    pub gas_limit: u64,       // Gas the call may use; running past it fails with OUT_OF_GAS
 }
 
 // ==================== CALL RESULT ====================
@@ -208,14 +331,66 @@ impl CallResult {
     }
 }
 
+// ==================== STATE ACCESS ====================
+
+/// State handle used by the op executor. `ReadOnly` is used for free view
+/// calls and rejects any op that would write to storage.
+pub enum StateAccess<'a> {
+    ReadOnly(&'a State),
+    ReadWrite(&'a mut State),
+}
+
+impl<'a> StateAccess<'a> {
+    pub fn get(&self) -> &State {
+        match self {
+            StateAccess::ReadOnly(s) => s,
+            StateAccess::ReadWrite(s) => s,
+        }
+    }
+
+    pub fn get_mut(&mut self, op: &str) -> Result<&mut State, BoxError> {
+        match self {
+            StateAccess::ReadOnly(_) => Err(format!("View functions cannot modify state (op: {})", op).into()),
+            StateAccess::ReadWrite(s) => Ok(s),
+        }
+    }
+}
+
 // ==================== MVM ENGINE ====================
 
-pub struct MVM;
+/// Height and time of the block a call executes in
+#[derive(Debug, Clone, Copy)]
+pub struct BlockEnv {
+    pub height: u64,
+    pub timestamp: u64,
+}
+
+pub struct MVM {
+    /// Block being produced; `None` outside block production (views, dry runs)
+    block: Option<BlockEnv>,
+}
 
 impl MVM {
-    pub fn new() -> Self { MVM }
+    pub fn new() -> Self { MVM { block: None } }
+
+    /// Run the following calls as part of `block`, so `block.timestamp` and
+    /// event heights come from the block instead of the wall clock
+    pub fn set_block(&mut self, block: Option<BlockEnv>) {
+        self.block = block;
+    }
+
+    /// The block calls run in: the one set by `set_block`, else the current
+    /// tip at wall-clock time
+    pub fn block_env(&self, state: &State) -> BlockEnv {
+        self.block.unwrap_or_else(|| BlockEnv {
+            height: state.get_height().unwrap_or(0),
+            timestamp: Utc::now().timestamp() as u64,
+        })
+    }
 
-    /// Deploy a new Mosh contract
+    /// Deploy a new Mosh contract at the address derived from `creator` and the
+    /// deploying tx's `nonce`, so it can be known before the tx is sent.
+    #[allow(clippy::too_many_arguments)]
     pub fn deploy(
         &self,
         state: &mut State,
@@ -225,6 +400,7 @@ impl MVM {
         variables: Vec<VarDef>,
         mappings: Vec<MappingDef>,
         functions: Vec<FnDef>,
+        nonce: u64,
     ) -> Result<String, BoxError> {
         
         // Validate name
@@ -236,7 +412,9 @@ impl MVM {
         if variables.len() > MAX_VARIABLES {
             return Err(format!("Max {} variables", MAX_VARIABLES).into());
         }
-        if mappings.len() > MAX_MAPPINGS {
+        // Each level of a nested mapping takes a slot
+        let mapping_slots: usize = mappings.iter().map(|m| if m.is_nested() { 2 } else { 1 }).sum();
+        if mapping_slots > MAX_MAPPINGS {
             return Err(format!("Max {} mappings", MAX_MAPPINGS).into());
         }
         if functions.len() > MAX_FUNCTIONS {
@@ -245,7 +423,7 @@ impl MVM {
         
         // Check duplicates
         let mut names = std::collections::HashSet::new();
-        let reserved = ["owner", "creator", "token", "address", "balance"];
+        let reserved = ["owner", "pending_owner", "creator", "token", "address", "balance"];
         
         for v in &variables {
             if reserved.contains(&v.name.as_str()) {
@@ -261,6 +439,9 @@ impl MVM {
             }
         }
         for f in &functions {
+            if ["accept_ownership", "renounce_ownership", "pause", "unpause"].contains(&f.name.as_str()) {
+                return Err(format!("Reserved: {}", f.name).into());
+            }
             if f.body.len() > MAX_OPS_PER_FUNCTION {
                 return Err(format!("Function {} has too many ops (max {})", f.name, MAX_OPS_PER_FUNCTION).into());
             }
@@ -273,20 +454,21 @@ impl MVM {
             }
         }
         
-        // Generate address
-        let mut hasher = Sha256::new();
-        hasher.update(creator.as_bytes());
-        hasher.update(name.as_bytes());
-        hasher.update(Utc::now().timestamp_nanos_opt().unwrap_or(0).to_le_bytes());
-        let hash = hasher.finalize();
-        let address = format!("mvm1contract{}", hex::encode(&hash[..10]));
+        let address = Address::derived("mvm1contract", creator, nonce).to_string();
+        if state.get_mosh_contract(&address)?.is_some() {
+            return Err(format!("Contract already exists: {}", address).into());
+        }
         
+        let block = self.block_env(state);
         let contract = MoshContract {
             address: address.clone(),
             name: name.to_string(),
             creator: creator.to_string(),
             owner: creator.to_string(),
-            created_at: Utc::now().timestamp(),
+            pending_owner: None,
+            paused: false,
+            created_at: block.timestamp as i64,
+            created_block: block.height,
             token,
             variables: variables.clone(),
             mappings,
@@ -297,19 +479,16 @@ impl MVM {
         
         // Initialize variables
         for v in &variables {
-            let val = v.default.clone().unwrap_or_else(|| match v.var_type {
-                VarType::Uint64 => "0".to_string(),
-                VarType::String => "".to_string(),
-                VarType::Bool => "false".to_string(),
-                VarType::Address => "".to_string(),
-            });
+            let val = v.default.clone().unwrap_or_else(|| v.var_type.zero_value().to_string());
             state.set_mosh_var(&address, &v.name, &val)?;
         }
         
         Ok(address)
     }
 
-    /// Call a contract function
+    /// Call a contract function. The call aborts with `OUT_OF_GAS` once
+    /// execution would use more than `gas_limit`; everything it wrote is undone.
+    #[allow(clippy::too_many_arguments)]
     pub fn call(
         &self,
         state: &mut State,
@@ -318,23 +497,94 @@ impl MVM {
         fn_name: &str,
         args: Vec<String>,
         amount: u64, // For payable
+        gas_limit: u64,
+    ) -> Result<CallResult, BoxError> {
+        self.call_at_depth(state, caller, contract_addr, fn_name, args, amount, 0, gas_limit, &[])
+    }
+
+    /// `call` with native MVM attached, seen as `msg.value`. The caller moves
+    /// `native_value` to the contract's address before calling.
+    #[allow(clippy::too_many_arguments)]
+    pub fn call_with_value(
+        &self,
+        state: &mut State,
+        caller: &str,
+        contract_addr: &str,
+        fn_name: &str,
+        args: Vec<String>,
+        amount: u64,
+        native_value: u64,
+        gas_limit: u64,
+    ) -> Result<CallResult, BoxError> {
+        self.call_at_depth(state, caller, contract_addr, fn_name, args, amount, native_value, gas_limit, &[])
+    }
+
+    /// `call` nested below the contracts in `call_stack` (outermost first).
+    /// Only the outermost call persists events; nested calls hand theirs
+    /// back to the parent.
+    ///
+    /// Each call runs under its own savepoint: a failed call undoes all of its
+    /// writes, and a nested call's writes are only kept if its parent succeeds.
+    #[allow(clippy::too_many_arguments)]
+    fn call_at_depth(
+        &self,
+        state: &mut State,
+        caller: &str,
+        contract_addr: &str,
+        fn_name: &str,
+        args: Vec<String>,
+        amount: u64,
+        native_value: u64,
+        gas_limit: u64,
+        call_stack: &[String],
+    ) -> Result<CallResult, BoxError> {
+        state.savepoint();
+        let result = self.call_frame(state, caller, contract_addr, fn_name, args, amount, native_value, gas_limit, call_stack);
+        match result {
+            Ok(ref r) if r.success => state.release_savepoint()?,
+            _ => state.rollback_savepoint(),
+        }
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn call_frame(
+        &self,
+        state: &mut State,
+        caller: &str,
+        contract_addr: &str,
+        fn_name: &str,
+        args: Vec<String>,
+        amount: u64,
+        native_value: u64,
+        gas_limit: u64,
+        call_stack: &[String],
     ) -> Result<CallResult, BoxError> {
+        let call_depth = call_stack.len();
         
         let contract = state.get_mosh_contract(contract_addr)?
             .ok_or_else(|| BoxError::from("Contract not found"))?;
         
-        let mut gas: u64 = 5000;
-        let now = Utc::now().timestamp() as u64;
+        // Even fixed-cost builtins stop at the limit
+        let mut gas: u64 = 0;
+        if Self::charge_gas(&mut gas, GAS_SCHEDULE.call, gas_limit).is_err() {
+            return Ok(CallResult::err(OUT_OF_GAS, gas));
+        }
+        let block = self.block_env(state);
+        let now = block.timestamp;
         
         // ========== AUTO GETTERS ==========
         // get_<var> - auto generated for all variables
         if fn_name.starts_with("get_") {
             let var_name = &fn_name[4..];
-            gas += 1000;
+            if Self::charge_gas(&mut gas, GAS_SCHEDULE.getter, gas_limit).is_err() {
+                return Ok(CallResult::err(OUT_OF_GAS, gas));
+            }
             
             // Reserved getters
             match var_name {
                 "owner" => return Ok(CallResult::ok(serde_json::json!(contract.owner), gas)),
+                "pending_owner" => return Ok(CallResult::ok(serde_json::json!(contract.pending_owner), gas)),
                 "creator" => return Ok(CallResult::ok(serde_json::json!(contract.creator), gas)),
                 "token" => return Ok(CallResult::ok(serde_json::json!(contract.token), gas)),
                 "address" => return Ok(CallResult::ok(serde_json::json!(contract.address), gas)),
@@ -352,6 +602,17 @@ impl MVM {
                 if args.is_empty() {
                     return Ok(CallResult::err("Missing key", gas));
                 }
+                if m.is_nested() {
+                    if args.len() < 2 {
+                        return Ok(CallResult::err("Need: key, key2", gas));
+                    }
+                    let val = state.get_mosh_map2(contract_addr, var_name, &args[0], &args[1])?.unwrap_or_default();
+                    return Ok(CallResult::ok(serde_json::json!({
+                        "key": &args[0],
+                        "key2": &args[1],
+                        "value": self.typed_value(&val, m.stored_type())
+                    }), gas));
+                }
                 let val = state.get_mosh_map(contract_addr, var_name, &args[0])?.unwrap_or_default();
                 return Ok(CallResult::ok(serde_json::json!({
                     "key": &args[0],
@@ -362,25 +623,118 @@ impl MVM {
             return Ok(CallResult::err(&format!("Unknown: {}", var_name), gas));
         }
         
+        // ========== OWNERSHIP ==========
+        // Second step of an ownership transfer: only the pending owner can accept
+        if fn_name == "accept_ownership" {
+            if Self::charge_gas(&mut gas, GAS_SCHEDULE.ownership, gas_limit).is_err() {
+                return Ok(CallResult::err(OUT_OF_GAS, gas));
+            }
+            if contract.pending_owner.as_deref() != Some(caller) {
+                return Ok(CallResult::err("Only pending owner", gas));
+            }
+            let mut updated = contract.clone();
+            updated.owner = caller.to_string();
+            updated.pending_owner = None;
+            state.save_mosh_contract(&updated)?;
+            return Ok(self.ownership_transferred(state, contract_addr, &contract.owner, caller, block, gas, call_depth));
+        }
+        
+        // Give up ownership for good; owner-only functions become uncallable
+        if fn_name == "renounce_ownership" {
+            if Self::charge_gas(&mut gas, GAS_SCHEDULE.ownership, gas_limit).is_err() {
+                return Ok(CallResult::err(OUT_OF_GAS, gas));
+            }
+            if caller != contract.owner {
+                return Ok(CallResult::err("Only owner", gas));
+            }
+            let mut updated = contract.clone();
+            updated.owner = RENOUNCED_OWNER.to_string();
+            updated.pending_owner = None;
+            state.save_mosh_contract(&updated)?;
+            return Ok(self.ownership_transferred(state, contract_addr, &contract.owner, RENOUNCED_OWNER, block, gas, call_depth));
+        }
+        
+        // ========== CIRCUIT BREAKER (Owner only) ==========
+        if fn_name == "pause" || fn_name == "unpause" {
+            if Self::charge_gas(&mut gas, GAS_SCHEDULE.ownership, gas_limit).is_err() {
+                return Ok(CallResult::err(OUT_OF_GAS, gas));
+            }
+            if caller != contract.owner {
+                return Ok(CallResult::err("Only owner", gas));
+            }
+            let pause = fn_name == "pause";
+            if contract.paused == pause {
+                return Ok(CallResult::err(if pause { "Already paused" } else { "Not paused" }, gas));
+            }
+            let mut updated = contract.clone();
+            updated.paused = pause;
+            state.save_mosh_contract(&updated)?;
+            
+            let event = ContractEvent {
+                name: if pause { "Paused" } else { "Unpaused" }.to_string(),
+                args: vec![serde_json::json!(caller)],
+                contract: contract_addr.to_string(),
+                block_height: block.height,
+                timestamp: now as i64,
+            };
+            if call_depth == 0 {
+                let _ = state.save_contract_event(&event);
+            }
+            return Ok(CallResult::ok_with_events(serde_json::json!({"paused": pause}), gas, vec![event]));
+        }
+        
         // ========== AUTO SETTERS (Owner only) ==========
         if fn_name.starts_with("set_") {
             let var_name = &fn_name[4..];
-            gas += 5000;
+            if Self::charge_gas(&mut gas, GAS_SCHEDULE.setter, gas_limit).is_err() {
+                return Ok(CallResult::err(OUT_OF_GAS, gas));
+            }
             
             // Owner check
             if caller != contract.owner {
                 return Ok(CallResult::err("Only owner", gas));
             }
+            if contract.paused {
+                return Ok(CallResult::err(CONTRACT_PAUSED, gas));
+            }
             
-            // Transfer ownership
-            if var_name == "owner" {
+            // Transfer ownership (two-step: the new owner must call accept_ownership)
+            if var_name == "owner" || var_name == "pending_owner" {
                 if args.is_empty() {
                     return Ok(CallResult::err("Missing address", gas));
                 }
+                if !Address::new(&args[0]).is_valid() {
+                    return Ok(CallResult::err(&format!("Invalid address: {}", args[0]), gas));
+                }
+                let mut updated = contract.clone();
+                updated.pending_owner = Some(args[0].clone());
+                state.save_mosh_contract(&updated)?;
+                return Ok(CallResult::ok(serde_json::json!({"pending_owner": &args[0]}), gas));
+            }
+            
+            // Link (or relink) the contract's token
+            if var_name == "token" {
+                if args.is_empty() {
+                    return Ok(CallResult::err("Missing token address", gas));
+                }
+                if state.get_token(&args[0])?.is_none() {
+                    return Ok(CallResult::err(&format!("Token not found: {}", args[0]), gas));
+                }
                 let mut updated = contract.clone();
-                updated.owner = args[0].clone();
+                updated.token = Some(args[0].clone());
                 state.save_mosh_contract(&updated)?;
-                return Ok(CallResult::ok(serde_json::json!({"new_owner": &args[0]}), gas));
+                
+                let event = ContractEvent {
+                    name: "TokenLinked".to_string(),
+                    args: vec![serde_json::json!(&args[0]), serde_json::json!(caller)],
+                    contract: contract_addr.to_string(),
+                    block_height: block.height,
+                    timestamp: now as i64,
+                };
+                if call_depth == 0 {
+                    let _ = state.save_contract_event(&event);
+                }
+                return Ok(CallResult::ok_with_events(serde_json::json!({"token": &args[0]}), gas, vec![event]));
             }
             
             // User variable
@@ -388,15 +742,42 @@ impl MVM {
                 if args.is_empty() {
                     return Ok(CallResult::err("Missing value", gas));
                 }
+                if args[0].len() > MAX_STRING_LENGTH {
+                    return Ok(CallResult::err(&format!("Value exceeds max length ({})", MAX_STRING_LENGTH), gas));
+                }
+                if let Err(msg) = self.check_arg(var_name, &args[0], &v.var_type) {
+                    return Ok(CallResult::err(&msg, gas));
+                }
                 state.set_mosh_var(contract_addr, var_name, &args[0])?;
                 return Ok(CallResult::ok(self.typed_value(&args[0], &v.var_type), gas));
             }
             
             // Mapping: set_mapname(key, value)
-            if contract.mappings.iter().any(|x| x.name == var_name) {
+            if let Some(m) = contract.mappings.iter().find(|x| x.name == var_name && x.is_nested()) {
+                if args.len() < 3 {
+                    return Ok(CallResult::err("Need: key, key2, value", gas));
+                }
+                if args[2].len() > MAX_STRING_LENGTH {
+                    return Ok(CallResult::err(&format!("Value exceeds max length ({})", MAX_STRING_LENGTH), gas));
+                }
+                let typed = [("key", &m.key_type), ("key2", &m.value_type), ("value", m.stored_type())];
+                if let Some(msg) = typed.iter().zip(&args).find_map(|((name, t), val)| self.check_arg(name, val, t).err()) {
+                    return Ok(CallResult::err(&msg, gas));
+                }
+                state.set_mosh_map2(contract_addr, &m.name, &args[0], &args[1], &args[2])?;
+                return Ok(CallResult::ok(serde_json::json!({"key": &args[0], "key2": &args[1], "value": &args[2]}), gas));
+            }
+            if let Some(m) = contract.mappings.iter().find(|x| x.name == var_name) {
                 if args.len() < 2 {
                     return Ok(CallResult::err("Need: key, value", gas));
                 }
+                if args[1].len() > MAX_STRING_LENGTH {
+                    return Ok(CallResult::err(&format!("Value exceeds max length ({})", MAX_STRING_LENGTH), gas));
+                }
+                let typed = [("key", &m.key_type), ("value", &m.value_type)];
+                if let Some(msg) = typed.iter().zip(&args).find_map(|((name, t), val)| self.check_arg(name, val, t).err()) {
+                    return Ok(CallResult::err(&msg, gas));
+                }
                 state.set_mosh_map(contract_addr, var_name, &args[0], &args[1])?;
                 return Ok(CallResult::ok(serde_json::json!({"key": &args[0], "value": &args[1]}), gas));
             }
@@ -411,18 +792,25 @@ impl MVM {
         }
         let func = func.unwrap();
         
-        gas += 10000;
+        if Self::charge_gas(&mut gas, GAS_SCHEDULE.function, gas_limit).is_err() {
+            return Ok(CallResult::err(OUT_OF_GAS, gas));
+        }
+        
+        if let Some(msg) = self.check_args(&func.args, &args) {
+            return Ok(CallResult::err(&msg, gas));
+        }
+        if contract.paused && !func.modifiers.contains(&FnModifier::View) {
+            return Ok(CallResult::err(CONTRACT_PAUSED, gas));
+        }
         
         // Check modifiers
         if func.modifiers.contains(&FnModifier::OnlyOwner) && caller != contract.owner {
             return Ok(CallResult::err("Only owner", gas));
         }
-        if func.modifiers.contains(&FnModifier::Payable) {
-            if contract.token.is_none() {
-                return Ok(CallResult::err("No token linked", gas));
-            }
+        if func.modifiers.contains(&FnModifier::Payable) && amount > 0 && contract.token.is_none() {
+            return Ok(CallResult::err("No token linked", gas));
         }
-        if !func.modifiers.contains(&FnModifier::Payable) && amount > 0 {
+        if !func.modifiers.contains(&FnModifier::Payable) && (amount > 0 || native_value > 0) {
             return Ok(CallResult::err("Function not payable", gas));
         }
         
@@ -430,10 +818,14 @@ impl MVM {
         let mut ctx = ExecContext {
             caller: caller.to_string(),
             amount,
-            block_height: state.get_height().unwrap_or(0),
+            native_value,
+            block_height: block.height,
             block_timestamp: now,
             args: HashMap::new(),
             locals: HashMap::new(),
+            call_depth,
+            call_stack: [call_stack, &[contract_addr.to_string()]].concat(),
+            gas_limit,
         };
         
         // Map args
@@ -449,9 +841,10 @@ impl MVM {
             if caller_bal < amount {
                 return Ok(CallResult::err(&format!("Insufficient: {} < {}", caller_bal, amount), gas));
             }
-            state.set_token_balance(token_addr, caller, caller_bal - amount)?;
             let contract_bal = state.get_token_balance(token_addr, contract_addr)?;
-            state.set_token_balance(token_addr, contract_addr, contract_bal + amount)?;
+            let contract_updated = contract_bal.checked_add(amount).ok_or("Token balance overflow")?;
+            state.set_token_balance(token_addr, caller, caller_bal - amount)?;
+            state.set_token_balance(token_addr, contract_addr, contract_updated)?;
         }
 
         // Execute operations using recursive helper
@@ -459,15 +852,17 @@ impl MVM {
         let mut return_value: Option<serde_json::Value> = None;
 
         let exec_result = self.execute_ops(
-            state, &contract, contract_addr, &func.body,
+            &mut StateAccess::ReadWrite(state), &contract, contract_addr, &func.body,
             &mut ctx, &mut gas, &mut events, &mut return_value, 0,
         );
 
         match exec_result {
             Ok(()) => {
                 // Save events to state
-                for event in &events {
-                    let _ = state.save_contract_event(event);
+                if call_depth == 0 {
+                    for event in &events {
+                        let _ = state.save_contract_event(event);
+                    }
                 }
                 Ok(CallResult::ok_with_events(
                     return_value.unwrap_or(serde_json::json!({"success": true})),
@@ -487,10 +882,97 @@ impl MVM {
         }
     }
 
+    /// Free read-only call of a `view` function. Runs the same op executor as
+    /// `call`, but any op that writes to storage fails the call. Views are
+    /// free but still stop at `gas_limit`.
+    pub fn view(
+        &self,
+        state: &State,
+        caller: &str,
+        contract_addr: &str,
+        fn_name: &str,
+        args: Vec<String>,
+        gas_limit: u64,
+    ) -> Result<CallResult, BoxError> {
+        self.view_at_depth(state, caller, contract_addr, fn_name, args, gas_limit, &[])
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn view_at_depth(
+        &self,
+        state: &State,
+        caller: &str,
+        contract_addr: &str,
+        fn_name: &str,
+        args: Vec<String>,
+        gas_limit: u64,
+        call_stack: &[String],
+    ) -> Result<CallResult, BoxError> {
+        let call_depth = call_stack.len();
+        let contract = state.get_mosh_contract(contract_addr)?
+            .ok_or_else(|| BoxError::from("Contract not found"))?;
+
+        let func = match contract.functions.iter().find(|f| f.name == fn_name) {
+            Some(f) => f,
+            None => return Ok(CallResult::err(&format!("Function not found: {}", fn_name), 0)),
+        };
+        if !func.modifiers.contains(&FnModifier::View) {
+            return Ok(CallResult::err(&format!("Function '{}' is not a view function", fn_name), 0));
+        }
+        if let Some(msg) = self.check_args(&func.args, &args) {
+            return Ok(CallResult::err(&msg, 0));
+        }
+
+        let block = self.block_env(state);
+        let mut ctx = ExecContext {
+            caller: caller.to_string(),
+            amount: 0,
+            native_value: 0,
+            block_height: block.height,
+            block_timestamp: block.timestamp,
+            args: HashMap::new(),
+            locals: HashMap::new(),
+            call_depth,
+            call_stack: [call_stack, &[contract_addr.to_string()]].concat(),
+            gas_limit,
+        };
+        for (i, arg_def) in func.args.iter().enumerate() {
+            let val = args.get(i).cloned().unwrap_or_default();
+            ctx.args.insert(arg_def.name.clone(), val);
+        }
+
+        let mut gas: u64 = 0;
+        let mut events: Vec<ContractEvent> = Vec::new();
+        let mut return_value: Option<serde_json::Value> = None;
+
+        let exec_result = self.execute_ops(
+            &mut StateAccess::ReadOnly(state), &contract, contract_addr, &func.body,
+            &mut ctx, &mut gas, &mut events, &mut return_value, 0,
+        );
+
+        match exec_result {
+            Ok(()) => {
+                let data = match return_value {
+                    Some(serde_json::Value::String(s)) => match func.returns {
+                        Some(ref t) => self.typed_value(&s, t),
+                        None => self.infer_value(&s),
+                    },
+                    Some(v) => v,
+                    None => serde_json::Value::Null,
+                };
+                Ok(CallResult::ok(data, gas))
+            }
+            Err(e) => {
+                let msg = e.to_string();
+                Ok(CallResult::err(msg.strip_prefix("GUARD:").unwrap_or(&msg), gas))
+            }
+        }
+    }
+
     /// Recursive operation executor — supports if/else nesting
     fn execute_ops(
         &self,
-        state: &mut State,
+        state: &mut StateAccess,
         contract: &MoshContract,
         contract_addr: &str,
         ops: &[Operation],
@@ -505,7 +987,7 @@ impl MVM {
         }
 
         for op in ops {
-            *gas += 1000;
+            Self::charge_gas(gas, GAS_SCHEDULE.op, ctx.gas_limit)?;
 
             // Normalize opcode: guard → require, signal → emit
             let op_name = match op.op.as_str() {
@@ -518,125 +1000,175 @@ impl MVM {
                 // SET variable
                 "set" => {
                     let var = op.var.as_deref().unwrap_or("");
-                    let value = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
-                    state.set_mosh_var(contract_addr, var, &value)?;
+                    let value = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    self.store_var(state, contract_addr, ctx, op_name, var, &value)?;
                 }
 
                 // ADD to variable
                 "add" => {
                     let var = op.var.as_deref().unwrap_or("");
-                    let add_val = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
-                    let current = state.get_mosh_var(contract_addr, var)?.unwrap_or("0".to_string());
-                    let new_val = current.parse::<u64>().unwrap_or(0) + add_val.parse::<u64>().unwrap_or(0);
-                    state.set_mosh_var(contract_addr, var, &new_val.to_string())?;
+                    let add_val = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    let current = self.load_var(state.get(), contract_addr, ctx, var)?;
+                    let new_val = current.parse::<u64>().unwrap_or(0).checked_add(add_val.parse::<u64>().unwrap_or(0))
+                        .ok_or_else(|| format!("Overflow adding to {}", var))?;
+                    self.store_var(state, contract_addr, ctx, op_name, var, &new_val.to_string())?;
                 }
 
                 // SUB from variable
                 "sub" => {
                     let var = op.var.as_deref().unwrap_or("");
-                    let sub_val = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
-                    let current = state.get_mosh_var(contract_addr, var)?.unwrap_or("0".to_string());
+                    let sub_val = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    let current = self.load_var(state.get(), contract_addr, ctx, var)?;
                     let new_val = current.parse::<u64>().unwrap_or(0).saturating_sub(sub_val.parse::<u64>().unwrap_or(0));
-                    state.set_mosh_var(contract_addr, var, &new_val.to_string())?;
+                    self.store_var(state, contract_addr, ctx, op_name, var, &new_val.to_string())?;
                 }
 
                 // MUL variable
                 "mul" => {
                     let var = op.var.as_deref().unwrap_or("");
-                    let mul_val = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
-                    let current = state.get_mosh_var(contract_addr, var)?.unwrap_or("0".to_string());
+                    let mul_val = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    let current = self.load_var(state.get(), contract_addr, ctx, var)?;
                     let new_val = current.parse::<u64>().unwrap_or(0).saturating_mul(mul_val.parse::<u64>().unwrap_or(0));
-                    state.set_mosh_var(contract_addr, var, &new_val.to_string())?;
+                    self.store_var(state, contract_addr, ctx, op_name, var, &new_val.to_string())?;
                 }
 
                 // DIV variable
                 "div" => {
                     let var = op.var.as_deref().unwrap_or("");
-                    let div_val = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
-                    let current = state.get_mosh_var(contract_addr, var)?.unwrap_or("0".to_string());
+                    let div_val = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    let current = self.load_var(state.get(), contract_addr, ctx, var)?;
                     let divisor = div_val.parse::<u64>().unwrap_or(0).max(1); // Zero protection
                     let new_val = current.parse::<u64>().unwrap_or(0) / divisor;
-                    state.set_mosh_var(contract_addr, var, &new_val.to_string())?;
+                    self.store_var(state, contract_addr, ctx, op_name, var, &new_val.to_string())?;
                 }
 
                 // MOD variable
                 "mod" => {
                     let var = op.var.as_deref().unwrap_or("");
-                    let mod_val = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
-                    let current = state.get_mosh_var(contract_addr, var)?.unwrap_or("0".to_string());
+                    let mod_val = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    let current = self.load_var(state.get(), contract_addr, ctx, var)?;
                     let divisor = mod_val.parse::<u64>().unwrap_or(0).max(1);
                     let new_val = current.parse::<u64>().unwrap_or(0) % divisor;
-                    state.set_mosh_var(contract_addr, var, &new_val.to_string())?;
+                    self.store_var(state, contract_addr, ctx, op_name, var, &new_val.to_string())?;
                 }
 
                 // MAP_SET
                 "map_set" => {
                     let map = op.map.as_deref().unwrap_or("");
-                    let key = self.resolve_value(state, contract, ctx, op.key.as_ref())?;
-                    let value = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
-                    state.set_mosh_map(contract_addr, map, &key, &value)?;
+                    let key = self.resolve_value(state.get(), contract, ctx, op.key.as_ref())?;
+                    let key2 = self.resolve_key2(state.get(), contract, ctx, op)?;
+                    let value = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    Self::check_length(map, &value)?;
+                    Self::map_write(state.get_mut(op_name)?, contract_addr, map, &key, key2.as_deref(), &value)?;
+                }
+
+                // MAP_DEL - remove a mapping entry
+                "map_del" => {
+                    let map = op.map.as_deref().unwrap_or("");
+                    let key = self.resolve_value(state.get(), contract, ctx, op.key.as_ref())?;
+                    let key2 = self.resolve_key2(state.get(), contract, ctx, op)?;
+                    Self::map_delete(state.get_mut(op_name)?, contract_addr, map, &key, key2.as_deref())?;
+                }
+
+                // DEL - reset a variable to its type's zero value
+                "del" => {
+                    let var = op.var.as_deref().unwrap_or("");
+                    let var_def = contract.variables.iter().find(|v| v.name == var)
+                        .ok_or_else(|| format!("Unknown variable: {}", var))?;
+                    state.get_mut(op_name)?.set_mosh_var(contract_addr, var, var_def.var_type.zero_value())?;
                 }
 
                 // MAP_ADD
                 "map_add" => {
                     let map = op.map.as_deref().unwrap_or("");
-                    let key = self.resolve_value(state, contract, ctx, op.key.as_ref())?;
-                    let add_val = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
-                    let current = state.get_mosh_map(contract_addr, map, &key)?.unwrap_or("0".to_string());
-                    let new_val = current.parse::<u64>().unwrap_or(0) + add_val.parse::<u64>().unwrap_or(0);
-                    state.set_mosh_map(contract_addr, map, &key, &new_val.to_string())?;
+                    let key = self.resolve_value(state.get(), contract, ctx, op.key.as_ref())?;
+                    let key2 = self.resolve_key2(state.get(), contract, ctx, op)?;
+                    let add_val = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    let current = Self::map_read(state.get(), contract_addr, map, &key, key2.as_deref())?.unwrap_or("0".to_string());
+                    let new_val = current.parse::<u64>().unwrap_or(0).checked_add(add_val.parse::<u64>().unwrap_or(0))
+                        .ok_or_else(|| format!("Overflow adding to {}[{}]", map, key))?;
+                    Self::map_write(state.get_mut(op_name)?, contract_addr, map, &key, key2.as_deref(), &new_val.to_string())?;
                 }
 
                 // MAP_SUB
                 "map_sub" => {
                     let map = op.map.as_deref().unwrap_or("");
-                    let key = self.resolve_value(state, contract, ctx, op.key.as_ref())?;
-                    let sub_val = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
-                    let current = state.get_mosh_map(contract_addr, map, &key)?.unwrap_or("0".to_string());
+                    let key = self.resolve_value(state.get(), contract, ctx, op.key.as_ref())?;
+                    let key2 = self.resolve_key2(state.get(), contract, ctx, op)?;
+                    let sub_val = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    let current = Self::map_read(state.get(), contract_addr, map, &key, key2.as_deref())?.unwrap_or("0".to_string());
                     let new_val = current.parse::<u64>().unwrap_or(0).saturating_sub(sub_val.parse::<u64>().unwrap_or(0));
-                    state.set_mosh_map(contract_addr, map, &key, &new_val.to_string())?;
+                    Self::map_write(state.get_mut(op_name)?, contract_addr, map, &key, key2.as_deref(), &new_val.to_string())?;
                 }
 
                 // MAP_MUL
                 "map_mul" => {
                     let map = op.map.as_deref().unwrap_or("");
-                    let key = self.resolve_value(state, contract, ctx, op.key.as_ref())?;
-                    let mul_val = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
-                    let current = state.get_mosh_map(contract_addr, map, &key)?.unwrap_or("0".to_string());
+                    let key = self.resolve_value(state.get(), contract, ctx, op.key.as_ref())?;
+                    let key2 = self.resolve_key2(state.get(), contract, ctx, op)?;
+                    let mul_val = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    let current = Self::map_read(state.get(), contract_addr, map, &key, key2.as_deref())?.unwrap_or("0".to_string());
                     let new_val = current.parse::<u64>().unwrap_or(0).saturating_mul(mul_val.parse::<u64>().unwrap_or(0));
-                    state.set_mosh_map(contract_addr, map, &key, &new_val.to_string())?;
+                    Self::map_write(state.get_mut(op_name)?, contract_addr, map, &key, key2.as_deref(), &new_val.to_string())?;
                 }
 
                 // MAP_DIV
                 "map_div" => {
                     let map = op.map.as_deref().unwrap_or("");
-                    let key = self.resolve_value(state, contract, ctx, op.key.as_ref())?;
-                    let div_val = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
-                    let current = state.get_mosh_map(contract_addr, map, &key)?.unwrap_or("0".to_string());
+                    let key = self.resolve_value(state.get(), contract, ctx, op.key.as_ref())?;
+                    let key2 = self.resolve_key2(state.get(), contract, ctx, op)?;
+                    let div_val = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    let current = Self::map_read(state.get(), contract_addr, map, &key, key2.as_deref())?.unwrap_or("0".to_string());
                     let divisor = div_val.parse::<u64>().unwrap_or(0).max(1);
                     let new_val = current.parse::<u64>().unwrap_or(0) / divisor;
-                    state.set_mosh_map(contract_addr, map, &key, &new_val.to_string())?;
+                    Self::map_write(state.get_mut(op_name)?, contract_addr, map, &key, key2.as_deref(), &new_val.to_string())?;
                 }
 
                 // MAP_MOD
                 "map_mod" => {
                     let map = op.map.as_deref().unwrap_or("");
-                    let key = self.resolve_value(state, contract, ctx, op.key.as_ref())?;
-                    let mod_val = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
-                    let current = state.get_mosh_map(contract_addr, map, &key)?.unwrap_or("0".to_string());
+                    let key = self.resolve_value(state.get(), contract, ctx, op.key.as_ref())?;
+                    let key2 = self.resolve_key2(state.get(), contract, ctx, op)?;
+                    let mod_val = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    let current = Self::map_read(state.get(), contract_addr, map, &key, key2.as_deref())?.unwrap_or("0".to_string());
                     let divisor = mod_val.parse::<u64>().unwrap_or(0).max(1);
                     let new_val = current.parse::<u64>().unwrap_or(0) % divisor;
-                    state.set_mosh_map(contract_addr, map, &key, &new_val.to_string())?;
+                    Self::map_write(state.get_mut(op_name)?, contract_addr, map, &key, key2.as_deref(), &new_val.to_string())?;
+                }
+
+                // MAP_GET - read a mapping entry into a local
+                "map_get" => {
+                    let var = op.var.as_deref().unwrap_or("");
+                    let map = op.map.as_deref().unwrap_or("");
+                    let key = self.resolve_value(state.get(), contract, ctx, op.key.as_ref())?;
+                    let key2 = self.resolve_key2(state.get(), contract, ctx, op)?;
+                    let value = Self::map_read(state.get(), contract_addr, map, &key, key2.as_deref())?.unwrap_or_default();
+                    ctx.locals.insert(var.to_string(), value);
                 }
 
                 // REQUIRE / GUARD - check condition
                 "require" => {
-                    let left = self.resolve_value(state, contract, ctx, op.left.as_ref())?;
+                    let left = self.resolve_value(state.get(), contract, ctx, op.left.as_ref())?;
                     let cmp = op.cmp.as_deref().unwrap_or(">");
-                    let right = self.resolve_value(state, contract, ctx, op.right.as_ref())?;
+                    let right = self.resolve_value(state.get(), contract, ctx, op.right.as_ref())?;
                     let msg = op.msg.as_deref().unwrap_or("Require failed");
 
-                    if !self.eval_condition(&left, cmp, &right) {
+                    // (left cmp right && every `and`) || any `or`
+                    let mut holds = self.eval_condition(&left, cmp, &right);
+                    for cond in op.and.iter().flatten() {
+                        if !holds {
+                            break;
+                        }
+                        holds = self.eval_condition_expr(state.get(), contract, ctx, cond)?;
+                    }
+                    for cond in op.or.iter().flatten() {
+                        if holds {
+                            break;
+                        }
+                        holds = self.eval_condition_expr(state.get(), contract, ctx, cond)?;
+                    }
+
+                    if !holds {
                         return Err(format!("GUARD:{}", msg).into());
                     }
                 }
@@ -644,8 +1176,8 @@ impl MVM {
                 // IF/ELSE control flow
                 "if" => {
                     let cond = op.condition.as_ref().ok_or("if: missing condition")?;
-                    let left = self.resolve_value(state, contract, ctx, Some(&cond.left))?;
-                    let right = self.resolve_value(state, contract, ctx, Some(&cond.right))?;
+                    let left = self.resolve_value(state.get(), contract, ctx, Some(&cond.left))?;
+                    let right = self.resolve_value(state.get(), contract, ctx, Some(&cond.right))?;
 
                     if self.eval_condition(&left, &cond.cmp, &right) {
                         if let Some(ref body) = op.then_body {
@@ -656,15 +1188,39 @@ impl MVM {
                     }
                 }
 
+                // REPEAT - run the body `count` times with the index in `loop.i`
+                "repeat" => {
+                    let count_str = self.resolve_value(state.get(), contract, ctx, op.count.as_ref())?;
+                    let count = count_str.parse::<u64>()
+                        .map_err(|_| format!("repeat: invalid count '{}'", count_str))?;
+                    if count > MAX_REPEAT_COUNT {
+                        return Err(format!("repeat: count {} exceeds max {}", count, MAX_REPEAT_COUNT).into());
+                    }
+                    let body = op.body.as_deref().unwrap_or_default();
+
+                    // Nested loops shadow the outer index and restore it afterwards
+                    let outer_index = ctx.locals.remove("loop.i");
+                    for i in 0..count {
+                        ctx.locals.insert("loop.i".to_string(), i.to_string());
+                        self.execute_ops(state, contract, contract_addr, body, ctx, gas, events, return_value, depth + 1)?;
+                    }
+                    match outer_index {
+                        Some(index) => { ctx.locals.insert("loop.i".to_string(), index); }
+                        None => { ctx.locals.remove("loop.i"); }
+                    }
+                }
+
                 // EMIT / SIGNAL - emit event
                 "emit" => {
+                    // Events are persisted with the call, so views may not emit
+                    state.get_mut(op_name)?;
                     let event_name = op.event_name.as_deref()
                         .or(op.var.as_deref())
                         .unwrap_or("Event");
                     let mut resolved_args = Vec::new();
                     if let Some(ref args_list) = op.event_args {
                         for arg in args_list {
-                            let resolved = self.resolve_value(state, contract, ctx, Some(arg))?;
+                            let resolved = self.resolve_value(state.get(), contract, ctx, Some(arg))?;
                             resolved_args.push(serde_json::json!(resolved));
                         }
                     }
@@ -684,10 +1240,11 @@ impl MVM {
                         None => return Err("No token".into()),
                     };
 
-                    let to = self.resolve_value(state, contract, ctx, op.to.as_ref())?;
-                    let amt = self.resolve_value(state, contract, ctx, op.amount.as_ref())?;
+                    let to = self.resolve_value(state.get(), contract, ctx, op.to.as_ref())?;
+                    let amt = self.resolve_value(state.get(), contract, ctx, op.amount.as_ref())?;
                     let amt_num = amt.parse::<u64>().unwrap_or(0);
 
+                    let state = state.get_mut(op_name)?;
                     let contract_bal = state.get_token_balance(&token_addr, contract_addr)?;
                     if contract_bal < amt_num {
                         return Err("Contract balance low".into());
@@ -695,19 +1252,99 @@ impl MVM {
 
                     state.set_token_balance(&token_addr, contract_addr, contract_bal - amt_num)?;
                     let to_bal = state.get_token_balance(&token_addr, &to)?;
-                    state.set_token_balance(&token_addr, &to, to_bal + amt_num)?;
+                    state.set_token_balance(&token_addr, &to, to_bal.checked_add(amt_num).ok_or("Token balance overflow")?)?;
+                }
+
+                // TRANSFER_NATIVE - pay native MVM out of the contract's own balance.
+                // Every check runs before the first write, so a failure moves nothing.
+                "transfer_native" => {
+                    let to = self.resolve_value(state.get(), contract, ctx, op.to.as_ref())?;
+                    let amt = self.resolve_value(state.get(), contract, ctx, op.amount.as_ref())?;
+                    let amt_num = amt.parse::<u64>().map_err(|_| format!("transfer_native: invalid amount: {}", amt))?;
+                    if !Address::new(&to).is_valid() {
+                        return Err(format!("transfer_native: invalid recipient: {}", to).into());
+                    }
+
+                    let state = state.get_mut(op_name)?;
+                    let contract_bal = state.get_balance(contract_addr)?;
+                    if contract_bal < amt_num {
+                        return Err("Contract native balance low".into());
+                    }
+                    if to != contract_addr {
+                        let to_bal = state.get_balance(&to)?;
+                        let to_updated = to_bal.checked_add(amt_num).ok_or("Balance overflow")?;
+                        state.set_balance(contract_addr, contract_bal - amt_num)?;
+                        state.set_balance(&to, to_updated)?;
+                    }
                 }
 
                 // RETURN value
                 "return" => {
-                    let val = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
+                    let val = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
                     *return_value = Some(serde_json::json!(val));
                 }
 
+                // CALL another Mosh contract; the callee sees this contract as caller
+                "call" => {
+                    if ctx.call_depth + 1 > MAX_CALL_DEPTH {
+                        return Err("Max call depth exceeded".into());
+                    }
+                    let target = self.resolve_value(state.get(), contract, ctx, op.contract.as_ref())?;
+                    // A contract can't be re-entered while any call into it is still running
+                    if ctx.call_stack.contains(&target) {
+                        return Err(REENTRANCY.into());
+                    }
+                    let method = op.method.as_deref().ok_or("call: missing method")?;
+                    let mut call_args = Vec::new();
+                    if let Some(ref args_list) = op.args {
+                        for arg in args_list {
+                            call_args.push(self.resolve_value(state.get(), contract, ctx, Some(arg))?);
+                        }
+                    }
+
+                    // The callee may only spend what the caller has left
+                    let remaining = ctx.gas_limit.saturating_sub(*gas);
+                    let result = match state {
+                        StateAccess::ReadOnly(s) => self.view_at_depth(
+                            s, contract_addr, &target, method, call_args, remaining, &ctx.call_stack,
+                        )?,
+                        StateAccess::ReadWrite(s) => self.call_at_depth(
+                            s, contract_addr, &target, method, call_args, 0, 0, remaining, &ctx.call_stack,
+                        )?,
+                    };
+                    Self::charge_gas(gas, result.gas_used, ctx.gas_limit)?;
+                    if !result.success {
+                        return Err(result.error.unwrap_or("Sub-call failed".to_string()).into());
+                    }
+                    events.extend(result.events);
+
+                    if let Some(var) = op.var.as_deref() {
+                        let value = match result.data {
+                            Some(serde_json::Value::String(s)) => s,
+                            Some(v) => v.to_string(),
+                            None => String::new(),
+                        };
+                        ctx.locals.insert(var.to_string(), value);
+                    }
+                }
+
+                // CONCAT - join resolved operands into a variable
+                "concat" => {
+                    let var = op.var.as_deref().unwrap_or("");
+                    let mut joined = String::new();
+                    if let Some(ref parts) = op.args {
+                        for part in parts {
+                            joined.push_str(&self.resolve_value(state.get(), contract, ctx, Some(part))?);
+                        }
+                    }
+                    self.store_var(state, contract_addr, ctx, op_name, var, &joined)?;
+                }
+
                 // LET - local variable
                 "let" => {
                     let var = op.var.as_deref().unwrap_or("");
-                    let value = self.resolve_value(state, contract, ctx, op.value.as_ref())?;
+                    let value = self.resolve_value(state.get(), contract, ctx, op.value.as_ref())?;
+                    Self::check_length(var, &value)?;
                     ctx.locals.insert(var.to_string(), value);
                 }
 
@@ -720,8 +1357,129 @@ impl MVM {
         Ok(())
     }
 
+    /// Read a variable target for arithmetic ops — locals shadow contract storage
+    fn load_var(
+        &self,
+        state: &State,
+        contract_addr: &str,
+        ctx: &ExecContext,
+        var: &str,
+    ) -> Result<String, BoxError> {
+        if let Some(local) = ctx.locals.get(var) {
+            return Ok(local.clone());
+        }
+        Ok(state.get_mosh_var(contract_addr, var)?.unwrap_or("0".to_string()))
+    }
+
+    /// Write a variable target — locals stay in the context, everything else hits storage
+    fn store_var(
+        &self,
+        state: &mut StateAccess,
+        contract_addr: &str,
+        ctx: &mut ExecContext,
+        op: &str,
+        var: &str,
+        value: &str,
+    ) -> Result<(), BoxError> {
+        Self::check_length(var, value)?;
+        if let Some(local) = ctx.locals.get_mut(var) {
+            *local = value.to_string();
+            return Ok(());
+        }
+        state.get_mut(op)?.set_mosh_var(contract_addr, var, value)
+    }
+
+    /// Add `amount` to the running gas total, failing once it passes the limit
+    fn charge_gas(gas: &mut u64, amount: u64, gas_limit: u64) -> Result<(), BoxError> {
+        *gas += amount;
+        if *gas > gas_limit {
+            return Err(OUT_OF_GAS.into());
+        }
+        Ok(())
+    }
+
+    /// Second key of a nested mapping op, if the op has one
+    fn resolve_key2(
+        &self,
+        state: &State,
+        contract: &MoshContract,
+        ctx: &ExecContext,
+        op: &Operation,
+    ) -> Result<Option<String>, BoxError> {
+        match op.key2 {
+            Some(ref k) => Ok(Some(self.resolve_value(state, contract, ctx, Some(k))?)),
+            None => Ok(None),
+        }
+    }
+
+    fn map_read(state: &State, contract_addr: &str, map: &str, key: &str, key2: Option<&str>) -> Result<Option<String>, BoxError> {
+        match key2 {
+            Some(k2) => state.get_mosh_map2(contract_addr, map, key, k2),
+            None => state.get_mosh_map(contract_addr, map, key),
+        }
+    }
+
+    fn map_write(state: &mut State, contract_addr: &str, map: &str, key: &str, key2: Option<&str>, value: &str) -> Result<(), BoxError> {
+        match key2 {
+            Some(k2) => state.set_mosh_map2(contract_addr, map, key, k2, value),
+            None => state.set_mosh_map(contract_addr, map, key, value),
+        }
+    }
+
+    fn map_delete(state: &mut State, contract_addr: &str, map: &str, key: &str, key2: Option<&str>) -> Result<(), BoxError> {
+        match key2 {
+            Some(k2) => state.delete_mosh_map2(contract_addr, map, key, k2),
+            None => state.delete_mosh_map(contract_addr, map, key),
+        }
+    }
+
+    /// Reject string values longer than `MAX_STRING_LENGTH` before they are stored
+    fn check_length(target: &str, value: &str) -> Result<(), BoxError> {
+        if value.len() > MAX_STRING_LENGTH {
+            return Err(format!("Value for '{}' exceeds max length ({} > {})", target, value.len(), MAX_STRING_LENGTH).into());
+        }
+        Ok(())
+    }
+
+    /// Resolve both sides of `cond` and compare them
+    fn eval_condition_expr(
+        &self,
+        state: &State,
+        contract: &MoshContract,
+        ctx: &ExecContext,
+        cond: &ConditionExpr,
+    ) -> Result<bool, BoxError> {
+        let left = self.resolve_value(state, contract, ctx, Some(&cond.left))?;
+        let right = self.resolve_value(state, contract, ctx, Some(&cond.right))?;
+        Ok(self.eval_condition(&left, &cond.cmp, &right))
+    }
+
+    /// Both operands as booleans, when at least one is `true`/`false` and the
+    /// other is a boolean too (`1`/`0` accepted, as set by numeric writes)
+    fn bool_operands(left: &str, right: &str) -> Option<(bool, bool)> {
+        let literal = |s: &str| s == "true" || s == "false";
+        if !literal(left) && !literal(right) {
+            return None;
+        }
+        let as_bool = |s: &str| match s {
+            "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        };
+        Some((as_bool(left)?, as_bool(right)?))
+    }
+
     /// Evaluate a comparison condition
     fn eval_condition(&self, left: &str, cmp: &str, right: &str) -> bool {
+        // Booleans only support equality
+        if let Some((l, r)) = Self::bool_operands(left, right) {
+            return match cmp {
+                "==" | "=" => l == r,
+                "!=" => l != r,
+                _ => false,
+            };
+        }
+
         let left_num = left.parse::<u64>().unwrap_or(0);
         let right_num = right.parse::<u64>().unwrap_or(0);
 
@@ -732,6 +1490,8 @@ impl MVM {
             "<=" => left_num <= right_num,
             "==" | "=" => left == right,
             "!=" => left != right,
+            "contains" => left.contains(right),
+            "starts_with" => left.starts_with(right),
             _ => false,
         }
     }
@@ -754,8 +1514,9 @@ impl MVM {
             // Special values (standard + Mosh aliases)
             match s {
                 "msg.sender" => return Ok(ctx.caller.clone()),
-                "msg.amount" | "msg.value" => return Ok(ctx.amount.to_string()),
-                "block.height" | "mosh.height" => return Ok(ctx.block_height.to_string()),
+                "msg.amount" => return Ok(ctx.amount.to_string()),
+                "msg.value" => return Ok(ctx.native_value.to_string()),
+                "block.height" | "block.number" | "mosh.height" => return Ok(ctx.block_height.to_string()),
                 "block.timestamp" | "mosh.time" => return Ok(ctx.block_timestamp.to_string()),
                 "contract.owner" => return Ok(contract.owner.clone()),
                 "contract.address" => return Ok(contract.address.clone()),
@@ -770,6 +1531,15 @@ impl MVM {
                 _ => {}
             }
             
+            // Native MVM balance of an address: balance:<expr> or balance(<expr>).
+            // Read-only, and reflects the balance at call time.
+            let balance_of = s.strip_prefix("balance:")
+                .or_else(|| s.strip_prefix("balance(").and_then(|rest| rest.strip_suffix(')')));
+            if let Some(expr) = balance_of {
+                let address = self.resolve_value(state, contract, ctx, Some(&serde_json::json!(expr)))?;
+                return Ok(state.get_balance(&address)?.to_string());
+            }
+            
             // Check if it's an argument
             if let Some(arg_val) = ctx.args.get(s) {
                 return Ok(arg_val.clone());
@@ -785,9 +1555,15 @@ impl MVM {
                 return Ok(state.get_mosh_var(&contract.address, s)?.unwrap_or_default());
             }
             
-            // Check if it's a mapping access: mapname[key]
+            // Check if it's a mapping access: mapname[key] or mapname[key][key2]
             if s.contains('[') && s.ends_with(']') {
                 let parts: Vec<&str> = s.trim_end_matches(']').split('[').collect();
+                if parts.len() == 3 {
+                    let map_name = parts[0];
+                    let key = self.resolve_value(state, contract, ctx, Some(&serde_json::json!(parts[1].trim_end_matches(']'))))?;
+                    let key2 = self.resolve_value(state, contract, ctx, Some(&serde_json::json!(parts[2])))?;
+                    return Ok(state.get_mosh_map2(&contract.address, map_name, &key, &key2)?.unwrap_or_default());
+                }
                 if parts.len() == 2 {
                     let map_name = parts[0];
                     let key_expr = parts[1];
@@ -816,6 +1592,53 @@ impl MVM {
         Ok(val.to_string())
     }
     
+    /// Result of an ownership change, with its `OwnershipTransferred(from, to)` event
+    #[allow(clippy::too_many_arguments)]
+    fn ownership_transferred(
+        &self,
+        state: &mut State,
+        contract_addr: &str,
+        from: &str,
+        to: &str,
+        block: BlockEnv,
+        gas: u64,
+        call_depth: usize,
+    ) -> CallResult {
+        let event = ContractEvent {
+            name: "OwnershipTransferred".to_string(),
+            args: vec![serde_json::json!(from), serde_json::json!(to)],
+            contract: contract_addr.to_string(),
+            block_height: block.height,
+            timestamp: block.timestamp as i64,
+        };
+        if call_depth == 0 {
+            let _ = state.save_contract_event(&event);
+        }
+        CallResult::ok_with_events(serde_json::json!({"previous_owner": from, "new_owner": to}), gas, vec![event])
+    }
+
+    /// Check that a raw call argument parses as its declared type, so a bad
+    /// value is rejected instead of silently reading as 0 / false later
+    fn check_arg(&self, name: &str, val: &str, var_type: &VarType) -> Result<(), String> {
+        let ok = match var_type {
+            VarType::Uint64 => val.parse::<u64>().is_ok(),
+            VarType::Bool => val == "true" || val == "false",
+            VarType::Address => Address::new(val).is_valid(),
+            VarType::String => true,
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("Invalid {:?} for '{}': {}", var_type, name, val))
+        }
+    }
+
+    /// `check_arg` over the provided arguments of a user function; missing
+    /// trailing arguments are left to the function body
+    fn check_args(&self, defs: &[FnArg], args: &[String]) -> Option<String> {
+        defs.iter().zip(args).find_map(|(def, val)| self.check_arg(&def.name, val, &def.arg_type).err())
+    }
+
     fn typed_value(&self, val: &str, var_type: &VarType) -> serde_json::Value {
         match var_type {
             VarType::Uint64 => serde_json::json!(val.parse::<u64>().unwrap_or(0)),
@@ -824,18 +1647,66 @@ impl MVM {
         }
     }
 
-    /// Legacy compatibility
+    /// Best-effort typing for untyped return values (number, bool, then string)
+    fn infer_value(&self, val: &str) -> serde_json::Value {
+        if let Ok(n) = val.parse::<u64>() {
+            serde_json::json!(n)
+        } else if val == "true" || val == "false" {
+            serde_json::json!(val == "true")
+        } else {
+            serde_json::json!(val)
+        }
+    }
+
+    /// Store a raw code contract at an address derived from `creator` and `nonce`
+    pub fn deploy_code(
+        &self,
+        state: &mut State,
+        creator: &str,
+        name: &str,
+        code: &[u8],
+        nonce: u64,
+    ) -> Result<String, BoxError> {
+        if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+            return Err(format!("Name: 1-{} chars", MAX_NAME_LENGTH).into());
+        }
+        if code.is_empty() || code.len() > MAX_CODE_SIZE {
+            return Err(format!("Code: 1-{} bytes", MAX_CODE_SIZE).into());
+        }
+
+        let address = Address::derived("mvm1code", creator, nonce).to_string();
+        if state.get_code_contract(&address)?.is_some() {
+            return Err(format!("Contract already exists: {}", address).into());
+        }
+
+        state.save_code_contract(&CodeContract {
+            address: address.clone(),
+            name: name.to_string(),
+            creator: creator.to_string(),
+            code: code.to_vec(),
+            created_at: self.block_env(state).timestamp as i64,
+        })?;
+        Ok(address)
+    }
+
+    /// Legacy compatibility: Mosh contracts run normally under `gas_limit`;
+    /// raw code contracts get a stub with a single `value` slot behind `set`
+    /// and `get`
     pub fn execute_call(
-        &mut self,
+        &self,
         state: &mut State,
         contract: &str,
         method: &str,
         args: &[String],
+        gas_limit: u64,
     ) -> Result<Option<serde_json::Value>, BoxError> {
         if contract.starts_with("mvm1contract") {
-            let result = self.call(state, "", contract, method, args.to_vec(), 0)?;
+            let result = self.call(state, "", contract, method, args.to_vec(), 0, gas_limit)?;
             if result.success { Ok(result.data) } else { Err(result.error.unwrap_or("Error".into()).into()) }
         } else {
+            if state.get_code_contract(contract)?.is_none() {
+                return Err(format!("Contract not found: {}", contract).into());
+            }
             if method == "set" && !args.is_empty() {
                 state.set_mosh_var(contract, "value", &args[0])?;
                 Ok(None)
@@ -850,4 +1721,538 @@ impl MVM {
 
 impl Default for MVM {
     fn default() -> Self { Self::new() }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::Keypair;
+    use crate::state::test_state;
+
+    /// Deploy a contract with one `check` function per require op and report
+    /// which of them pass
+    fn require_results(requires: Vec<serde_json::Value>) -> Vec<bool> {
+        let (mut state, _dir) = test_state(&format!("require-{}", requires.len()));
+        let creator = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+
+        let variables: Vec<VarDef> = serde_json::from_value(serde_json::json!([
+            {"name": "paused", "var_type": "Bool", "default": "false"},
+            {"name": "count", "var_type": "Uint64", "default": "5"},
+            {"name": "label", "var_type": "String", "default": "mosh"}
+        ])).unwrap();
+        let functions: Vec<FnDef> = requires.iter().enumerate()
+            .map(|(i, op)| serde_json::from_value(serde_json::json!({
+                "name": format!("check{}", i),
+                "modifiers": ["Write"],
+                "body": [op]
+            })).unwrap())
+            .collect();
+        let address = vm.deploy(&mut state, &creator, "Guards", None, variables, vec![], functions, 0).unwrap();
+
+        (0..requires.len())
+            .map(|i| vm.call(&mut state, &creator, &address, &format!("check{}", i), vec![], 0, 100_000)
+                .map(|r| r.success)
+                .unwrap_or(false))
+            .collect()
+    }
+
+    #[test]
+    fn test_code_contract_stores_blob_and_runs_stub() {
+        let (mut state, _dir) = test_state("code");
+        let vm = MVM::new();
+
+        let address = vm.deploy_code(&mut state, "mvm1alice", "Blob", &[0x60, 0x01], 0).unwrap();
+        assert!(address.starts_with("mvm1code"));
+        assert_eq!(state.get_code_contract(&address).unwrap().unwrap().code, vec![0x60, 0x01]);
+        assert!(vm.deploy_code(&mut state, "mvm1alice", "Blob", &[0x60], 0).is_err());
+        assert!(vm.deploy_code(&mut state, "mvm1alice", "Empty", &[], 1).is_err());
+
+        vm.execute_call(&mut state, &address, "set", &["7".to_string()], 0).unwrap();
+        assert_eq!(vm.execute_call(&mut state, &address, "get", &[], 0).unwrap(), Some(serde_json::json!("7")));
+        assert!(vm.execute_call(&mut state, "mvm1codemissing", "get", &[], 0).is_err());
+    }
+
+    #[test]
+    fn test_require_compares_bools_numbers_and_strings() {
+        let results = require_results(vec![
+            serde_json::json!({"op": "require", "left": "paused", "cmp": "==", "right": false}),
+            serde_json::json!({"op": "require", "left": "paused", "cmp": "==", "right": true}),
+            serde_json::json!({"op": "require", "left": "paused", "cmp": "!=", "right": "0"}),
+            serde_json::json!({"op": "require", "left": "count", "cmp": ">", "right": 4}),
+            serde_json::json!({"op": "require", "left": "count", "cmp": "<", "right": 5}),
+            serde_json::json!({"op": "require", "left": "label", "cmp": "==", "right": "mosh"}),
+            serde_json::json!({"op": "require", "left": "label", "cmp": "starts_with", "right": "x"}),
+        ]);
+        assert_eq!(results, vec![true, false, false, true, false, true, false]);
+    }
+
+    #[test]
+    fn test_require_and_or_conditions() {
+        let results = require_results(vec![
+            // count > 4 && paused == false
+            serde_json::json!({"op": "require", "left": "count", "cmp": ">", "right": 4,
+                "and": [{"left": "paused", "cmp": "==", "right": false}]}),
+            // count > 4 && label == "other"
+            serde_json::json!({"op": "require", "left": "count", "cmp": ">", "right": 4,
+                "and": [{"left": "label", "cmp": "==", "right": "other"}]}),
+            // count > 9 || label == "mosh"
+            serde_json::json!({"op": "require", "left": "count", "cmp": ">", "right": 9,
+                "or": [{"left": "label", "cmp": "==", "right": "mosh"}]}),
+            // count > 9 || paused == true
+            serde_json::json!({"op": "require", "left": "count", "cmp": ">", "right": 9,
+                "or": [{"left": "paused", "cmp": "==", "right": true}]}),
+        ]);
+        assert_eq!(results, vec![true, false, true, false]);
+    }
+
+    #[test]
+    fn test_time_lock_uses_block_timestamp() {
+        let (mut state, _dir) = test_state("timelock");
+        let creator = Keypair::generate().address().to_string();
+        let mut vm = MVM::new();
+
+        let variables: Vec<VarDef> = serde_json::from_value(serde_json::json!([
+            {"name": "locked_until", "var_type": "Uint64", "default": "1900000000"}
+        ])).unwrap();
+        let functions: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "withdraw", "modifiers": ["Write"], "body": [
+                {"op": "require", "left": "block.timestamp", "cmp": ">=", "right": "locked_until", "msg": "Still locked"}
+            ]},
+            {"name": "after", "modifiers": ["Write"], "args": [{"name": "height", "arg_type": "Uint64"}], "body": [
+                {"op": "require", "left": "block.number", "cmp": ">", "right": "height"}
+            ]}
+        ])).unwrap();
+        let address = vm.deploy(&mut state, &creator, "Vesting", None, variables, vec![], functions, 0).unwrap();
+
+        let mut call = |vm: &MVM, method: &str, args: Vec<String>| {
+            vm.call(&mut state, &creator, &address, method, args, 0, 100_000).unwrap()
+        };
+
+        vm.set_block(Some(BlockEnv { height: 7, timestamp: 1_899_999_999 }));
+        let locked = call(&vm, "withdraw", vec![]);
+        assert!(!locked.success);
+        assert_eq!(locked.error.as_deref(), Some("Still locked"));
+        assert!(call(&vm, "after", vec!["6".to_string()]).success);
+        assert!(!call(&vm, "after", vec!["7".to_string()]).success);
+
+        vm.set_block(Some(BlockEnv { height: 8, timestamp: 1_900_000_000 }));
+        assert!(call(&vm, "withdraw", vec![]).success);
+    }
+
+    #[test]
+    fn test_call_args_are_checked_against_declared_types() {
+        let (mut state, _dir) = test_state("args");
+        let creator = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+
+        let variables: Vec<VarDef> = serde_json::from_value(serde_json::json!([
+            {"name": "paused", "var_type": "Bool", "default": "false"}
+        ])).unwrap();
+        let mappings: Vec<MappingDef> = serde_json::from_value(serde_json::json!([
+            {"name": "balances", "key_type": "Address", "value_type": "Uint64"}
+        ])).unwrap();
+        let functions: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "credit", "modifiers": ["Write"],
+             "args": [{"name": "to", "arg_type": "Address"}, {"name": "amount", "arg_type": "Uint64"}],
+             "body": [{"op": "map_set", "map": "balances", "key": "to", "value": "amount"}]}
+        ])).unwrap();
+        let address = vm.deploy(&mut state, &creator, "Ledger", None, variables, mappings, functions, 0).unwrap();
+
+        let mut call = |method: &str, args: &[&str]| {
+            vm.call(&mut state, &creator, &address, method, args.iter().map(|a| a.to_string()).collect(), 0, 100_000).unwrap()
+        };
+
+        let bad_amount = call("credit", &[&creator, "abc"]);
+        assert_eq!(bad_amount.error.as_deref(), Some("Invalid Uint64 for 'amount': abc"));
+        assert!(call("credit", &["mvm1nobody", "5"]).error.unwrap().contains("'to'"));
+        assert!(call("credit", &[&creator, "5"]).success);
+
+        assert!(call("set_paused", &["yes"]).error.unwrap().contains("'paused'"));
+        assert!(call("set_paused", &["true"]).success);
+        assert!(call("set_balances", &[&creator, "-1"]).error.unwrap().contains("'value'"));
+    }
+
+    #[test]
+    fn test_require_native_balance_of_caller() {
+        let (mut state, _dir) = test_state("native-bal");
+        let rich = Keypair::generate().address().to_string();
+        let poor = Keypair::generate().address().to_string();
+        state.set_balance(&rich, 100).unwrap();
+        state.set_balance(&poor, 99).unwrap();
+        let vm = MVM::new();
+
+        let functions: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "members_only", "modifiers": ["Write"], "body": [
+                {"op": "require", "left": "balance(msg.sender)", "cmp": ">=", "right": 100, "msg": "Hold 100 MVM"}
+            ]},
+            {"name": "balance_of", "modifiers": ["View"], "args": [{"name": "who", "arg_type": "Address"}], "body": [
+                {"op": "return", "value": "balance:who"}
+            ]}
+        ])).unwrap();
+        let address = vm.deploy(&mut state, &rich, "Club", None, vec![], vec![], functions, 0).unwrap();
+
+        assert!(vm.call(&mut state, &rich, &address, "members_only", vec![], 0, 100_000).unwrap().success);
+        let denied = vm.call(&mut state, &poor, &address, "members_only", vec![], 0, 100_000).unwrap();
+        assert_eq!(denied.error.as_deref(), Some("Hold 100 MVM"));
+        let seen = vm.view(&state, &rich, &address, "balance_of", vec![poor.clone()], 100_000).unwrap();
+        assert_eq!(seen.data, Some(serde_json::json!(99)));
+    }
+
+    #[test]
+    fn test_transfer_native_pays_out_of_contract_balance() {
+        let (mut state, _dir) = test_state("native-pay");
+        let owner = Keypair::generate().address().to_string();
+        let payee = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+
+        let functions: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "refund", "modifiers": ["Write"],
+             "args": [{"name": "to", "arg_type": "Address"}, {"name": "amount", "arg_type": "Uint64"}],
+             "body": [{"op": "transfer_native", "to": "to", "amount": "amount"}]}
+        ])).unwrap();
+        let address = vm.deploy(&mut state, &owner, "Refunds", None, vec![], vec![], functions, 0).unwrap();
+        state.set_balance(&address, 500).unwrap();
+
+        let mut refund = |amount: &str| {
+            vm.call(&mut state, &owner, &address, "refund", vec![payee.clone(), amount.to_string()], 0, 100_000).unwrap()
+        };
+        assert!(refund("200").success);
+        assert_eq!(refund("301").error.as_deref(), Some("Contract native balance low"));
+        assert_eq!(state.get_balance(&address).unwrap(), 300);
+        assert_eq!(state.get_balance(&payee).unwrap(), 200);
+    }
+
+    #[test]
+    fn test_msg_amount_is_tokens_and_msg_value_is_native() {
+        let (mut state, _dir) = test_state("msg-value");
+        let owner = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+
+        let variables: Vec<VarDef> = serde_json::from_value(serde_json::json!([
+            {"name": "tokens", "var_type": "Uint64", "default": "0"},
+            {"name": "native", "var_type": "Uint64", "default": "0"}
+        ])).unwrap();
+        let functions: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "pay", "modifiers": ["Payable"], "body": [
+                {"op": "set", "var": "tokens", "value": "msg.amount"},
+                {"op": "set", "var": "native", "value": "msg.value"}
+            ]}
+        ])).unwrap();
+        let token = crate::standards::create_mvm20_token(&mut state, &owner, "Till", "TIL", 7, 0, 0, 0).unwrap();
+        let address = vm.deploy(&mut state, &owner, "Till", Some(token), variables, vec![], functions, 1).unwrap();
+
+        assert!(vm.call_with_value(&mut state, &owner, &address, "pay", vec![], 7, 9, 100_000).unwrap().success);
+        assert_eq!(state.get_mosh_var(&address, "tokens").unwrap().as_deref(), Some("7"));
+        assert_eq!(state.get_mosh_var(&address, "native").unwrap().as_deref(), Some("9"));
+    }
+
+    #[test]
+    fn test_call_into_executing_contract_is_reentrancy() {
+        let (mut state, _dir) = test_state("reentry");
+        let creator = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+
+        let functions: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "relay", "modifiers": ["Write"], "args": [{"name": "target", "arg_type": "Address"}], "body": [
+                {"op": "call", "contract": "target", "method": "bounce", "args": ["contract.address"]}
+            ]},
+            {"name": "bounce", "modifiers": ["Write"], "args": [{"name": "back", "arg_type": "Address"}], "body": [
+                {"op": "call", "contract": "back", "method": "ping"}
+            ]},
+            {"name": "ping", "modifiers": ["Write"], "body": []}
+        ])).unwrap();
+        let a = vm.deploy(&mut state, &creator, "A", None, vec![], vec![], functions.clone(), 0).unwrap();
+        let b = vm.deploy(&mut state, &creator, "B", None, vec![], vec![], functions, 1).unwrap();
+
+        // A -> B -> A re-enters A
+        let reentered = vm.call(&mut state, &creator, &a, "relay", vec![b.clone()], 0, 100_000).unwrap();
+        assert_eq!(reentered.error.as_deref(), Some(REENTRANCY));
+        // B -> A is a plain nested call
+        assert!(vm.call(&mut state, &creator, &b, "bounce", vec![a.clone()], 0, 100_000).unwrap().success);
+    }
+
+    #[test]
+    fn test_failed_parent_undoes_nested_call_writes() {
+        let (mut state, _dir) = test_state("nested-undo");
+        let creator = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+
+        let variables: Vec<VarDef> = serde_json::from_value(serde_json::json!([
+            {"name": "count", "var_type": "Uint64", "default": "0"}
+        ])).unwrap();
+        let counter: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "bump", "modifiers": ["Write"], "body": [{"op": "add", "var": "count", "value": 1}]}
+        ])).unwrap();
+        let relay: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "relay", "modifiers": ["Write"], "args": [{"name": "target", "arg_type": "Address"}], "body": [
+                {"op": "call", "contract": "target", "method": "bump"}
+            ]},
+            {"name": "relay_then_fail", "modifiers": ["Write"], "args": [{"name": "target", "arg_type": "Address"}], "body": [
+                {"op": "set", "var": "count", "value": 9},
+                {"op": "call", "contract": "target", "method": "bump"},
+                {"op": "require", "left": 1, "cmp": "==", "right": 2, "message": "nope"}
+            ]}
+        ])).unwrap();
+        let b = vm.deploy(&mut state, &creator, "Counter", None, variables.clone(), vec![], counter, 0).unwrap();
+        let a = vm.deploy(&mut state, &creator, "Relay", None, variables, vec![], relay, 1).unwrap();
+
+        let failed = vm.call(&mut state, &creator, &a, "relay_then_fail", vec![b.clone()], 0, 100_000).unwrap();
+        assert!(!failed.success);
+        assert_eq!(state.get_mosh_var(&a, "count").unwrap().as_deref(), Some("0"));
+        assert_eq!(state.get_mosh_var(&b, "count").unwrap().as_deref(), Some("0"));
+
+        assert!(vm.call(&mut state, &creator, &a, "relay", vec![b.clone()], 0, 100_000).unwrap().success);
+        assert_eq!(state.get_mosh_var(&b, "count").unwrap().as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_out_of_gas_call_keeps_no_writes() {
+        let (mut state, _dir) = test_state("out-of-gas");
+        let creator = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+
+        let variables: Vec<VarDef> = serde_json::from_value(serde_json::json!([
+            {"name": "count", "var_type": "Uint64", "default": "0"}
+        ])).unwrap();
+        let functions: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "spend", "modifiers": ["Write"], "body": [
+                {"op": "set", "var": "count", "value": 1},
+                {"op": "add", "var": "count", "value": 1},
+                {"op": "add", "var": "count", "value": 1}
+            ]}
+        ])).unwrap();
+        let address = vm.deploy(&mut state, &creator, "Spender", None, variables, vec![], functions, 0).unwrap();
+
+        // call + function + two ops fit, the third op does not
+        let limit = GAS_SCHEDULE.call + GAS_SCHEDULE.function + 2 * GAS_SCHEDULE.op + GAS_SCHEDULE.op / 2;
+        let ran = vm.call(&mut state, &creator, &address, "spend", vec![], 0, limit).unwrap();
+        assert_eq!(ran.error.as_deref(), Some(OUT_OF_GAS));
+        assert_eq!(state.get_mosh_var(&address, "count").unwrap().as_deref(), Some("0"));
+    }
+
+    #[test]
+    fn test_add_that_overflows_fails_the_call() {
+        let (mut state, _dir) = test_state("add-overflow");
+        let creator = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+
+        let variables: Vec<VarDef> = serde_json::from_value(serde_json::json!([
+            {"name": "total", "var_type": "Uint64", "default": u64::MAX.to_string()}
+        ])).unwrap();
+        let mappings: Vec<MappingDef> = serde_json::from_value(serde_json::json!([
+            {"name": "balances", "key_type": "Address", "value_type": "Uint64"}
+        ])).unwrap();
+        let functions: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "bump", "modifiers": ["Write"], "body": [
+                {"op": "add", "var": "total", "value": 1}
+            ]},
+            {"name": "credit", "modifiers": ["Write"], "body": [
+                {"op": "map_add", "map": "balances", "key": "msg.sender", "value": u64::MAX.to_string()},
+                {"op": "map_add", "map": "balances", "key": "msg.sender", "value": 1}
+            ]}
+        ])).unwrap();
+        let address = vm.deploy(&mut state, &creator, "Overflow", None, variables, mappings, functions, 0).unwrap();
+
+        let bumped = vm.call(&mut state, &creator, &address, "bump", vec![], 0, 100_000).unwrap();
+        assert_eq!(bumped.error.as_deref(), Some("Overflow adding to total"));
+        assert_eq!(state.get_mosh_var(&address, "total").unwrap(), Some(u64::MAX.to_string()));
+
+        let credited = vm.call(&mut state, &creator, &address, "credit", vec![], 0, 100_000).unwrap();
+        assert!(!credited.success);
+        assert_eq!(state.get_mosh_map(&address, "balances", &creator).unwrap(), None);
+    }
+
+    #[test]
+    fn test_builtin_setter_stops_at_the_gas_limit() {
+        let (mut state, _dir) = test_state("setter-gas");
+        let creator = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+
+        let variables: Vec<VarDef> = serde_json::from_value(serde_json::json!([
+            {"name": "x", "var_type": "Uint64", "default": "0"}
+        ])).unwrap();
+        let address = vm.deploy(&mut state, &creator, "Settable", None, variables, vec![], vec![], 0).unwrap();
+
+        let limit = GAS_SCHEDULE.call + GAS_SCHEDULE.setter;
+        let starved = vm.call(&mut state, &creator, &address, "set_x", vec!["5".to_string()], 0, limit - 1).unwrap();
+        assert_eq!(starved.error.as_deref(), Some(OUT_OF_GAS));
+        assert_eq!(state.get_mosh_var(&address, "x").unwrap().as_deref(), Some("0"));
+
+        let ran = vm.call(&mut state, &creator, &address, "set_x", vec!["5".to_string()], 0, limit).unwrap();
+        assert!(ran.success);
+        assert_eq!(ran.gas_used, limit);
+        assert_eq!(state.get_mosh_var(&address, "x").unwrap().as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn test_paused_contract_rejects_writes_but_serves_reads() {
+        let (mut state, _dir) = test_state("pause");
+        let creator = Keypair::generate().address().to_string();
+        let other = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+
+        let variables: Vec<VarDef> = serde_json::from_value(serde_json::json!([
+            {"name": "count", "var_type": "Uint64", "default": "1"}
+        ])).unwrap();
+        let functions: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "bump", "modifiers": ["Write"], "body": [{"op": "add", "var": "count", "value": 1}]},
+            {"name": "peek", "modifiers": ["View"], "body": [{"op": "return", "value": "count"}]}
+        ])).unwrap();
+        let address = vm.deploy(&mut state, &creator, "Breaker", None, variables, vec![], functions, 0).unwrap();
+
+        let mut call = |caller: &str, method: &str, args: &[&str]| {
+            vm.call(&mut state, caller, &address, method, args.iter().map(|a| a.to_string()).collect(), 0, 100_000).unwrap()
+        };
+
+        assert_eq!(call(&other, "pause", &[]).error.as_deref(), Some("Only owner"));
+        let paused = call(&creator, "pause", &[]);
+        assert!(paused.success);
+        assert_eq!(paused.events[0].name, "Paused");
+        assert_eq!(call(&creator, "pause", &[]).error.as_deref(), Some("Already paused"));
+
+        assert_eq!(call(&creator, "bump", &[]).error.as_deref(), Some(CONTRACT_PAUSED));
+        assert_eq!(call(&creator, "set_count", &["9"]).error.as_deref(), Some(CONTRACT_PAUSED));
+        assert!(call(&creator, "get_count", &[]).success);
+        assert!(call(&creator, "peek", &[]).success);
+
+        assert!(call(&creator, "unpause", &[]).success);
+        assert!(call(&creator, "bump", &[]).success);
+        assert_eq!(call(&other, "get_count", &[]).data, Some(serde_json::json!(2)));
+    }
+
+    #[test]
+    fn test_set_token_links_an_existing_token_for_the_owner_only() {
+        let (mut state, _dir) = test_state("set-token");
+        let creator = Keypair::generate().address().to_string();
+        let other = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+        let token = crate::standards::create_mvm20_token(&mut state, &creator, "Gold", "GLD", 1_000, 8, 0, 0).unwrap();
+        let address = vm.deploy(&mut state, &creator, "Vault", None, vec![], vec![], vec![], 1).unwrap();
+
+        let mut call = |caller: &str, args: &[&str]| {
+            vm.call(&mut state, caller, &address, "set_token", args.iter().map(|a| a.to_string()).collect(), 0, 100_000).unwrap()
+        };
+        assert_eq!(call(&other, &[&token]).error.as_deref(), Some("Only owner"));
+        assert_eq!(call(&creator, &[]).error.as_deref(), Some("Missing token address"));
+        assert!(call(&creator, &["mvm1tokenmissing"]).error.unwrap().starts_with("Token not found"));
+
+        let linked = call(&creator, &[&token]);
+        assert!(linked.success);
+        assert_eq!(linked.events[0].name, "TokenLinked");
+        assert_eq!(linked.events[0].args, vec![serde_json::json!(token), serde_json::json!(creator)]);
+        assert_eq!(state.get_mosh_contract(&address).unwrap().unwrap().token, Some(token));
+    }
+
+    #[test]
+    fn test_ownership_moves_in_two_steps_and_can_be_renounced() {
+        let (mut state, _dir) = test_state("ownership");
+        let creator = Keypair::generate().address().to_string();
+        let heir = Keypair::generate().address().to_string();
+        let other = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+
+        let reserved: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "accept_ownership", "modifiers": ["Write"], "body": []}
+        ])).unwrap();
+        assert!(vm.deploy(&mut state, &creator, "Thief", None, vec![], vec![], reserved, 0).is_err());
+        let address = vm.deploy(&mut state, &creator, "Heirloom", None, vec![], vec![], vec![], 0).unwrap();
+
+        let mut call = |caller: &str, method: &str, args: &[&str]| {
+            vm.call(&mut state, caller, &address, method, args.iter().map(|a| a.to_string()).collect(), 0, 100_000).unwrap()
+        };
+        assert!(call(&creator, "set_owner", &["nobody"]).error.unwrap().starts_with("Invalid address"));
+        assert_eq!(call(&creator, "set_owner", &[&heir]).data, Some(serde_json::json!({"pending_owner": heir})));
+        // Nothing changes hands until the pending owner accepts
+        assert_eq!(call(&other, "get_owner", &[]).data, Some(serde_json::json!(creator)));
+        assert_eq!(call(&other, "get_pending_owner", &[]).data, Some(serde_json::json!(heir)));
+        assert_eq!(call(&other, "accept_ownership", &[]).error.as_deref(), Some("Only pending owner"));
+
+        let accepted = call(&heir, "accept_ownership", &[]);
+        assert_eq!(accepted.events[0].name, "OwnershipTransferred");
+        assert_eq!(accepted.events[0].args, vec![serde_json::json!(creator), serde_json::json!(heir)]);
+        assert_eq!(call(&other, "get_owner", &[]).data, Some(serde_json::json!(heir)));
+        assert_eq!(call(&other, "get_pending_owner", &[]).data, Some(serde_json::Value::Null));
+        assert_eq!(call(&creator, "set_owner", &[&other]).error.as_deref(), Some("Only owner"));
+
+        assert_eq!(call(&creator, "renounce_ownership", &[]).error.as_deref(), Some("Only owner"));
+        assert!(call(&heir, "renounce_ownership", &[]).success);
+        assert_eq!(call(&other, "get_owner", &[]).data, Some(serde_json::json!(RENOUNCED_OWNER)));
+        assert_eq!(call(&heir, "set_owner", &[&other]).error.as_deref(), Some("Only owner"));
+    }
+
+    #[test]
+    fn test_map_del_and_del_clear_state() {
+        let (mut state, _dir) = test_state("del");
+        let creator = Keypair::generate().address().to_string();
+        let other = Keypair::generate().address().to_string();
+        let vm = MVM::new();
+
+        let variables: Vec<VarDef> = serde_json::from_value(serde_json::json!([
+            {"name": "count", "var_type": "Uint64", "default": "7"},
+            {"name": "label", "var_type": "String", "default": "mosh"}
+        ])).unwrap();
+        let mappings: Vec<MappingDef> = serde_json::from_value(serde_json::json!([
+            {"name": "balances", "key_type": "Address", "value_type": "Uint64"}
+        ])).unwrap();
+        let functions: Vec<FnDef> = serde_json::from_value(serde_json::json!([
+            {"name": "forget", "modifiers": ["Write"],
+             "args": [{"name": "who", "arg_type": "Address"}],
+             "body": [{"op": "map_del", "map": "balances", "key": "who"}]},
+            {"name": "reset", "modifiers": ["Write"],
+             "body": [{"op": "del", "var": "count"}, {"op": "del", "var": "label"}]},
+            {"name": "sneaky", "modifiers": ["View"], "body": [{"op": "del", "var": "count"}]},
+            {"name": "bogus", "modifiers": ["Write"], "body": [{"op": "del", "var": "missing"}]}
+        ])).unwrap();
+        let address = vm.deploy(&mut state, &creator, "Eraser", None, variables, mappings, functions, 0).unwrap();
+
+        let mut call = |method: &str, args: &[&str]| {
+            vm.call(&mut state, &creator, &address, method, args.iter().map(|a| a.to_string()).collect(), 0, 100_000).unwrap()
+        };
+
+        assert!(call("set_balances", &[&creator, "5"]).success);
+        assert!(call("set_balances", &[&other, "9"]).success);
+        assert!(call("forget", &[&creator]).success);
+        assert!(!call("bogus", &[]).success);
+        assert!(call("reset", &[]).success);
+
+        let entries = state.get_all_mosh_map_entries(&address, "balances").unwrap();
+        assert_eq!(entries, vec![(other.clone(), "9".to_string())]);
+        assert_eq!(state.get_mosh_var(&address, "count").unwrap().as_deref(), Some("0"));
+        assert_eq!(state.get_mosh_var(&address, "label").unwrap().as_deref(), Some(""));
+
+        let view = vm.view(&state, &creator, &address, "sneaky", vec![], 100_000).unwrap();
+        assert!(view.error.unwrap().contains("cannot modify state"));
+    }
+
+    #[test]
+    fn test_deploy_is_reproducible_across_nodes() {
+        let creator = Keypair::generate().address().to_string();
+        let deploy_on_fresh_node = |node: &str| {
+            let (mut state, _dir) = test_state(&format!("deploy-{}", node));
+            let mut vm = MVM::new();
+            vm.set_block(Some(BlockEnv { height: 3, timestamp: 1_800_000_000 }));
+            let address = vm.deploy(&mut state, &creator, "Counter", None, vec![], vec![], vec![], 4).unwrap();
+            let contract = state.get_mosh_contract(&address).unwrap().unwrap();
+            (address, contract.created_at, contract.created_block)
+        };
+
+        let first = deploy_on_fresh_node("a");
+        assert_eq!(first, deploy_on_fresh_node("b"));
+        assert_eq!((first.1, first.2), (1_800_000_000, 3));
+    }
+
+    #[test]
+    fn test_schema_hash_matches_deployed_contract() {
+        let (mut state, _dir) = test_state("schema");
+        let creator = Keypair::generate().address().to_string();
+        let variables = vec![VarDef { name: "count".to_string(), var_type: VarType::Uint64, default: Some("0".to_string()) }];
+
+        let address = MVM::new().deploy(&mut state, &creator, "Counter", None, variables.clone(), vec![], vec![], 0).unwrap();
+        let deployed = state.get_mosh_contract(&address).unwrap().unwrap().schema_hash();
+
+        assert_eq!(deployed, schema_hash("Counter", &variables, &[], &[]));
+        assert_ne!(deployed, schema_hash("Counter2", &variables, &[], &[]));
+        assert_ne!(deployed, schema_hash("Counter", &[], &[], &[]));
+    }
+}
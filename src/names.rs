@@ -0,0 +1,69 @@
+//! Built-in name registry: human-readable names for addresses
+
+use crate::state::State;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+pub const MIN_NAME_LENGTH: usize = 3;
+pub const MAX_NAME_LENGTH: usize = 32;
+
+/// Check a name against `^[a-z0-9]([a-z0-9-]*[a-z0-9])?$` and the length limits
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.len() < MIN_NAME_LENGTH || name.len() > MAX_NAME_LENGTH {
+        return Err(format!("Name must be {} to {} characters", MIN_NAME_LENGTH, MAX_NAME_LENGTH));
+    }
+    if !name.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-') {
+        return Err(format!("Name \"{}\" may only use a-z, 0-9 and '-'", name));
+    }
+    if name.starts_with('-') || name.ends_with('-') {
+        return Err(format!("Name \"{}\" may not start or end with '-'", name));
+    }
+    Ok(())
+}
+
+/// Give `name` to `owner`, first come first served. The owner's first name
+/// becomes its primary name for reverse lookups.
+pub fn register_name(state: &mut State, name: &str, owner: &str) -> Result<(), BoxError> {
+    validate_name(name)?;
+    if let Some(holder) = state.resolve_name(name)? {
+        return Err(format!("Name \"{}\" is already registered to {}", name, holder).into());
+    }
+    state.set_name(name, owner)?;
+    if state.get_primary_name(owner)?.is_none() {
+        state.set_primary_name(owner, name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_state;
+
+    #[test]
+    fn test_validate_name_charset() {
+        assert!(validate_name("alice").is_ok());
+        assert!(validate_name("mohsin-2").is_ok());
+        assert!(validate_name("ab").is_err());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LENGTH + 1)).is_err());
+        assert!(validate_name("Alice").is_err());
+        assert!(validate_name("al.ice").is_err());
+        assert!(validate_name("-alice").is_err());
+        assert!(validate_name("alice-").is_err());
+    }
+
+    #[test]
+    fn test_register_name_first_come_first_served() {
+        let (mut state, _dir) = test_state("names");
+
+        register_name(&mut state, "alice", "mvm1alice").unwrap();
+        assert!(register_name(&mut state, "alice", "mvm1bob").is_err());
+        register_name(&mut state, "alice-alt", "mvm1alice").unwrap();
+
+        assert_eq!(state.resolve_name("alice").unwrap().as_deref(), Some("mvm1alice"));
+        assert_eq!(state.resolve_name("alice-alt").unwrap().as_deref(), Some("mvm1alice"));
+        // The first name stays primary
+        assert_eq!(state.get_primary_name("mvm1alice").unwrap().as_deref(), Some("alice"));
+        assert_eq!(state.get_primary_name("mvm1bob").unwrap(), None);
+    }
+}
@@ -4,7 +4,7 @@ pub mod mesh;
 use crate::chain::{Block, BoxError};
 use async_trait::async_trait;
 
-pub use star::StarNetwork;
+pub use star::{StarNetwork, P2PMessage};
 
 #[async_trait]
 pub trait Network: Send + Sync {
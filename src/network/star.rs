@@ -1,5 +1,7 @@
-use crate::chain::{Block, Blockchain, Transaction, BoxError};
+use crate::chain::{Block, Blockchain, ServiceNode, ServiceNodes, Transaction, BoxError};
 use crate::config::Config;
+use crate::address::{Keypair, verify_tx_signature, SIG_SCHEME_ED25519};
+use crate::mvm::ContractEvent;
 use crate::state::{State, StateSnapshot};
 use crate::network::Network;
 
@@ -9,8 +11,9 @@ use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{broadcast, RwLock, mpsc};
-use tracing::{info, error};
+use tokio::sync::{broadcast, RwLock, mpsc, oneshot};
+use tokio_tungstenite::{connect_async, tungstenite::Message as WsMessage};
+use tracing::{info, warn, error, debug};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
@@ -18,16 +21,167 @@ pub enum P2PMessage {
     Hello { node_id: String, node_type: String },
     Welcome { node_id: String, height: u64, peers: Vec<String> },
     GetState,
-    StateSnapshot(StateSnapshot),
+    /// Like GetState, but also requests nonces, tokens and contract state
+    GetFullState,
+    StateSnapshot(Box<StateSnapshot>),
     NewBlock(Block),
     GetBlock { height: u64 },
     BlockResponse(Option<Block>),
-    SubmitTx(Transaction),
+    /// Single contract reads for light clients that don't want a snapshot
+    GetContractVar { contract: String, var: String },
+    ContractVarResponse { value: Option<String> },
+    GetMapEntry { contract: String, map: String, key: String },
+    MapEntryResponse { value: Option<String> },
+    SubmitTx(Box<Transaction>),
     TxConfirmed { hash: String },
+    TxRejected { hash: String, error: String },
+    /// Follower -> master, on connect and every `STATS_REPORT_INTERVAL_SECS`:
+    /// live browser/peer counts and where to pay the node's service rewards
+    StatsReport(StatsReport),
     Ping,
     Pong,
 }
 
+/// A follower's counts, signed by the node key behind its payout `address`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsReport {
+    pub node_id: String,
+    pub browsers: u32,
+    pub peers: u32,
+    pub address: String,
+    /// Unix time the report was signed; stale reports are ignored
+    pub timestamp: i64,
+    pub public_key: String,
+    pub signature: String,
+}
+
+impl StatsReport {
+    fn signed(node_id: &str, browsers: u32, peers: u32, keypair: &Keypair, timestamp: i64) -> Self {
+        let mut report = StatsReport {
+            node_id: node_id.to_string(),
+            browsers,
+            peers,
+            address: keypair.address().to_string(),
+            timestamp,
+            public_key: keypair.public_key_hex(),
+            signature: String::new(),
+        };
+        report.signature = keypair.sign_hex(&report.sign_message());
+        report
+    }
+
+    fn sign_message(&self) -> Vec<u8> {
+        format!("stats_report:{}:{}:{}:{}:{}", self.node_id, self.browsers, self.peers, self.address, self.timestamp).into_bytes()
+    }
+
+    /// Signed by the key behind `address` within `STATS_REPORT_MAX_AGE_SECS` of `now`
+    fn verify(&self, now: i64) -> bool {
+        (now - self.timestamp).abs() <= STATS_REPORT_MAX_AGE_SECS
+            && verify_tx_signature(&self.address, &self.sign_message(), &self.signature, &self.public_key, SIG_SCHEME_ED25519)
+                .unwrap_or(false)
+    }
+}
+
+/// Upper bound for the follower's reconnect delay
+const MAX_RECONNECT_BACKOFF_SECS: u64 = 30;
+
+/// Resolves a forwarded tx with the master's verdict
+type TxAck = oneshot::Sender<Result<String, String>>;
+
+/// How long a follower waits for the master to acknowledge a forwarded tx
+const TX_ACK_TIMEOUT_SECS: u64 = 10;
+
+/// How often a follower sends the master its `StatsReport`
+const STATS_REPORT_INTERVAL_SECS: u64 = 10;
+
+/// How far a `StatsReport`'s timestamp may be from the master's clock
+const STATS_REPORT_MAX_AGE_SECS: i64 = 3 * STATS_REPORT_INTERVAL_SECS as i64;
+
+/// How often the master pings each connected peer
+const PING_INTERVAL_SECS: u64 = 15;
+
+/// A peer that hasn't answered a ping for this long is dropped. Followers use
+/// the same limit for silence from the master before reconnecting.
+const PONG_TIMEOUT_SECS: u64 = 45;
+
+/// Check a block names `producer` as its validator and is signed by the key
+/// behind that address. The key travels with the block, but it only passes
+/// if it hashes to `producer`, so the trust comes from the configured address.
+fn block_signature_ok(block: &Block, producer: &str) -> bool {
+    block.validator == producer && block.verify_signature(&block.public_key).unwrap_or(false)
+}
+
+/// How far past the local clock a peer block's timestamp may be. The timestamp
+/// decides whose turn a block falls in, so it can't be set freely.
+const MAX_BLOCK_FUTURE_SECS: i64 = 15;
+
+/// The address allowed to produce `block` on top of `parent`: the validator
+/// whose turn it is at the block's timestamp when a validator set is
+/// configured, else the master named in `genesis.master_address`
+fn expected_producer(config: &Config, block: &Block, parent: &Block) -> Result<String, BoxError> {
+    if !config.validators.addresses.is_empty() {
+        if block.timestamp < parent.timestamp {
+            return Err(format!("timestamp {} is before its parent's {}", block.timestamp, parent.timestamp).into());
+        }
+        if block.timestamp > chrono::Utc::now().timestamp() + MAX_BLOCK_FUTURE_SECS {
+            return Err(format!("timestamp {} is too far in the future", block.timestamp).into());
+        }
+        let elapsed = (block.timestamp - parent.timestamp) as u64;
+        if let Some(validator) = config.validators.validator_at(block.height, elapsed) {
+            return Ok(validator.to_string());
+        }
+    }
+    if !config.genesis.master_address.is_empty() {
+        return Ok(config.genesis.master_address.clone());
+    }
+    Err("no validator set or genesis.master_address to check the producer against".into())
+}
+
+/// Outcome of importing a block received from a peer
+enum BlockImport {
+    Applied,
+    /// Block height is past local tip + 1; missing blocks must be fetched first
+    Ahead { local_height: u64 },
+    /// Already have this height
+    Known,
+}
+
+/// Validate a peer block against the local tip, then replay and persist it
+async fn import_block(blockchain: &Arc<RwLock<Blockchain>>, config: &Config, block: &Block) -> Result<BlockImport, BoxError> {
+    if !block.is_valid() {
+        return Err("hash mismatch".into());
+    }
+
+    let mut bc = blockchain.write().await;
+    let tip = {
+        let state_guard = bc.state.read().await;
+        let local_height = state_guard.get_height()?;
+        if block.height <= local_height {
+            return Ok(BlockImport::Known);
+        }
+        if block.height > local_height + 1 {
+            return Ok(BlockImport::Ahead { local_height });
+        }
+        state_guard.get_block(local_height)?.ok_or("local tip missing")?
+    };
+    if block.prev_hash != tip.hash {
+        return Err(format!("prev_hash {} does not match local tip {}", block.prev_hash, tip.hash).into());
+    }
+    let producer = expected_producer(config, block, &tip)?;
+    if block.validator != producer {
+        return Err(format!("produced by {}, but height {} belongs to {}", block.validator, block.height, producer).into());
+    }
+    if !block_signature_ok(block, &producer) {
+        return Err("invalid signature".into());
+    }
+
+    bc.import_block(block).await?;
+    for tx in &block.transactions {
+        bc.remove_transaction(&tx.hash);
+    }
+    Ok(BlockImport::Applied)
+}
+
 #[derive(Clone)]
 pub struct ConnectedPeer {
     pub node_id: String,
@@ -35,13 +189,24 @@ pub struct ConnectedPeer {
     pub tx: mpsc::Sender<P2PMessage>,
 }
 
+#[derive(Clone)]
 pub struct StarNetwork {
     config: Config,
     blockchain: Arc<RwLock<Blockchain>>,
     state: Arc<RwLock<State>>,
     peers: Arc<RwLock<HashMap<String, ConnectedPeer>>>,
     browsers: Arc<RwLock<HashMap<String, mpsc::Sender<P2PMessage>>>>,
-    block_tx: broadcast::Sender<Block>,
+    /// Transactions entering the local mempool, for browsers that opt in
+    tx_tx: broadcast::Sender<Transaction>,
+    /// Contract events from locally executed txs, for browsers that opt in
+    event_tx: broadcast::Sender<ContractEvent>,
+    /// Outbound channel to the master while a follower session is up
+    master: Arc<RwLock<Option<mpsc::Sender<P2PMessage>>>>,
+    /// Forwarded txs waiting for the master's TxConfirmed / TxRejected
+    pending_acks: Arc<RwLock<HashMap<String, TxAck>>>,
+    /// Followers' latest `StatsReport`s by node id; the master ranks these
+    /// for service rewards
+    peer_stats: ServiceNodes,
 }
 
 impl StarNetwork {
@@ -50,7 +215,8 @@ impl StarNetwork {
         blockchain: Arc<RwLock<Blockchain>>,
         state: Arc<RwLock<State>>,
     ) -> Self {
-        let (block_tx, _) = broadcast::channel(100);
+        let (tx_tx, _) = broadcast::channel(1000);
+        let (event_tx, _) = broadcast::channel(1000);
         
         StarNetwork {
             config,
@@ -58,7 +224,11 @@ impl StarNetwork {
             state,
             peers: Arc::new(RwLock::new(HashMap::new())),
             browsers: Arc::new(RwLock::new(HashMap::new())),
-            block_tx,
+            tx_tx,
+            event_tx,
+            master: Arc::new(RwLock::new(None)),
+            pending_acks: Arc::new(RwLock::new(HashMap::new())),
+            peer_stats: ServiceNodes::default(),
         }
     }
 
@@ -106,10 +276,36 @@ impl StarNetwork {
         let peers = self.peers.clone();
         let blockchain = self.blockchain.clone();
         let state = self.state.clone();
-        let peer_id_clone = peer_id.clone();
+        let mut peer_id_clone = peer_id.clone();
         let tx_clone = tx.clone();
+        // Set by Hello; the connection is keyed by node id from then on
+        let mut hello: Option<(String, String)> = None;
+
+        // Ping on an interval so a half-open connection doesn't linger in `peers`
+        let mut heartbeat = tokio::time::interval(tokio::time::Duration::from_secs(PING_INTERVAL_SECS));
+        heartbeat.tick().await;
+        let mut last_pong = tokio::time::Instant::now();
 
-        while let Some(Ok(msg)) = receiver.next().await {
+        loop {
+            let msg = tokio::select! {
+                msg = receiver.next() => match msg {
+                    Some(Ok(msg)) => msg,
+                    _ => break,
+                },
+                _ = heartbeat.tick() => {
+                    if last_pong.elapsed() >= tokio::time::Duration::from_secs(PONG_TIMEOUT_SECS) {
+                        warn!("💔 No pong from {} in {}s, dropping", peer_id_clone, PONG_TIMEOUT_SECS);
+                        break;
+                    }
+                    // A peer whose outbound queue is still full isn't reading
+                    // it; awaiting room here would stall this loop for good
+                    if let Err(e) = tx_clone.try_send(P2PMessage::Ping) {
+                        warn!("💔 Can't ping {} ({}), dropping", peer_id_clone, e);
+                        break;
+                    }
+                    continue;
+                }
+            };
             if let Message::Text(text) = msg {
                 if let Ok(p2p_msg) = serde_json::from_str::<P2PMessage>(&text) {
                     match p2p_msg {
@@ -117,30 +313,56 @@ impl StarNetwork {
                             info!("🔗 Peer connected: {} ({})", node_id, node_type);
                             let peer = ConnectedPeer {
                                 node_id: node_id.clone(),
-                                node_type,
+                                node_type: node_type.clone(),
                                 tx: tx_clone.clone(),
                             };
-                            peers.write().await.insert(node_id, peer);
+                            peers.write().await.insert(node_id.clone(), peer);
+                            peer_id_clone = node_id.clone();
+                            hello = Some((node_id, node_type));
                         }
-                        P2PMessage::GetState => {
+                        P2PMessage::StatsReport(report) => {
+                            // Only from a follower that said Hello under the same id,
+                            // signed by the key it is to be paid at
+                            let from_follower = hello.as_ref()
+                                .is_some_and(|(id, node_type)| *id == report.node_id && node_type == "follower");
+                            if from_follower && report.verify(chrono::Utc::now().timestamp()) {
+                                let stats = ServiceNode {
+                                    node_id: report.node_id.clone(),
+                                    address: report.address,
+                                    browsers: report.browsers,
+                                    peers: report.peers,
+                                };
+                                self.peer_stats.write().await.insert(report.node_id, stats);
+                            } else {
+                                warn!("Ignoring stats report for {} from {}", report.node_id, peer_id_clone);
+                            }
+                        }
+                        P2PMessage::GetState | P2PMessage::GetFullState => {
+                            let full = matches!(p2p_msg, P2PMessage::GetFullState);
                             let snapshot = {
                                 let state_guard = state.read().await;
-                                state_guard.get_state_snapshot().unwrap()
+                                state_guard.get_state_snapshot(full).unwrap()
                             };
-                            let _ = tx_clone.send(P2PMessage::StateSnapshot(snapshot)).await;
+                            let _ = tx_clone.send(P2PMessage::StateSnapshot(Box::new(snapshot))).await;
                         }
                         P2PMessage::SubmitTx(transaction) => {
+                            let tx_hash = transaction.hash.clone();
                             let result = {
                                 let mut bc = blockchain.write().await;
-                                bc.add_transaction(transaction)
+                                let result = bc.add_transaction((*transaction).clone());
+                                if result.is_ok() {
+                                    bc.announce_transaction(&transaction);
+                                }
+                                result
                             };
                             match result {
-                                Ok(hash) => {
-                                    info!("📤 TX received from peer: {}", &hash[..16]);
-                                    let _ = tx_clone.send(P2PMessage::TxConfirmed { hash }).await;
+                                Ok(_) => {
+                                    info!("📤 TX received from peer: {}", &tx_hash[..16]);
+                                    let _ = tx_clone.send(P2PMessage::TxConfirmed { hash: tx_hash }).await;
                                 }
                                 Err(e) => {
                                     error!("Failed to add TX: {}", e);
+                                    let _ = tx_clone.send(P2PMessage::TxRejected { hash: tx_hash, error: e.to_string() }).await;
                                 }
                             }
                         }
@@ -151,23 +373,293 @@ impl StarNetwork {
                             };
                             let _ = tx_clone.send(P2PMessage::BlockResponse(block)).await;
                         }
+                        P2PMessage::GetContractVar { contract, var } => {
+                            let value = state.read().await.get_mosh_var(&contract, &var).ok().flatten();
+                            let _ = tx_clone.send(P2PMessage::ContractVarResponse { value }).await;
+                        }
+                        P2PMessage::GetMapEntry { contract, map, key } => {
+                            let value = state.read().await.get_mosh_map(&contract, &map, &key).ok().flatten();
+                            let _ = tx_clone.send(P2PMessage::MapEntryResponse { value }).await;
+                        }
+                        P2PMessage::NewBlock(block) | P2PMessage::BlockResponse(Some(block)) => {
+                            // Only validators produce blocks besides this node
+                            if !self.config.validators.addresses.contains(&block.validator) {
+                                warn!("Ignoring block #{} by non-validator {} from {}", block.height, block.validator, peer_id_clone);
+                                continue;
+                            }
+                            for request in self.on_peer_block(block, &peer_id_clone).await {
+                                let _ = tx_clone.send(request).await;
+                            }
+                        }
                         P2PMessage::Ping => {
                             let _ = tx_clone.send(P2PMessage::Pong).await;
                         }
+                        P2PMessage::Pong => {
+                            last_pong = tokio::time::Instant::now();
+                        }
                         _ => {}
                     }
                 }
             }
         }
 
-        // Clean up
-        peers.write().await.remove(&peer_id_clone);
+        // Clean up, unless the node has already reconnected on a new socket
+        {
+            let mut peers_guard = peers.write().await;
+            if peers_guard.get(&peer_id_clone).is_some_and(|p| p.tx.same_channel(&tx_clone)) {
+                peers_guard.remove(&peer_id_clone);
+                self.peer_stats.write().await.remove(&peer_id_clone);
+            }
+        }
         sender_task.abort();
         info!("🔌 Peer disconnected: {}", peer_id_clone);
     }
 
-    pub fn subscribe_blocks(&self) -> broadcast::Receiver<Block> {
-        self.block_tx.subscribe()
+    /// Import a block from a peer, returning any catch-up requests to send back
+    async fn on_peer_block(&self, block: Block, source: &str) -> Vec<P2PMessage> {
+        let height = block.height;
+        match import_block(&self.blockchain, &self.config, &block).await {
+            Ok(BlockImport::Applied) => {
+                debug!(height, tx_count = block.transactions.len(), hash = %block.hash, source, "📥 Block applied");
+                // A validator follower's block reaches the other followers through the master
+                let peers = self.peers.read().await;
+                for (_, peer) in peers.iter().filter(|(id, _)| id.as_str() != source) {
+                    let _ = peer.tx.send(P2PMessage::NewBlock(block.clone())).await;
+                }
+                drop(peers);
+                self.notify_browsers(&P2PMessage::NewBlock(block)).await;
+                vec![]
+            }
+            Ok(BlockImport::Ahead { local_height }) => {
+                info!("⏩ Block #{} is ahead of local #{}, requesting missing blocks", height, local_height);
+                (local_height + 1..height)
+                    .map(|missing| P2PMessage::GetBlock { height: missing })
+                    .collect()
+            }
+            Ok(BlockImport::Known) => vec![],
+            Err(e) => {
+                warn!("⚠️ Rejected block #{} from {}: {}", height, source, e);
+                vec![]
+            }
+        }
+    }
+
+    /// Keep following the master, reconnecting with exponential backoff
+    async fn run_follower(self, master_url: String) {
+        let mut backoff = 1;
+        loop {
+            match self.follow_master(&master_url).await {
+                Ok(()) => {
+                    warn!("🔌 Master connection closed");
+                    backoff = 1;
+                }
+                Err(e) => {
+                    warn!("🔌 Master connection failed: {}", e);
+                }
+            }
+            info!("Reconnecting to master in {}s", backoff);
+            tokio::time::sleep(tokio::time::Duration::from_secs(backoff)).await;
+            backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF_SECS);
+        }
+    }
+
+    /// One session with the master: handshake, bootstrap from its snapshot, then
+    /// apply blocks as they arrive. Returns Ok when the master closes the socket.
+    async fn follow_master(&self, master_url: &str) -> Result<(), BoxError> {
+        let (ws, _) = connect_async(master_url).await?;
+        info!("🔗 Connected to master: {}", master_url);
+        let (mut sender, mut receiver) = ws.split();
+
+        let hello = P2PMessage::Hello {
+            node_id: self.config.node.id.clone(),
+            node_type: self.config.node.node_type.clone(),
+        };
+        for msg in [hello, P2PMessage::GetFullState] {
+            sender.send(WsMessage::Text(serde_json::to_string(&msg)?)).await?;
+        }
+
+        // Spawn sender task so API handlers can forward txs on this connection
+        let (tx, mut rx) = mpsc::channel::<P2PMessage>(100);
+        let sender_task = tokio::spawn(async move {
+            while let Some(msg) = rx.recv().await {
+                if let Ok(text) = serde_json::to_string(&msg) {
+                    if sender.send(WsMessage::Text(text)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        *self.master.write().await = Some(tx.clone());
+
+        // Keep the master's view of this node current for service rewards
+        let keypair = self.state.read().await.get_keypair().cloned()
+            .ok_or("Node keypair not loaded")?;
+        let node_id = self.config.node.id.clone();
+        let browsers = self.browsers.clone();
+        let peers = self.peers.clone();
+        let report_tx = tx.clone();
+        let report_task = tokio::spawn(async move {
+            // The first tick fires immediately, so the master hears on connect
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(STATS_REPORT_INTERVAL_SECS));
+            loop {
+                interval.tick().await;
+                let report = P2PMessage::StatsReport(StatsReport::signed(
+                    &node_id,
+                    browsers.read().await.len() as u32,
+                    peers.read().await.len() as u32,
+                    &keypair,
+                    chrono::Utc::now().timestamp(),
+                ));
+                if report_tx.send(report).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let result = self.read_master(&mut receiver, &tx).await;
+
+        // Clean up; dropping the ack senders fails any in-flight forwards
+        *self.master.write().await = None;
+        self.pending_acks.write().await.clear();
+        report_task.abort();
+        sender_task.abort();
+        result
+    }
+
+    async fn read_master<S>(&self, receiver: &mut S, tx: &mpsc::Sender<P2PMessage>) -> Result<(), BoxError>
+    where
+        S: futures::Stream<Item = Result<WsMessage, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        // The master pings every PING_INTERVAL_SECS, so silence means a dead link
+        let silence = tokio::time::Duration::from_secs(PONG_TIMEOUT_SECS);
+        while let Some(msg) = tokio::time::timeout(silence, receiver.next()).await
+            .map_err(|_| format!("No message from master in {}s", PONG_TIMEOUT_SECS))?
+        {
+            let text = match msg? {
+                WsMessage::Text(text) => text,
+                WsMessage::Close(_) => break,
+                _ => continue,
+            };
+            let Ok(p2p_msg) = serde_json::from_str::<P2PMessage>(&text) else {
+                continue;
+            };
+
+            let replies = match p2p_msg {
+                P2PMessage::Welcome { node_id, height, .. } => {
+                    info!("👋 Master {} at height #{}", node_id, height);
+                    vec![]
+                }
+                P2PMessage::StateSnapshot(snapshot) => {
+                    self.state.write().await.apply_snapshot(&snapshot)?;
+                    info!("📸 Synced to master snapshot at height #{}", snapshot.height);
+                    vec![]
+                }
+                P2PMessage::NewBlock(block) | P2PMessage::BlockResponse(Some(block)) => {
+                    self.on_peer_block(block, "master").await
+                }
+                P2PMessage::TxConfirmed { hash } => {
+                    if let Some(ack) = self.pending_acks.write().await.remove(&hash) {
+                        let _ = ack.send(Ok(hash));
+                    }
+                    vec![]
+                }
+                P2PMessage::TxRejected { hash, error } => {
+                    if let Some(ack) = self.pending_acks.write().await.remove(&hash) {
+                        let _ = ack.send(Err(error));
+                    }
+                    vec![]
+                }
+                P2PMessage::Ping => vec![P2PMessage::Pong],
+                _ => vec![],
+            };
+            for reply in replies {
+                tx.send(reply).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send a tx to the master and wait until it is accepted into the master's mempool
+    pub async fn forward_tx(&self, transaction: Transaction) -> Result<String, BoxError> {
+        let master = self.master.read().await.clone()
+            .ok_or("Not connected to the master node")?;
+
+        let hash = transaction.hash.clone();
+        let (ack_tx, ack_rx) = oneshot::channel();
+        self.pending_acks.write().await.insert(hash.clone(), ack_tx);
+
+        if master.send(P2PMessage::SubmitTx(Box::new(transaction))).await.is_err() {
+            self.pending_acks.write().await.remove(&hash);
+            return Err("Master connection closed".into());
+        }
+
+        let ack = tokio::time::timeout(tokio::time::Duration::from_secs(TX_ACK_TIMEOUT_SECS), ack_rx).await;
+        self.pending_acks.write().await.remove(&hash);
+        match ack {
+            Ok(Ok(Ok(hash))) => Ok(hash),
+            Ok(Ok(Err(e))) => Err(format!("Rejected by master: {}", e).into()),
+            Ok(Err(_)) => Err("Master connection closed before acknowledging".into()),
+            Err(_) => Err("Timed out waiting for master to acknowledge".into()),
+        }
+    }
+
+    /// Sender for mempool notifications; hand this to `Blockchain::set_tx_notifier`
+    pub fn tx_sender(&self) -> broadcast::Sender<Transaction> {
+        self.tx_tx.clone()
+    }
+
+    pub fn subscribe_txs(&self) -> broadcast::Receiver<Transaction> {
+        self.tx_tx.subscribe()
+    }
+
+    /// Sender for contract events; hand this to `Blockchain::set_event_notifier`
+    pub fn event_sender(&self) -> broadcast::Sender<ContractEvent> {
+        self.event_tx.clone()
+    }
+
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ContractEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// `(node_id, node_type)` for each connected node, sorted by id. Waits for
+    /// the peer lock, unlike `peer_count`, so a busy map never reads as empty
+    pub async fn list_peers(&self) -> Vec<(String, String)> {
+        let mut peers: Vec<(String, String)> = self.peers.read().await.values()
+            .map(|p| (p.node_id.clone(), p.node_type.clone()))
+            .collect();
+        peers.sort();
+        peers
+    }
+
+    /// Handle for `Blockchain::set_service_nodes`
+    pub fn peer_stats(&self) -> ServiceNodes {
+        self.peer_stats.clone()
+    }
+
+    /// Each reporting follower's latest browser count, by node id
+    pub async fn peer_browser_counts(&self) -> HashMap<String, u32> {
+        self.peer_stats.read().await.iter()
+            .map(|(node_id, stats)| (node_id.clone(), stats.browsers))
+            .collect()
+    }
+
+    /// Track a browser socket so it receives block pushes and is counted in /status
+    pub async fn register_browser(&self, browser_id: String, tx: mpsc::Sender<P2PMessage>) {
+        self.browsers.write().await.insert(browser_id, tx);
+    }
+
+    pub async fn unregister_browser(&self, browser_id: &str) {
+        self.browsers.write().await.remove(browser_id);
+    }
+
+    /// Push to every browser without waiting; a browser that can't keep up
+    /// misses the message rather than stalling the sender
+    async fn notify_browsers(&self, msg: &P2PMessage) {
+        let browsers = self.browsers.read().await;
+        for (_, tx) in browsers.iter() {
+            let _ = tx.try_send(msg.clone());
+        }
     }
 }
 
@@ -179,9 +671,10 @@ impl Network for StarNetwork {
         if is_master {
             info!("Starting P2P server for master node...");
         } else {
-            let master_url = &self.config.network.star.master_url;
+            let master_url = self.config.network.star.master_url.clone();
             if !master_url.is_empty() {
                 info!("Connecting to master: {}", master_url);
+                tokio::spawn(self.clone().run_follower(master_url));
             }
         }
         
@@ -196,14 +689,12 @@ impl Network for StarNetwork {
             let _ = peer.tx.send(msg.clone()).await;
         }
         drop(peers);
-        
-        let browsers = self.browsers.read().await;
-        for (_, tx) in browsers.iter() {
-            let _ = tx.send(msg.clone()).await;
+        // A follower producing on its validator turn hands the block to the master
+        if let Some(master) = self.master.read().await.clone() {
+            let _ = master.send(msg.clone()).await;
         }
-        drop(browsers);
         
-        let _ = self.block_tx.send(block.clone());
+        self.notify_browsers(&msg).await;
         
         Ok(())
     }
@@ -216,3 +707,150 @@ impl Network for StarNetwork {
         self.browsers.try_read().map(|b| b.len()).unwrap_or(0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::{BlockRewards, TxStatus};
+    use crate::state::{test_state, TestDir};
+
+    /// A node at genesis whose config lists `validators`
+    async fn node(name: &str, validators: &[&Keypair]) -> (Arc<RwLock<Blockchain>>, Arc<RwLock<State>>, Config, TestDir) {
+        let (mut state, dir) = test_state(&format!("star-{}", name));
+        let master = state.get_or_create_master_address().unwrap();
+        let state = Arc::new(RwLock::new(state));
+        let mut config: Config = toml::from_str(include_str!("../../config.toml")).unwrap();
+        config.validators.addresses = validators.iter().map(|k| k.address().to_string()).collect();
+        config.validators.turn_timeout_secs = 5;
+        let blockchain = Blockchain::new(config.clone(), state.clone(), master).await.unwrap();
+        (Arc::new(RwLock::new(blockchain)), state, config, dir)
+    }
+
+    /// An empty block on top of the local tip, `after` seconds later, naming
+    /// `validator` and signed by `signer`
+    async fn next_block(state: &Arc<RwLock<State>>, config: &Config, validator: &Keypair, signer: &Keypair, after: i64) -> Block {
+        let tip = state.read().await.get_block(state.read().await.get_height().unwrap()).unwrap().unwrap();
+        let reward = config.rewards.block_reward * 100_000_000 * config.rewards.validator_percent / 100;
+        let rewards = BlockRewards { validator_reward: reward, service_rewards: vec![], total_minted: reward };
+        let mut block = Block::new(tip.height + 1, &tip.hash, validator.address().as_str(), vec![], rewards, 1_000_000);
+        block.timestamp = tip.timestamp + after;
+        block.hash = block.calculate_hash();
+        block.sign(signer);
+        block
+    }
+
+    /// A signed transfer as a producer would record it after running it
+    fn recorded_transfer(sender: &Keypair, to: &str, value: u64) -> Transaction {
+        let mut tx = crate::chain::signed_transfer(sender, to, value, 0);
+        tx.status = TxStatus::Success;
+        tx.gas_used = crate::chain::TxType::Transfer.base_gas();
+        tx
+    }
+
+    #[tokio::test]
+    async fn test_imported_block_replays_its_txs() {
+        let validator = Keypair::generate();
+        let (chain, state, config, _dir) = node("replay", &[&validator]).await;
+        let (sender, recipient) = (Keypair::generate(), Keypair::generate().address().to_string());
+        state.write().await.set_balance(sender.address().as_str(), 1_000_000_000).unwrap();
+
+        // Recorded as failed, but it succeeds when run again
+        let mut tampered = next_block(&state, &config, &validator, &validator, 1).await;
+        let mut failed = recorded_transfer(&sender, &recipient, 5_000);
+        failed.status = TxStatus::Failed;
+        tampered.transactions = vec![failed];
+        tampered.hash = tampered.calculate_hash();
+        tampered.sign(&validator);
+        let err = import_block(&chain, &config, &tampered).await.err().unwrap();
+        assert!(err.to_string().contains("replays as Success"), "{}", err);
+        assert_eq!(state.read().await.get_height().unwrap(), 0);
+        assert_eq!(state.read().await.get_balance(&recipient).unwrap(), 0);
+
+        let mut block = next_block(&state, &config, &validator, &validator, 1).await;
+        block.transactions = vec![recorded_transfer(&sender, &recipient, 5_000)];
+        block.hash = block.calculate_hash();
+        block.sign(&validator);
+        assert!(matches!(import_block(&chain, &config, &block).await.unwrap(), BlockImport::Applied));
+
+        let guard = state.read().await;
+        assert_eq!(guard.get_balance(&recipient).unwrap(), 5_000);
+        assert_eq!(guard.get_nonce(sender.address().as_str()).unwrap(), 1);
+        assert_eq!(guard.get_balance(validator.address().as_str()).unwrap(), block.rewards.validator_reward);
+    }
+
+    #[test]
+    fn test_stats_report_must_be_fresh_and_signed_by_its_address() {
+        let keypair = Keypair::generate();
+        let report = StatsReport::signed("node-1", 12, 3, &keypair, 1_000);
+        assert!(report.verify(1_000 + STATS_REPORT_MAX_AGE_SECS));
+        assert!(!report.verify(1_001 + STATS_REPORT_MAX_AGE_SECS));
+
+        let mut inflated = report.clone();
+        inflated.browsers = 9_999;
+        assert!(!inflated.verify(1_000));
+
+        // A valid signature, but for someone else's payout address
+        let mut redirected = report;
+        redirected.address = Keypair::generate().address().to_string();
+        assert!(!redirected.verify(1_000));
+    }
+
+    #[tokio::test]
+    async fn test_block_from_the_wrong_validator_is_rejected() {
+        let (a, b) = (Keypair::generate(), Keypair::generate());
+        let (chain, state, config, _dir) = node("wrong-validator", &[&a, &b]).await;
+
+        // Height 1 is b's turn
+        let early = next_block(&state, &config, &a, &a, 1).await;
+        let err = import_block(&chain, &config, &early).await.err().unwrap();
+        assert!(err.to_string().contains("belongs to"), "{}", err);
+
+        let on_turn = next_block(&state, &config, &b, &b, 1).await;
+        assert!(matches!(import_block(&chain, &config, &on_turn).await.unwrap(), BlockImport::Applied));
+    }
+
+    #[tokio::test]
+    async fn test_block_from_an_unknown_signer_is_rejected() {
+        let (a, b, stranger) = (Keypair::generate(), Keypair::generate(), Keypair::generate());
+        let (chain, state, config, _dir) = node("unknown-signer", &[&a, &b]).await;
+
+        // Claims b's turn but carries a key that isn't b's
+        let forged = next_block(&state, &config, &b, &stranger, 1).await;
+        let err = import_block(&chain, &config, &forged).await.err().unwrap();
+        assert!(err.to_string().contains("invalid signature"), "{}", err);
+
+        let outsider = next_block(&state, &config, &stranger, &stranger, 1).await;
+        assert!(import_block(&chain, &config, &outsider).await.is_err());
+        assert_eq!(state.read().await.get_height().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_block_needs_a_configured_producer() {
+        let master = Keypair::generate();
+        let (chain, state, mut config, _dir) = node("no-producer", &[]).await;
+
+        let block = next_block(&state, &config, &master, &master, 1).await;
+        let err = import_block(&chain, &config, &block).await.err().unwrap();
+        assert!(err.to_string().contains("no validator set"), "{}", err);
+
+        config.genesis.master_address = master.address().to_string();
+        assert!(matches!(import_block(&chain, &config, &block).await.unwrap(), BlockImport::Applied));
+    }
+
+    #[tokio::test]
+    async fn test_missed_turn_passes_to_the_next_validator() {
+        let (a, b, c) = (Keypair::generate(), Keypair::generate(), Keypair::generate());
+        let (chain, state, config, _dir) = node("missed-turn", &[&a, &b, &c]).await;
+
+        // b had height 1 but stayed silent past the 5s turn timeout
+        let late = next_block(&state, &config, &b, &b, 5).await;
+        assert!(import_block(&chain, &config, &late).await.is_err());
+
+        let stand_in = next_block(&state, &config, &c, &c, 5).await;
+        assert!(matches!(import_block(&chain, &config, &stand_in).await.unwrap(), BlockImport::Applied));
+
+        let future = next_block(&state, &config, &a, &a, 3_600).await;
+        let err = import_block(&chain, &config, &future).await.err().unwrap();
+        assert!(err.to_string().contains("future"), "{}", err);
+    }
+}
@@ -0,0 +1,86 @@
+//! Per-IP token bucket rate limiter for the write endpoints
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_sec: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    /// `burst` requests at once, refilling at `requests_per_minute`
+    pub fn new(requests_per_minute: u32, burst: u32) -> Self {
+        RateLimiter {
+            capacity: burst.max(1) as f64,
+            refill_per_sec: requests_per_minute.max(1) as f64 / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for `ip`. When the bucket is empty, returns the seconds
+    /// until the next token is available.
+    pub fn check(&self, ip: IpAddr) -> Result<(), u64> {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), u64> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(ip).or_insert(Bucket { tokens: self.capacity, updated: now });
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - bucket.tokens) / self.refill_per_sec;
+            Err(wait.ceil() as u64)
+        }
+    }
+
+    /// Forget clients whose bucket has refilled completely
+    pub fn cleanup(&self) {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.retain(|_, b| {
+            let elapsed = now.saturating_duration_since(b.updated).as_secs_f64();
+            b.tokens + elapsed * self.refill_per_sec < self.capacity
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_burst_then_refill() {
+        let limiter = RateLimiter::new(60, 2);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(limiter.check_at(ip, start).is_ok());
+        assert!(limiter.check_at(ip, start).is_ok());
+        assert_eq!(limiter.check_at(ip, start), Err(1));
+
+        // Buckets are per IP
+        assert!(limiter.check_at(other, start).is_ok());
+
+        // One request per second refills
+        assert!(limiter.check_at(ip, start + Duration::from_secs(1)).is_ok());
+        assert!(limiter.check_at(ip, start + Duration::from_secs(1)).is_err());
+    }
+}
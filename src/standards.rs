@@ -2,10 +2,15 @@
 
 use crate::state::State;
 use serde::{Deserialize, Serialize};
-use sha2::{Sha256, Digest};
+use crate::address::Address;
 
 type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
+pub const DEFAULT_TOKEN_DECIMALS: u8 = 8;
+pub const MAX_TOKEN_DECIMALS: u8 = 18;
+pub const MAX_LOGO_URI_LENGTH: usize = 256;
+pub const MAX_TOKEN_DESCRIPTION_LENGTH: usize = 1024;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MVM20Token {
     pub address: String,
@@ -15,30 +20,98 @@ pub struct MVM20Token {
     pub total_supply: u64,
     pub creator: String,
     pub created_at: i64,
+    /// Height of the block that created the token (0 for tokens stored before this was tracked)
+    #[serde(default)]
+    pub created_block: u64,
+    /// `http(s)://` or `ipfs://` image for wallets and explorers
+    #[serde(default)]
+    pub logo_uri: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Check optional token metadata: the logo must be an `http(s)://` or
+/// `ipfs://` URI, and both fields are length-capped
+pub fn validate_token_metadata(logo_uri: Option<&str>, description: Option<&str>) -> Result<(), String> {
+    if let Some(uri) = logo_uri.filter(|u| !u.is_empty()) {
+        if !["http://", "https://", "ipfs://"].iter().any(|scheme| uri.starts_with(scheme)) {
+            return Err(format!("logo_uri must start with http://, https:// or ipfs://, got \"{}\"", uri));
+        }
+        if uri.len() > MAX_LOGO_URI_LENGTH {
+            return Err(format!("logo_uri is longer than {} bytes", MAX_LOGO_URI_LENGTH));
+        }
+    }
+    if description.is_some_and(|d| d.len() > MAX_TOKEN_DESCRIPTION_LENGTH) {
+        return Err(format!("description is longer than {} bytes", MAX_TOKEN_DESCRIPTION_LENGTH));
+    }
+    Ok(())
+}
+
+/// Parse a decimal amount like `"12.5"` into base units with `decimals` places
+pub fn parse_decimal_amount(text: &str, decimals: u8) -> Result<u64, String> {
+    let invalid = || format!("Invalid amount \"{}\"", text);
+    let (whole, fraction) = text.trim().split_once('.').unwrap_or((text.trim(), ""));
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if (whole.is_empty() && fraction.is_empty()) || !digits(whole) || !digits(fraction) {
+        return Err(invalid());
+    }
+    if fraction.len() > decimals as usize {
+        return Err(format!("Amount \"{}\" has more than {} decimal places", text, decimals));
+    }
+
+    let scale = 10u64.checked_pow(decimals as u32).ok_or_else(invalid)?;
+    let parse = |s: &str| if s.is_empty() { Ok(0) } else { s.parse::<u64>().map_err(|_| invalid()) };
+    let fraction = parse(&format!("{:0<width$}", fraction, width = decimals as usize))?;
+    parse(whole)?.checked_mul(scale)
+        .and_then(|w| w.checked_add(fraction))
+        .ok_or_else(|| format!("Amount \"{}\" is too large", text))
 }
 
+/// Inverse of `parse_decimal_amount`: `raw` base units as a decimal string
+/// with `decimals` places, trailing zeros trimmed (`150` at 2 places is `"1.5"`)
+pub fn format_amount(raw: u64, decimals: u8) -> String {
+    let scale = 10u64.pow(decimals as u32);
+    let whole = raw / scale;
+    let fraction = raw % scale;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let digits = format!("{:0width$}", fraction, width = decimals as usize);
+    format!("{}.{}", whole, digits.trim_end_matches('0'))
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn create_mvm20_token(
     state: &mut State,
     creator: &str,
     name: &str,
     symbol: &str,
     total_supply: u64,
+    decimals: u8,
+    nonce: u64,
+    created_block: u64,
 ) -> Result<String, BoxError> {
-    let mut hasher = Sha256::new();
-    hasher.update(creator);
-    hasher.update(name);
-    hasher.update(chrono::Utc::now().timestamp().to_le_bytes());
-    let hash = hasher.finalize();
-    let contract_address = format!("mvm1token{}", hex::encode(&hash[..10]));
+    if decimals > MAX_TOKEN_DECIMALS {
+        return Err(format!("Decimals must be at most {}", MAX_TOKEN_DECIMALS).into());
+    }
+
+    // Same derivation as contracts: known before the creating tx is sent
+    let contract_address = Address::derived("mvm1token", creator, nonce).to_string();
+    if state.get_token(&contract_address)?.is_some() {
+        return Err(format!("Token already exists: {}", contract_address).into());
+    }
 
     let token = MVM20Token {
         address: contract_address.clone(),
         name: name.to_string(),
         symbol: symbol.to_string(),
-        decimals: 8,
-        total_supply: total_supply * 100_000_000,
+        decimals,
+        total_supply: total_supply.checked_mul(10u64.pow(decimals as u32)).ok_or("Total supply too large")?,
         creator: creator.to_string(),
         created_at: chrono::Utc::now().timestamp(),
+        created_block,
+        logo_uri: None,
+        description: None,
     };
 
     state.save_token(&token)?;
@@ -49,6 +122,33 @@ pub fn create_mvm20_token(
     Ok(contract_address)
 }
 
+/// Set a token's logo and description; only its creator may. A `None` field
+/// is left as is, an empty string clears it.
+pub fn update_token_metadata(
+    state: &mut State,
+    contract: &str,
+    caller: &str,
+    logo_uri: Option<&str>,
+    description: Option<&str>,
+) -> Result<(), BoxError> {
+    let mut token = state.get_token(contract)?
+        .ok_or_else(|| format!("Token not found: {}", contract))?;
+    if token.creator != caller {
+        return Err("Only the token creator can update its metadata".into());
+    }
+    validate_token_metadata(logo_uri, description)?;
+
+    let cleared = |v: &str| (!v.is_empty()).then(|| v.to_string());
+    if let Some(uri) = logo_uri {
+        token.logo_uri = cleared(uri);
+    }
+    if let Some(text) = description {
+        token.description = cleared(text);
+    }
+    state.save_token(&token)?;
+    Ok(())
+}
+
 pub fn transfer_mvm20(
     state: &mut State,
     contract: &str,
@@ -63,8 +163,14 @@ pub fn transfer_mvm20(
         return Err("Insufficient token balance".into());
     }
 
+    // Self-transfers are a no-op (the second write would otherwise mint)
+    if from == to {
+        return Ok(());
+    }
+    let to_updated = to_balance.checked_add(amount).ok_or("Token balance overflow")?;
+
     state.set_token_balance(contract, from, from_balance - amount)?;
-    state.set_token_balance(contract, to, to_balance + amount)?;
+    state.set_token_balance(contract, to, to_updated)?;
 
     Ok(())
 }
@@ -89,3 +195,62 @@ pub fn get_all_tokens(
 ) -> Result<Vec<MVM20Token>, BoxError> {
     state.get_all_tokens()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::test_state;
+
+    #[test]
+    fn test_parse_decimal_amount() {
+        assert_eq!(parse_decimal_amount("1", 8), Ok(100_000_000));
+        assert_eq!(parse_decimal_amount("12.5", 8), Ok(1_250_000_000));
+        assert_eq!(parse_decimal_amount(".25", 2), Ok(25));
+        assert_eq!(parse_decimal_amount("7.", 0), Ok(7));
+        assert_eq!(parse_decimal_amount("0.00000001", 8), Ok(1));
+
+        assert!(parse_decimal_amount("0.000000001", 8).unwrap_err().contains("decimal places"));
+        assert!(parse_decimal_amount("184467440738", 8).unwrap_err().contains("too large"));
+        for bad in ["", ".", "-1", "1e3", "1.2.3", "abc"] {
+            assert!(parse_decimal_amount(bad, 8).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_format_amount_trims_trailing_zeros() {
+        assert_eq!(format_amount(0, 0), "0");
+        assert_eq!(format_amount(1_234, 0), "1234");
+
+        assert_eq!(format_amount(0, 8), "0");
+        assert_eq!(format_amount(100_000_000, 8), "1");
+        assert_eq!(format_amount(150_000_000, 8), "1.5");
+        assert_eq!(format_amount(1, 8), "0.00000001");
+        assert_eq!(format_amount(1_250_000_010, 8), "12.5000001");
+
+        assert_eq!(format_amount(1_500_000_000_000_000_000, 18), "1.5");
+        assert_eq!(format_amount(1, 18), "0.000000000000000001");
+        assert_eq!(format_amount(u64::MAX, 18), "18.446744073709551615");
+
+        for (raw, decimals) in [(150_000_000, 8), (7, 0), (1, 18)] {
+            assert_eq!(parse_decimal_amount(&format_amount(raw, decimals), decimals), Ok(raw));
+        }
+    }
+
+    #[test]
+    fn test_token_metadata_is_creator_only_and_validated() {
+        let (mut state, _dir) = test_state("token-meta");
+        let token = create_mvm20_token(&mut state, "mvm1alice", "Gold", "GLD", 1_000, 8, 0, 1).unwrap();
+
+        update_token_metadata(&mut state, &token, "mvm1alice", Some("ipfs://bafylogo"), Some("Shiny")).unwrap();
+        assert!(update_token_metadata(&mut state, &token, "mvm1bob", Some("https://x.io/a.png"), None).is_err());
+        assert!(update_token_metadata(&mut state, &token, "mvm1alice", Some("javascript:alert(1)"), None).is_err());
+        let long = format!("https://x.io/{}", "a".repeat(MAX_LOGO_URI_LENGTH));
+        assert!(update_token_metadata(&mut state, &token, "mvm1alice", Some(&long), None).is_err());
+
+        // Only the fields passed change; an empty string clears one
+        update_token_metadata(&mut state, &token, "mvm1alice", None, Some("")).unwrap();
+        let stored = state.get_token(&token).unwrap().unwrap();
+        assert_eq!(stored.logo_uri.as_deref(), Some("ipfs://bafylogo"));
+        assert_eq!(stored.description, None);
+    }
+}
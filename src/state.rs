@@ -1,16 +1,140 @@
 use crate::chain::Block;
 use crate::address::{Address, Keypair};
 use crate::standards::MVM20Token;
+use crate::cache::{CacheStats, LruCache};
 
-use rocksdb::{DB, Options};
+use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, DB, Direction, IteratorMode, Options, WriteBatch};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
+use std::sync::Mutex;
+use tracing::info;
 
 type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type KvPair = (Box<[u8]>, Box<[u8]>);
+/// A staged write's key and the staged entry it replaced
+type UndoEntry = (Vec<u8>, Option<Option<Vec<u8>>>);
+
+/// Blocks behind the tip included in a state snapshot
+const SNAPSHOT_BLOCKS: u64 = 10;
+
+/// Keys of deployed Mosh contracts, as counted and paged by `/contracts`
+const CONTRACT_KEY_PREFIX: &str = "mosh:mvm1contract";
+
+/// Balance/nonce entries cached when the config does not say otherwise
+pub const DEFAULT_ACCOUNT_CACHE_SIZE: usize = 10_000;
+
+/// Column families the database is split into
+const COLUMN_FAMILIES: [&str; 7] = ["blocks", "txs", "accounts", "tokens", "contracts", "events", "meta"];
+
+/// Column family a key lives in, picked from its prefix (the part before the
+/// first `:`). Keys keep their full prefix inside the column family, so prefix
+/// scans work unchanged but only walk their own kind of data.
+fn cf_for(key: &[u8]) -> &'static str {
+    let prefix = key.split(|&b| b == b':').next().unwrap_or_default();
+    match prefix {
+        b"block" | b"block_hash" => "blocks",
+        b"tx" | b"tx_block" | b"tx_by_block" | b"tx_by_addr" | b"tx_events" | b"tx_seq" => "txs",
+        b"balance" | b"balance_history" | b"balance_at" | b"nonce" | b"faucet" | b"name" | b"name_of" => "accounts",
+        b"token" | b"token_balance" | b"token_list" | b"token_allowance" => "tokens",
+        b"mosh" | b"mosh_var" | b"mosh_map" | b"mosh_by_creator" | b"mosh_source" | b"code" | b"storage" => "contracts",
+        b"event" => "events",
+        b"meta" => "meta",
+        // Fallback for unlisted prefixes; list new ones above
+        _ => "meta",
+    }
+}
+
+/// `balance_at:{address}:` followed by the big-endian height, so an address's
+/// changes sort by height and a reverse seek finds the last one before a block
+fn balance_at_key(address: &str, height: u64) -> Vec<u8> {
+    let mut key = format!("balance_at:{}:", address).into_bytes();
+    key.extend_from_slice(&height.to_be_bytes());
+    key
+}
+
+/// Height and address of a `balance_history:{height}:{address}` key
+fn parse_history_key(key: &[u8]) -> Option<(u64, &str)> {
+    let rest = std::str::from_utf8(key).ok()?.strip_prefix("balance_history:")?;
+    let (height, address) = rest.split_once(':')?;
+    Some((height.parse().ok()?, address))
+}
+
+/// The old (`offset` 0) or new (`offset` 8) balance of a history value
+fn history_value(value: &[u8], offset: usize) -> Result<u64, BoxError> {
+    let bytes: [u8; 8] = value.get(offset..offset + 8).and_then(|b| b.try_into().ok())
+        .ok_or_else(|| BoxError::from("Invalid balance history bytes"))?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Move every key of a pre-column-family database out of the default column
+/// family into the one it belongs to, in a single batch. Returns the number
+/// of keys moved (0 once migrated, as the default family is left empty).
+fn migrate_default_cf(db: &DB) -> Result<usize, BoxError> {
+    let mut batch = WriteBatch::default();
+    let mut moved = 0;
+    for item in db.iterator(IteratorMode::Start) {
+        let (key, value) = item?;
+        let cf = db.cf_handle(cf_for(&key))
+            .ok_or_else(|| BoxError::from(format!("Missing column family {}", cf_for(&key))))?;
+        batch.put_cf(cf, &key, value);
+        batch.delete(&key);
+        moved += 1;
+    }
+    if moved > 0 {
+        db.write(batch)?;
+    }
+    Ok(moved)
+}
+
+/// Addresses a tx is listed under in the `tx_by_addr:` index
+fn tx_index_addresses(tx: &crate::chain::Transaction) -> Vec<&str> {
+    let mut addresses = vec![tx.from.as_str()];
+    if let Some(ref to) = tx.to {
+        addresses.push(to.as_str());
+    }
+    match &tx.data {
+        Some(crate::chain::TxData::TransferToken { contract, to, .. }) => {
+            // Index by token contract AND by token recipient
+            addresses.push(to.as_str());
+            addresses.push(contract.as_str());
+        }
+        Some(crate::chain::TxData::CallContract { contract, .. })
+        | Some(crate::chain::TxData::Call { contract, .. }) => addresses.push(contract.as_str()),
+        _ => {}
+    }
+    addresses
+}
 
 pub struct State {
     db: DB,
     keypair: Option<Keypair>,
+    /// Pending writes while staging is active (None value = deleted key).
+    /// Reads see staged values; nothing reaches RocksDB until committed in a
+    /// single batch (or discarded).
+    staged: Option<BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
+    /// Committed `balance:`/`nonce:` values by key. Staged writes evict their
+    /// key instead of updating it, so a discarded batch never leaves it stale.
+    account_cache: Mutex<LruCache<String, u64>>,
+    /// Balance changes since the last block, as (balance before, balance now).
+    /// Written out under the next block's height by `record_balance_history`.
+    balance_journal: BTreeMap<String, (u64, u64)>,
+    /// The journal as it was when staging began, restored on discard
+    staged_journal: Option<BTreeMap<String, (u64, u64)>>,
+    /// Open savepoints, innermost last
+    savepoints: Vec<Savepoint>,
+}
+
+/// Writes made since `State::savepoint`, so `rollback_savepoint` can undo them
+struct Savepoint {
+    /// Staging was off when it opened: the savepoint staged its own writes and
+    /// commits or discards them as a whole
+    owns_staging: bool,
+    /// Every staged write under the savepoint, oldest first, with the staged
+    /// entry it replaced (`None` = the key wasn't staged yet)
+    undo: Vec<UndoEntry>,
+    /// The balance journal as it was when the savepoint opened
+    journal: BTreeMap<String, (u64, u64)>,
 }
 
 impl State {
@@ -20,15 +144,440 @@ impl State {
         
         let mut opts = Options::default();
         opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
         opts.set_max_open_files(100);
         
-        let db = DB::open(&opts, path)?;
+        let descriptors = COLUMN_FAMILIES.iter()
+            .map(|name| ColumnFamilyDescriptor::new(*name, Options::default()));
+        let db = DB::open_cf_descriptors(&opts, path, descriptors)?;
+
+        // Databases created before the split keep everything in the default family
+        let moved = migrate_default_cf(&db)?;
+        if moved > 0 {
+            info!("📦 Migrated {} keys into column families", moved);
+        }
         
-        Ok(State { db, keypair: None })
+        let mut state = State::with_db(db);
+        let indexed = state.backfill_tx_seq()?;
+        if indexed > 0 {
+            info!("📦 Indexed {} transactions into the global tx index", indexed);
+        }
+        let keyed = state.backfill_balance_at()?;
+        if keyed > 0 {
+            info!("📦 Indexed {} balance changes by address", keyed);
+        }
+        state.backfill_counts()?;
+        Ok(state)
+    }
+
+    /// Open an existing database without write access, for a read-only
+    /// replica. It reads the data as of opening; every write fails.
+    pub fn open_read_only(data_dir: &str) -> Result<Self, BoxError> {
+        let path = Path::new(data_dir).join("rocksdb");
+        let mut opts = Options::default();
+        opts.set_max_open_files(100);
+        let db = DB::open_cf_for_read_only(&opts, path, COLUMN_FAMILIES, false)?;
+        let state = State::with_db(db);
+
+        // The backfills in `new` need writes, so a replica can't build them
+        let missing = state.missing_indexes()?;
+        if !missing.is_empty() {
+            return Err(format!(
+                "Database in {} has no {} index yet; open it once with a writable node to build it",
+                data_dir, missing.join(" or ")
+            ).into());
+        }
+        Ok(state)
+    }
+
+    /// Indexes that `new` would still have to backfill
+    fn missing_indexes(&self) -> Result<Vec<&'static str>, BoxError> {
+        let mut missing = Vec::new();
+        if self.db_get(b"meta:tx_seq")?.is_none() && self.get_height()? > 0 {
+            missing.push("tx_seq");
+        }
+        if self.db_get(b"meta:balance_at")?.is_none() && !self.db_scan_page(b"balance_history:", None, 1, |_| true)?.is_empty() {
+            missing.push("balance_at");
+        }
+        Ok(missing)
+    }
+
+    fn with_db(db: DB) -> Self {
+        State {
+            db,
+            keypair: None,
+            staged: None,
+            account_cache: Mutex::new(LruCache::new(DEFAULT_ACCOUNT_CACHE_SIZE)),
+            balance_journal: BTreeMap::new(),
+            staged_journal: None,
+            savepoints: Vec::new(),
+        }
+    }
+
+    /// Build the `tx_seq:` index of a database written before it existed, in
+    /// one batch. Returns the number of transactions indexed (0 once built).
+    fn backfill_tx_seq(&mut self) -> Result<u64, BoxError> {
+        if self.db_get(b"meta:tx_seq")?.is_some() {
+            return Ok(0);
+        }
+        let height = self.get_height()?;
+        if height == 0 {
+            return Ok(0);
+        }
+        self.write_atomically(|state| {
+            let mut seq = 0u64;
+            for h in 0..=height {
+                let Some(block) = state.get_block(h)? else { continue };
+                for tx in &block.transactions {
+                    state.db_put(format!("tx_seq:{:020}", seq).as_bytes(), tx.hash.as_bytes())?;
+                    seq += 1;
+                }
+            }
+            state.db_put(b"meta:tx_seq", seq.to_le_bytes())?;
+            Ok(seq)
+        })
+    }
+
+    /// Build the `balance_at:` index of a database written before it existed,
+    /// in one batch. Returns the number of changes indexed (0 once built).
+    fn backfill_balance_at(&mut self) -> Result<u64, BoxError> {
+        if self.db_get(b"meta:balance_at")?.is_some() {
+            return Ok(0);
+        }
+        self.write_atomically(|state| {
+            let mut keyed = 0u64;
+            for (key, value) in state.db_scan(b"balance_history:")? {
+                let Some((height, address)) = parse_history_key(&key) else { continue };
+                state.db_put(balance_at_key(address, height), value)?;
+                keyed += 1;
+            }
+            state.db_put(b"meta:balance_at", keyed.to_le_bytes())?;
+            Ok(keyed)
+        })
+    }
+
+    /// Start the token and contract counters of a database written before
+    /// they existed from one scan each
+    fn backfill_counts(&mut self) -> Result<(), BoxError> {
+        if self.get_count(b"meta:token_count")?.is_none() {
+            let tokens = self.scan_tokens()? as u64;
+            self.db_put(b"meta:token_count", tokens.to_le_bytes())?;
+        }
+        if self.get_count(b"meta:contract_count")?.is_none() {
+            let contracts = self.scan_mosh_contracts()? as u64;
+            self.db_put(b"meta:contract_count", contracts.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Replace the balance/nonce cache with one holding `size` accounts (0 disables it)
+    pub fn with_account_cache(mut self, size: usize) -> Self {
+        self.account_cache = Mutex::new(LruCache::new(size));
+        self
+    }
+
+    pub fn account_cache_stats(&self) -> CacheStats {
+        self.account_cache.lock().unwrap().stats()
+    }
+
+    /// Handle of the column family `key` is stored in
+    fn cf(&self, key: &[u8]) -> &ColumnFamily {
+        self.db.cf_handle(cf_for(key)).expect("column families are opened in State::new")
+    }
+
+    // ==================== RAW ACCESS ====================
+
+    fn db_get(&self, key: impl AsRef<[u8]>) -> Result<Option<Vec<u8>>, BoxError> {
+        if let Some(ref staged) = self.staged {
+            if let Some(value) = staged.get(key.as_ref()) {
+                return Ok(value.clone());
+            }
+        }
+        Ok(self.db.get_cf(self.cf(key.as_ref()), key)?)
+    }
+
+    fn db_put(&mut self, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> Result<(), BoxError> {
+        if self.staged.is_some() {
+            self.stage_write(key.as_ref().to_vec(), Some(value.as_ref().to_vec()));
+            return Ok(());
+        }
+        self.db.put_cf(self.cf(key.as_ref()), key, value)?;
+        Ok(())
+    }
+
+    fn db_delete(&mut self, key: impl AsRef<[u8]>) -> Result<(), BoxError> {
+        if self.staged.is_some() {
+            self.stage_write(key.as_ref().to_vec(), None);
+            return Ok(());
+        }
+        self.db.delete_cf(self.cf(key.as_ref()), key)?;
+        Ok(())
+    }
+
+    /// Buffer a write, noting what it replaced for the innermost savepoint
+    fn stage_write(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+        let Some(ref mut staged) = self.staged else {
+            return;
+        };
+        let previous = staged.insert(key.clone(), value);
+        if let Some(savepoint) = self.savepoints.last_mut().filter(|sp| !sp.owns_staging) {
+            savepoint.undo.push((key, previous));
+        }
+    }
+
+    /// All key/value pairs under `prefix`, in key order, with staged writes merged in
+    fn db_scan(&self, prefix: &[u8]) -> Result<Vec<KvPair>, BoxError> {
+        let mut entries: BTreeMap<Vec<u8>, Box<[u8]>> = BTreeMap::new();
+        for item in self.db.prefix_iterator_cf(self.cf(prefix), prefix) {
+            let (key, value) = item?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            entries.insert(key.to_vec(), value);
+        }
+
+        if let Some(ref staged) = self.staged {
+            for (key, value) in staged.range(prefix.to_vec()..) {
+                if !key.starts_with(prefix) {
+                    break;
+                }
+                match value {
+                    Some(v) => { entries.insert(key.clone(), v.clone().into_boxed_slice()); }
+                    None => { entries.remove(key); }
+                }
+            }
+        }
+
+        Ok(entries.into_iter().map(|(k, v)| (k.into_boxed_slice(), v)).collect())
+    }
+
+    /// Up to `limit` pairs under `prefix` that pass `keep`, in key order,
+    /// starting just after the key `after` (or at the start of the prefix).
+    /// Unlike `db_scan` this stops reading once the page is full.
+    fn db_scan_page(
+        &self,
+        prefix: &[u8],
+        after: Option<&[u8]>,
+        limit: usize,
+        keep: impl Fn(&[u8]) -> bool,
+    ) -> Result<Vec<KvPair>, BoxError> {
+        let start = after.unwrap_or(prefix);
+        let staged = self.staged.as_ref();
+        let mut entries: BTreeMap<Vec<u8>, Box<[u8]>> = BTreeMap::new();
+        let mut last_key: Option<Vec<u8>> = None;
+
+        for item in self.db.iterator_cf(self.cf(prefix), IteratorMode::From(start, Direction::Forward)) {
+            let (key, value) = item?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            if Some(key.as_ref()) == after || !keep(&key) {
+                continue;
+            }
+            // Staged keys are merged below
+            if staged.is_some_and(|st| st.contains_key(key.as_ref())) {
+                continue;
+            }
+            entries.insert(key.to_vec(), value);
+            if entries.len() >= limit {
+                last_key = Some(key.to_vec());
+                break;
+            }
+        }
+
+        if let Some(staged) = staged {
+            for (key, value) in staged.range(start.to_vec()..) {
+                if !key.starts_with(prefix) || last_key.as_ref().is_some_and(|last| key > last) {
+                    break;
+                }
+                if Some(key.as_slice()) == after || !keep(key) {
+                    continue;
+                }
+                if let Some(v) = value {
+                    entries.insert(key.clone(), v.clone().into_boxed_slice());
+                }
+            }
+        }
+
+        Ok(entries.into_iter().take(limit).map(|(k, v)| (k.into_boxed_slice(), v)).collect())
+    }
+
+    /// Up to `limit` pairs under `prefix`, in reverse key order, starting just
+    /// before the key `before` (or at the end of the prefix)
+    fn db_scan_back(&self, prefix: &[u8], before: Option<&[u8]>, limit: usize) -> Result<Vec<KvPair>, BoxError> {
+        // One past the last key the prefix can hold
+        let mut end = prefix.to_vec();
+        if let Some(last) = end.last_mut() {
+            *last += 1;
+        }
+        let start = before.unwrap_or(&end);
+        let staged = self.staged.as_ref();
+        let mut entries: BTreeMap<Vec<u8>, Box<[u8]>> = BTreeMap::new();
+        let mut last_key: Option<Vec<u8>> = None;
+
+        for item in self.db.iterator_cf(self.cf(prefix), IteratorMode::From(start, Direction::Reverse)) {
+            let (key, value) = item?;
+            if key.as_ref() >= start {
+                continue;
+            }
+            if !key.starts_with(prefix) {
+                break;
+            }
+            // Staged keys are merged below
+            if staged.is_some_and(|st| st.contains_key(key.as_ref())) {
+                continue;
+            }
+            entries.insert(key.to_vec(), value);
+            if entries.len() >= limit {
+                last_key = Some(key.to_vec());
+                break;
+            }
+        }
+
+        if let Some(staged) = staged {
+            for (key, value) in staged.range(..start.to_vec()).rev() {
+                if !key.starts_with(prefix) || last_key.as_ref().is_some_and(|last| key < last) {
+                    break;
+                }
+                match value {
+                    Some(v) => { entries.insert(key.clone(), v.clone().into_boxed_slice()); }
+                    None => { entries.remove(key); }
+                }
+            }
+        }
+
+        Ok(entries.into_iter().rev().take(limit).map(|(k, v)| (k.into_boxed_slice(), v)).collect())
+    }
+
+    // ==================== STAGING ====================
+
+    /// Start buffering writes in memory instead of writing to RocksDB
+    pub fn begin_staging(&mut self) {
+        self.staged = Some(BTreeMap::new());
+        self.staged_journal = Some(self.balance_journal.clone());
+    }
+
+    /// Drop all buffered writes, leaving RocksDB untouched
+    pub fn discard_staging(&mut self) {
+        self.staged = None;
+        self.savepoints.clear();
+        if let Some(journal) = self.staged_journal.take() {
+            self.balance_journal = journal;
+        }
+    }
+
+    /// Buffered writes that differ from RocksDB, as (key, committed value,
+    /// staged value) in key order. `None` means the key is absent / deleted.
+    pub fn staged_changes(&self) -> Result<Vec<StagedChange>, BoxError> {
+        let Some(ref staged) = self.staged else {
+            return Ok(Vec::new());
+        };
+        let mut changes = Vec::new();
+        for (key, after) in staged {
+            let before = self.db.get_cf(self.cf(key), key)?;
+            if before != *after {
+                changes.push(StagedChange {
+                    key: String::from_utf8_lossy(key).into_owned(),
+                    before,
+                    after: after.clone(),
+                });
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Write all buffered writes to RocksDB in one atomic batch
+    fn commit_staging(&mut self) -> Result<(), BoxError> {
+        let Some(staged) = self.staged.take() else {
+            return Ok(());
+        };
+        self.staged_journal = None;
+        self.savepoints.clear();
+        let mut batch = WriteBatch::default();
+        for (key, value) in staged {
+            let cf = self.cf(&key);
+            match value {
+                Some(v) => batch.put_cf(cf, &key, v),
+                None => batch.delete_cf(cf, &key),
+            }
+        }
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// Run `f` with its writes buffered and commit them in one RocksDB write,
+    /// so a crash leaves either all of them or none. Nothing is written if `f`
+    /// fails. Inside an existing staging the writes just join it.
+    pub fn write_atomically<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, BoxError>) -> Result<T, BoxError> {
+        if self.staged.is_some() {
+            return f(self);
+        }
+        self.begin_staging();
+        match f(self) {
+            Ok(value) => {
+                self.commit_staging()?;
+                Ok(value)
+            }
+            Err(e) => {
+                self.discard_staging();
+                Err(e)
+            }
+        }
+    }
+
+    /// Open a savepoint: the writes that follow can be undone with
+    /// `rollback_savepoint` or kept with `release_savepoint`. Savepoints nest.
+    /// Outside staging the outermost one stages its writes and commits them
+    /// in one batch on release.
+    pub fn savepoint(&mut self) {
+        let owns_staging = self.staged.is_none();
+        if owns_staging {
+            self.begin_staging();
+        }
+        self.savepoints.push(Savepoint {
+            owns_staging,
+            undo: Vec::new(),
+            journal: self.balance_journal.clone(),
+        });
+    }
+
+    /// Keep the writes made since the innermost savepoint. They join the
+    /// enclosing savepoint, so rolling that one back still undoes them.
+    pub fn release_savepoint(&mut self) -> Result<(), BoxError> {
+        let Some(savepoint) = self.savepoints.pop() else {
+            return Ok(());
+        };
+        if savepoint.owns_staging {
+            return self.commit_staging();
+        }
+        if let Some(parent) = self.savepoints.last_mut().filter(|sp| !sp.owns_staging) {
+            parent.undo.extend(savepoint.undo);
+        }
+        Ok(())
+    }
+
+    /// Undo every write made since the innermost savepoint
+    pub fn rollback_savepoint(&mut self) {
+        let Some(savepoint) = self.savepoints.pop() else {
+            return;
+        };
+        if savepoint.owns_staging {
+            self.discard_staging();
+            return;
+        }
+        if let Some(ref mut staged) = self.staged {
+            for (key, previous) in savepoint.undo.into_iter().rev() {
+                match previous {
+                    Some(value) => { staged.insert(key, value); }
+                    None => { staged.remove(&key); }
+                }
+            }
+        }
+        self.balance_journal = savepoint.journal;
     }
 
     pub fn get_or_create_master_address(&mut self) -> Result<Address, BoxError> {
-        if let Some(bytes) = self.db.get(b"meta:keypair")? {
+        if let Some(bytes) = self.db_get(b"meta:keypair")? {
             let key_bytes: [u8; 32] = bytes.as_slice().try_into()
                 .map_err(|_| BoxError::from("Invalid keypair bytes"))?;
             let keypair = Keypair::from_bytes(&key_bytes)?;
@@ -40,7 +589,7 @@ impl State {
         let keypair = Keypair::generate();
         let address = keypair.address();
         
-        self.db.put(b"meta:keypair", keypair.to_bytes())?;
+        self.db_put(b"meta:keypair", keypair.to_bytes())?;
         self.keypair = Some(keypair);
 
         Ok(address)
@@ -51,29 +600,147 @@ impl State {
     }
 
     // Block operations
+    /// Save a block with its txs and hash index in one atomic write
     pub fn save_block(&mut self, block: &Block) -> Result<(), BoxError> {
-        let key = format!("block:{}", block.height);
-        let value = serde_json::to_string(block)?;
-        self.db.put(key.as_bytes(), value.as_bytes())?;
-        
-        let hash_key = format!("block_hash:{}", block.hash);
-        self.db.put(hash_key.as_bytes(), block.height.to_le_bytes())?;
-        
-        for (idx, tx) in block.transactions.iter().enumerate() {
-            let tx_key = format!("tx:{}", tx.hash);
-            let tx_value = serde_json::to_string(tx)?;
-            self.db.put(tx_key.as_bytes(), tx_value.as_bytes())?;
+        self.write_atomically(|state| {
+            let key = format!("block:{}", block.height);
+            let value = serde_json::to_string(block)?;
+            state.db_put(key.as_bytes(), value.as_bytes())?;
+            
+            let hash_key = format!("block_hash:{}", block.hash);
+            state.db_put(hash_key.as_bytes(), block.height.to_le_bytes())?;
             
-            let idx_key = format!("tx_by_block:{}:{}", block.height, idx);
-            self.db.put(idx_key.as_bytes(), tx.hash.as_bytes())?;
+            for (idx, tx) in block.transactions.iter().enumerate() {
+                let tx_key = format!("tx:{}", tx.hash);
+                let tx_value = serde_json::to_string(tx)?;
+                state.db_put(tx_key.as_bytes(), tx_value.as_bytes())?;
+                
+                let idx_key = format!("tx_by_block:{}:{}", block.height, idx);
+                state.db_put(idx_key.as_bytes(), tx.hash.as_bytes())?;
+            }
+
+            // Global index in chain order, for recent-tx queries
+            let next_seq = state.get_tx_seq()?;
+            for (offset, tx) in block.transactions.iter().enumerate() {
+                let seq_key = format!("tx_seq:{:020}", next_seq + offset as u64);
+                state.db_put(seq_key.as_bytes(), tx.hash.as_bytes())?;
+            }
+            state.db_put(b"meta:tx_seq", (next_seq + block.transactions.len() as u64).to_le_bytes())?;
+
+            Ok(())
+        })
+    }
+
+    /// Delete block and transaction history older than the newest `keep_blocks`
+    /// blocks. Account, token and contract state is cumulative and never touched;
+    /// genesis and the snapshot window are always kept. Returns blocks removed.
+    pub fn prune(&mut self, current_height: u64, keep_blocks: u64) -> Result<u64, BoxError> {
+        if keep_blocks == 0 {
+            return Ok(0);
+        }
+        let keep = keep_blocks.max(SNAPSHOT_BLOCKS + 1);
+        let Some(last) = current_height.checked_sub(keep) else {
+            return Ok(0);
+        };
+
+        let first = self.get_pruned_height()? + 1;
+        if first <= last {
+            self.trim_tx_seq(false, |h| h <= last)?;
         }
 
+        let mut pruned = 0;
+        for height in first..=last {
+            if let Some(block) = self.get_block(height)? {
+                self.delete_block_data(&block)?;
+                pruned += 1;
+            }
+        }
+        if first <= last {
+            self.db_put(b"meta:pruned_height", last.to_le_bytes())?;
+        }
+        Ok(pruned)
+    }
+
+    /// Highest height removed by `prune` (0 if nothing was pruned)
+    pub fn get_pruned_height(&self) -> Result<u64, BoxError> {
+        match self.db_get(b"meta:pruned_height")? {
+            Some(bytes) => Ok(u64::from_le_bytes(
+                bytes.as_slice().try_into()
+                    .map_err(|_| BoxError::from("Invalid pruned height bytes"))?
+            )),
+            None => Ok(0),
+        }
+    }
+
+    /// Drop every block above `height` and make it the chain tip. Account and
+    /// contract state is left as is. Returns blocks removed.
+    pub fn truncate_blocks(&mut self, height: u64) -> Result<u64, BoxError> {
+        let tip = self.get_height()?;
+        self.trim_tx_seq(true, |h| h > height)?;
+        let mut removed = 0;
+        for h in (height + 1)..=tip {
+            match self.get_block(h) {
+                Ok(Some(block)) => self.delete_block_data(&block)?,
+                // Unreadable block: its hash and tx keys can't be found, drop the row
+                _ => self.db_delete(format!("block:{}", h))?,
+            }
+            removed += 1;
+        }
+        self.set_height(height)?;
+        Ok(removed)
+    }
+
+    /// Drop the `tx_seq` entries of transactions in blocks matched by `removed`,
+    /// walking in from the newest (`from_top`) or oldest end of the index. Blocks
+    /// only ever leave at an end, so the walk stops at the first entry kept.
+    /// Must run before the blocks' `tx_block` keys are deleted.
+    fn trim_tx_seq(&mut self, from_top: bool, removed: impl Fn(u64) -> bool) -> Result<(), BoxError> {
+        const PAGE: usize = 256;
+        loop {
+            let page = if from_top {
+                self.db_scan_back(b"tx_seq:", None, PAGE)?
+            } else {
+                self.db_scan_page(b"tx_seq:", None, PAGE, |_| true)?
+            };
+            let full = page.len() == PAGE;
+            for (key, hash) in page {
+                let height = self.get_transaction_block_height(&String::from_utf8_lossy(&hash))?;
+                if height.is_some_and(|h| !removed(h)) {
+                    return Ok(());
+                }
+                self.db_delete(key)?;
+            }
+            if !full {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Delete a block and everything indexed under it
+    fn delete_block_data(&mut self, block: &Block) -> Result<(), BoxError> {
+        for (idx, tx) in block.transactions.iter().enumerate() {
+            self.db_delete(format!("tx:{}", tx.hash))?;
+            self.db_delete(format!("tx_by_block:{}:{}", block.height, idx))?;
+            self.db_delete(format!("tx_block:{}", tx.hash))?;
+            self.db_delete(format!("tx_events:{}", tx.hash))?;
+            for address in tx_index_addresses(tx) {
+                self.db_delete(format!("tx_by_addr:{}:{}", address, tx.hash))?;
+            }
+        }
+        for (key, _) in self.db_scan(format!("balance_history:{:020}:", block.height).as_bytes())? {
+            if let Some((height, address)) = parse_history_key(&key) {
+                self.db_delete(balance_at_key(address, height))?;
+            }
+            self.db_delete(key)?;
+        }
+        self.db_delete(format!("block_hash:{}", block.hash))?;
+        self.db_delete(format!("block:{}", block.height))?;
         Ok(())
     }
 
     pub fn get_block(&self, height: u64) -> Result<Option<Block>, BoxError> {
         let key = format!("block:{}", height);
-        if let Some(value) = self.db.get(key.as_bytes())? {
+        if let Some(value) = self.db_get(key.as_bytes())? {
             let block: Block = serde_json::from_slice(&value)?;
             Ok(Some(block))
         } else {
@@ -83,7 +750,7 @@ impl State {
 
     pub fn get_block_by_hash(&self, hash: &str) -> Result<Option<Block>, BoxError> {
         let hash_key = format!("block_hash:{}", hash);
-        if let Some(height_bytes) = self.db.get(hash_key.as_bytes())? {
+        if let Some(height_bytes) = self.db_get(hash_key.as_bytes())? {
             let height = u64::from_le_bytes(
                 height_bytes.as_slice().try_into()
                     .map_err(|_| BoxError::from("Invalid height bytes"))?
@@ -96,12 +763,12 @@ impl State {
 
     // Height operations
     pub fn set_height(&mut self, height: u64) -> Result<(), BoxError> {
-        self.db.put(b"meta:height", height.to_le_bytes())?;
+        self.db_put(b"meta:height", height.to_le_bytes())?;
         Ok(())
     }
 
     pub fn get_height(&self) -> Result<u64, BoxError> {
-        if let Some(bytes) = self.db.get(b"meta:height")? {
+        if let Some(bytes) = self.db_get(b"meta:height")? {
             Ok(u64::from_le_bytes(
                 bytes.as_slice().try_into()
                     .map_err(|_| BoxError::from("Invalid height bytes"))?
@@ -111,42 +778,106 @@ impl State {
         }
     }
 
+    /// Read a `u64` account value through the cache. Keys with a staged write
+    /// bypass it, since the staged value may still be discarded.
+    fn get_cached_u64(&self, key: &str, what: &str) -> Result<u64, BoxError> {
+        let staged = self.staged.as_ref().is_some_and(|st| st.contains_key(key.as_bytes()));
+        if !staged {
+            if let Some(value) = self.account_cache.lock().unwrap().get(&key.to_string()) {
+                return Ok(value);
+            }
+        }
+
+        let value = match self.db_get(key.as_bytes())? {
+            Some(bytes) => u64::from_le_bytes(
+                bytes.as_slice().try_into()
+                    .map_err(|_| BoxError::from(format!("Invalid {} bytes", what)))?
+            ),
+            None => 0,
+        };
+        if !staged {
+            self.account_cache.lock().unwrap().put(key.to_string(), value);
+        }
+        Ok(value)
+    }
+
+    fn set_cached_u64(&mut self, key: String, value: u64) -> Result<(), BoxError> {
+        self.db_put(key.as_bytes(), value.to_le_bytes())?;
+        let mut cache = self.account_cache.lock().unwrap();
+        if self.staged.is_some() {
+            cache.remove(&key);
+        } else {
+            cache.put(key, value);
+        }
+        Ok(())
+    }
+
     // Balance operations
     pub fn set_balance(&mut self, address: &str, balance: u64) -> Result<(), BoxError> {
-        let key = format!("balance:{}", address);
-        self.db.put(key.as_bytes(), balance.to_le_bytes())?;
+        let before = self.get_balance(address)?;
+        self.set_cached_u64(format!("balance:{}", address), balance)?;
+        self.balance_journal.entry(address.to_string()).or_insert((before, before)).1 = balance;
         Ok(())
     }
 
-    pub fn get_balance(&self, address: &str) -> Result<u64, BoxError> {
-        let key = format!("balance:{}", address);
-        if let Some(bytes) = self.db.get(key.as_bytes())? {
-            Ok(u64::from_le_bytes(
-                bytes.as_slice().try_into()
-                    .map_err(|_| BoxError::from("Invalid balance bytes"))?
-            ))
-        } else {
-            Ok(0)
+    /// Store the balance changes made since the last block as
+    /// `balance_history:{height}:{address}` = old ‖ new (heights zero-padded so
+    /// they sort), and again under `balance_at_key` for lookups by address.
+    /// Returns the number of accounts that changed.
+    pub fn record_balance_history(&mut self, height: u64) -> Result<usize, BoxError> {
+        let journal = std::mem::take(&mut self.balance_journal);
+        let mut recorded = 0;
+        for (address, (old, new)) in journal {
+            if old == new {
+                continue;
+            }
+            let mut value = old.to_le_bytes().to_vec();
+            value.extend_from_slice(&new.to_le_bytes());
+            self.db_put(balance_at_key(&address, height), &value)?;
+            self.db_put(format!("balance_history:{:020}:{}", height, address), value)?;
+            recorded += 1;
+        }
+        Ok(recorded)
+    }
+
+    /// Balance of `address` as of the end of block `height`: the "new" value of
+    /// its last change at or before `height`, found with one seek back. When
+    /// that change has been pruned, the first later change holds the "old"
+    /// value instead, and with no changes at all it's the current balance.
+    pub fn get_balance_at(&self, address: &str, height: u64) -> Result<u64, BoxError> {
+        let pruned = self.get_pruned_height()?;
+        if height < pruned {
+            return Err(format!("Balance history up to block #{} has been pruned", pruned).into());
+        }
+
+        let prefix = format!("balance_at:{}:", address);
+        let after = height.checked_add(1).map(|next| balance_at_key(address, next));
+        if let Some((_, value)) = self.db_scan_back(prefix.as_bytes(), after.as_deref(), 1)?.first() {
+            return history_value(value, 8);
+        }
+        let key = balance_at_key(address, height);
+        if let Some((_, value)) = self.db_scan_page(prefix.as_bytes(), Some(&key), 1, |_| true)?.first() {
+            return history_value(value, 0);
+        }
+
+        // Changes since the tip aren't in a block yet
+        match self.balance_journal.get(address) {
+            Some((old, _)) => Ok(*old),
+            None => self.get_balance(address),
         }
     }
 
+    pub fn get_balance(&self, address: &str) -> Result<u64, BoxError> {
+        self.get_cached_u64(&format!("balance:{}", address), "balance")
+    }
+
     // Nonce operations
     pub fn set_nonce(&mut self, address: &str, nonce: u64) -> Result<(), BoxError> {
-        let key = format!("nonce:{}", address);
-        self.db.put(key.as_bytes(), nonce.to_le_bytes())?;
-        Ok(())
+        self.set_cached_u64(format!("nonce:{}", address), nonce)
     }
 
     pub fn get_nonce(&self, address: &str) -> Result<u64, BoxError> {
-        let key = format!("nonce:{}", address);
-        if let Some(bytes) = self.db.get(key.as_bytes())? {
-            Ok(u64::from_le_bytes(
-                bytes.as_slice().try_into()
-                    .map_err(|_| BoxError::from("Invalid nonce bytes"))?
-            ))
-        } else {
-            Ok(0)
-        }
+        self.get_cached_u64(&format!("nonce:{}", address), "nonce")
     }
 
     pub fn increment_nonce(&mut self, address: &str) -> Result<u64, BoxError> {
@@ -158,12 +889,42 @@ impl State {
 
     // Total supply
     pub fn set_total_supply(&mut self, supply: u64) -> Result<(), BoxError> {
-        self.db.put(b"meta:total_supply", supply.to_le_bytes())?;
+        self.db_put(b"meta:total_supply", supply.to_le_bytes())?;
         Ok(())
     }
 
+    /// Running total of protocol fees credited to the treasury
+    pub fn get_treasury_fees(&self) -> Result<u64, BoxError> {
+        match self.db_get(b"meta:treasury_fees")? {
+            Some(bytes) => Ok(u64::from_le_bytes(
+                bytes.as_slice().try_into()
+                    .map_err(|_| BoxError::from("Invalid treasury fee bytes"))?
+            )),
+            None => Ok(0),
+        }
+    }
+
+    pub fn add_treasury_fees(&mut self, fee: u64) -> Result<(), BoxError> {
+        let total = self.get_treasury_fees()?.checked_add(fee).ok_or("Treasury fee overflow")?;
+        self.db_put(b"meta:treasury_fees", total.to_le_bytes())?;
+        Ok(())
+    }
+
+    /// Sum of every `balance:` entry, to check `meta:total_supply` against
+    pub fn recompute_total_supply(&self) -> Result<u64, BoxError> {
+        let mut total: u64 = 0;
+        for (key, value) in self.db_scan(b"balance:")? {
+            let balance = u64::from_le_bytes(
+                value.as_ref().try_into()
+                    .map_err(|_| BoxError::from(format!("Invalid balance bytes at {}", String::from_utf8_lossy(&key))))?
+            );
+            total = total.checked_add(balance).ok_or("Balance sum overflow")?;
+        }
+        Ok(total)
+    }
+
     pub fn get_total_supply(&self) -> Result<u64, BoxError> {
-        if let Some(bytes) = self.db.get(b"meta:total_supply")? {
+        if let Some(bytes) = self.db_get(b"meta:total_supply")? {
             Ok(u64::from_le_bytes(
                 bytes.as_slice().try_into()
                     .map_err(|_| BoxError::from("Invalid supply bytes"))?
@@ -176,13 +937,13 @@ impl State {
     // Contract storage
     pub fn set_contract_storage(&mut self, contract: &str, key: &str, value: &str) -> Result<(), BoxError> {
         let storage_key = format!("storage:{}:{}", contract, key);
-        self.db.put(storage_key.as_bytes(), value.as_bytes())?;
+        self.db_put(storage_key.as_bytes(), value.as_bytes())?;
         Ok(())
     }
 
     pub fn get_contract_storage(&self, contract: &str, key: &str) -> Result<Option<String>, BoxError> {
         let storage_key = format!("storage:{}:{}", contract, key);
-        if let Some(bytes) = self.db.get(storage_key.as_bytes())? {
+        if let Some(bytes) = self.db_get(storage_key.as_bytes())? {
             Ok(Some(String::from_utf8(bytes.to_vec())?))
         } else {
             Ok(None)
@@ -202,18 +963,21 @@ impl State {
 
     pub fn save_mosh_contract(&mut self, contract: &crate::mvm::MoshContract) -> Result<(), BoxError> {
         let key = format!("mosh:{}", contract.address);
+        if key.starts_with(CONTRACT_KEY_PREFIX) && self.db_get(key.as_bytes())?.is_none() {
+            self.bump_count(b"meta:contract_count")?;
+        }
         let value = serde_json::to_string(contract)?;
-        self.db.put(key.as_bytes(), value.as_bytes())?;
+        self.db_put(key.as_bytes(), value.as_bytes())?;
         
         let creator_key = format!("mosh_by_creator:{}:{}", contract.creator, contract.address);
-        self.db.put(creator_key.as_bytes(), b"1")?;
+        self.db_put(creator_key.as_bytes(), b"1")?;
         
         Ok(())
     }
 
     pub fn get_mosh_contract(&self, address: &str) -> Result<Option<crate::mvm::MoshContract>, BoxError> {
         let key = format!("mosh:{}", address);
-        if let Some(bytes) = self.db.get(key.as_bytes())? {
+        if let Some(bytes) = self.db_get(key.as_bytes())? {
             let contract: crate::mvm::MoshContract = serde_json::from_slice(&bytes)?;
             Ok(Some(contract))
         } else {
@@ -221,13 +985,45 @@ impl State {
         }
     }
 
+    pub fn save_code_contract(&mut self, contract: &crate::mvm::CodeContract) -> Result<(), BoxError> {
+        let key = format!("code:{}", contract.address);
+        let value = serde_json::to_string(contract)?;
+        self.db_put(key.as_bytes(), value.as_bytes())
+    }
+
+    pub fn get_code_contract(&self, address: &str) -> Result<Option<crate::mvm::CodeContract>, BoxError> {
+        let key = format!("code:{}", address);
+        match self.db_get(key.as_bytes())? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Record `source` for a contract whose schema hashed to `hash`. Kept on
+    /// this node only; it is not part of the chain or of snapshots.
+    pub fn save_verified_source(&mut self, address: &str, source_json: &str, hash: &str) -> Result<(), BoxError> {
+        let key = format!("mosh_source:{}", address);
+        let value = serde_json::to_string(&VerifiedSource {
+            source: source_json.to_string(),
+            schema_hash: hash.to_string(),
+            verified_at: chrono::Utc::now().timestamp(),
+        })?;
+        self.db_put(key.as_bytes(), value.as_bytes())
+    }
+
+    pub fn get_verified_source(&self, address: &str) -> Result<Option<VerifiedSource>, BoxError> {
+        let key = format!("mosh_source:{}", address);
+        match self.db_get(key.as_bytes())? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
     pub fn get_all_mosh_contracts(&self) -> Result<Vec<crate::mvm::MoshContract>, BoxError> {
         let mut contracts = Vec::new();
         let prefix = b"mosh:mvm1contract";
         
-        let iter = self.db.prefix_iterator(prefix);
-        for item in iter {
-            let (key, value) = item?;
+        for (key, value) in self.db_scan(prefix)? {
             let key_str = String::from_utf8(key.to_vec())?;
             if key_str.starts_with("mosh:mvm1contract") {
                 let contract: crate::mvm::MoshContract = serde_json::from_slice(&value)?;
@@ -238,13 +1034,35 @@ impl State {
         Ok(contracts)
     }
 
+    /// One page of contracts in address order, after the `after` address
+    /// when given, otherwise skipping the first `offset`
+    pub fn get_mosh_contracts_page(&self, after: Option<&str>, offset: usize, limit: usize) -> Result<Vec<crate::mvm::MoshContract>, BoxError> {
+        let after_key = after.map(|a| format!("mosh:{}", a));
+        let skip = if after.is_some() { 0 } else { offset };
+        let page = self.db_scan_page(CONTRACT_KEY_PREFIX.as_bytes(), after_key.as_deref().map(str::as_bytes), skip + limit, |_| true)?;
+        page.into_iter().skip(skip)
+            .map(|(_, value)| Ok(serde_json::from_slice(&value)?))
+            .collect()
+    }
+
+    /// Kept by `save_mosh_contract`; databases that predate the counter are
+    /// counted by scanning until `backfill_counts` runs on a writable open
+    pub fn count_mosh_contracts(&self) -> Result<usize, BoxError> {
+        match self.get_count(b"meta:contract_count")? {
+            Some(count) => Ok(count as usize),
+            None => self.scan_mosh_contracts(),
+        }
+    }
+
+    fn scan_mosh_contracts(&self) -> Result<usize, BoxError> {
+        Ok(self.db_scan(CONTRACT_KEY_PREFIX.as_bytes())?.len())
+    }
+
     pub fn get_mosh_contracts_by_creator(&self, creator: &str) -> Result<Vec<crate::mvm::MoshContract>, BoxError> {
         let mut contracts = Vec::new();
         let prefix = format!("mosh_by_creator:{}:", creator);
         
-        let iter = self.db.prefix_iterator(prefix.as_bytes());
-        for item in iter {
-            let (key, _) = item?;
+        for (key, _) in self.db_scan(prefix.as_bytes())? {
             let key_str = String::from_utf8(key.to_vec())?;
             if let Some(addr) = key_str.strip_prefix(&prefix) {
                 if let Some(contract) = self.get_mosh_contract(addr)? {
@@ -260,13 +1078,13 @@ impl State {
 
     pub fn set_mosh_var(&mut self, contract: &str, var: &str, value: &str) -> Result<(), BoxError> {
         let key = format!("mosh_var:{}:{}", contract, var);
-        self.db.put(key.as_bytes(), value.as_bytes())?;
+        self.db_put(key.as_bytes(), value.as_bytes())?;
         Ok(())
     }
 
     pub fn get_mosh_var(&self, contract: &str, var: &str) -> Result<Option<String>, BoxError> {
         let key = format!("mosh_var:{}:{}", contract, var);
-        if let Some(bytes) = self.db.get(key.as_bytes())? {
+        if let Some(bytes) = self.db_get(key.as_bytes())? {
             Ok(Some(String::from_utf8(bytes.to_vec())?))
         } else {
             Ok(None)
@@ -277,26 +1095,48 @@ impl State {
 
     pub fn set_mosh_map(&mut self, contract: &str, map: &str, key: &str, value: &str) -> Result<(), BoxError> {
         let db_key = format!("mosh_map:{}:{}:{}", contract, map, key);
-        self.db.put(db_key.as_bytes(), value.as_bytes())?;
+        self.db_put(db_key.as_bytes(), value.as_bytes())?;
         Ok(())
     }
 
     pub fn get_mosh_map(&self, contract: &str, map: &str, key: &str) -> Result<Option<String>, BoxError> {
         let db_key = format!("mosh_map:{}:{}:{}", contract, map, key);
-        if let Some(bytes) = self.db.get(db_key.as_bytes())? {
+        if let Some(bytes) = self.db_get(db_key.as_bytes())? {
+            Ok(Some(String::from_utf8(bytes.to_vec())?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn delete_mosh_map(&mut self, contract: &str, map: &str, key: &str) -> Result<(), BoxError> {
+        self.db_delete(format!("mosh_map:{}:{}:{}", contract, map, key))
+    }
+
+    /// Nested mapping entry, stored under the composite key `key1:key2`
+    pub fn set_mosh_map2(&mut self, contract: &str, map: &str, key1: &str, key2: &str, value: &str) -> Result<(), BoxError> {
+        let db_key = format!("mosh_map:{}:{}:{}:{}", contract, map, key1, key2);
+        self.db_put(db_key.as_bytes(), value.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn get_mosh_map2(&self, contract: &str, map: &str, key1: &str, key2: &str) -> Result<Option<String>, BoxError> {
+        let db_key = format!("mosh_map:{}:{}:{}:{}", contract, map, key1, key2);
+        if let Some(bytes) = self.db_get(db_key.as_bytes())? {
             Ok(Some(String::from_utf8(bytes.to_vec())?))
         } else {
             Ok(None)
         }
     }
 
+    pub fn delete_mosh_map2(&mut self, contract: &str, map: &str, key1: &str, key2: &str) -> Result<(), BoxError> {
+        self.db_delete(format!("mosh_map:{}:{}:{}:{}", contract, map, key1, key2))
+    }
+
     pub fn get_all_mosh_map_entries(&self, contract: &str, map: &str) -> Result<Vec<(String, String)>, BoxError> {
         let mut entries = Vec::new();
         let prefix = format!("mosh_map:{}:{}:", contract, map);
         
-        let iter = self.db.prefix_iterator(prefix.as_bytes());
-        for item in iter {
-            let (key, value) = item?;
+        for (key, value) in self.db_scan(prefix.as_bytes())? {
             let key_str = String::from_utf8(key.to_vec())?;
             if let Some(map_key) = key_str.strip_prefix(&prefix) {
                 let val = String::from_utf8(value.to_vec())?;
@@ -314,9 +1154,7 @@ impl State {
         // Find next index for this contract+height
         let prefix = format!("event:{}:{}:", event.contract, event.block_height);
         let mut idx = 0u64;
-        let iter = self.db.prefix_iterator(prefix.as_bytes());
-        for item in iter {
-            let (key, _) = item?;
+        for (key, _) in self.db_scan(prefix.as_bytes())? {
             let key_str = String::from_utf8(key.to_vec())?;
             if key_str.starts_with(&prefix) {
                 idx += 1;
@@ -327,17 +1165,31 @@ impl State {
 
         let key = format!("event:{}:{}:{}", event.contract, event.block_height, idx);
         let value = serde_json::to_string(event)?;
-        self.db.put(key.as_bytes(), value.as_bytes())?;
+        self.db_put(key.as_bytes(), value.as_bytes())?;
+        Ok(())
+    }
+
+    /// Record the events a transaction emitted, for its receipt
+    pub fn save_tx_events(&mut self, tx_hash: &str, events: &[crate::mvm::ContractEvent]) -> Result<(), BoxError> {
+        let key = format!("tx_events:{}", tx_hash);
+        let value = serde_json::to_string(events)?;
+        self.db_put(key.as_bytes(), value.as_bytes())?;
         Ok(())
     }
 
+    pub fn get_tx_events(&self, tx_hash: &str) -> Result<Vec<crate::mvm::ContractEvent>, BoxError> {
+        let key = format!("tx_events:{}", tx_hash);
+        match self.db_get(key.as_bytes())? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(vec![]),
+        }
+    }
+
     pub fn get_contract_events(&self, contract: &str) -> Result<Vec<crate::mvm::ContractEvent>, BoxError> {
         let mut events = Vec::new();
         let prefix = format!("event:{}:", contract);
 
-        let iter = self.db.prefix_iterator(prefix.as_bytes());
-        for item in iter {
-            let (key, value) = item?;
+        for (key, value) in self.db_scan(prefix.as_bytes())? {
             let key_str = String::from_utf8(key.to_vec())?;
             if key_str.starts_with(&prefix) {
                 let event: crate::mvm::ContractEvent = serde_json::from_slice(&value)?;
@@ -357,9 +1209,7 @@ impl State {
         // Top balances
         let mut balances: Vec<(String, u64)> = Vec::new();
         let prefix = b"balance:";
-        let iter = self.db.prefix_iterator(prefix);
-        for item in iter {
-            let (key, value) = item?;
+        for (key, value) in self.db_scan(prefix)? {
             let key_str = String::from_utf8(key.to_vec())?;
             if let Some(addr) = key_str.strip_prefix("balance:") {
                 if let Ok(bytes) = value.as_ref().try_into() {
@@ -396,9 +1246,7 @@ impl State {
         // Top transaction senders (by nonce as proxy for tx count)
         let mut tx_counts: Vec<(String, u64)> = Vec::new();
         let nonce_prefix = b"nonce:";
-        let iter = self.db.prefix_iterator(nonce_prefix);
-        for item in iter {
-            let (key, value) = item?;
+        for (key, value) in self.db_scan(nonce_prefix)? {
             let key_str = String::from_utf8(key.to_vec())?;
             if let Some(addr) = key_str.strip_prefix("nonce:") {
                 if let Ok(bytes) = value.as_ref().try_into() {
@@ -423,18 +1271,21 @@ impl State {
     // Token operations (MVM-20)
     pub fn save_token(&mut self, token: &MVM20Token) -> Result<(), BoxError> {
         let key = format!("token:{}", token.address);
+        if Self::is_token_key(key.as_bytes()) && self.db_get(key.as_bytes())?.is_none() {
+            self.bump_count(b"meta:token_count")?;
+        }
         let value = serde_json::to_string(token)?;
-        self.db.put(key.as_bytes(), value.as_bytes())?;
+        self.db_put(key.as_bytes(), value.as_bytes())?;
         
         let list_key = format!("token_list:{}", token.address);
-        self.db.put(list_key.as_bytes(), b"1")?;
+        self.db_put(list_key.as_bytes(), b"1")?;
         
         Ok(())
     }
 
     pub fn get_token(&self, address: &str) -> Result<Option<MVM20Token>, BoxError> {
         let key = format!("token:{}", address);
-        if let Some(bytes) = self.db.get(key.as_bytes())? {
+        if let Some(bytes) = self.db_get(key.as_bytes())? {
             let token: MVM20Token = serde_json::from_slice(&bytes)?;
             Ok(Some(token))
         } else {
@@ -444,30 +1295,69 @@ impl State {
 
     pub fn get_all_tokens(&self) -> Result<Vec<MVM20Token>, BoxError> {
         let mut tokens = Vec::new();
-        let prefix = b"token:";
-        
-        let iter = self.db.prefix_iterator(prefix);
-        for item in iter {
-            let (key, value) = item?;
-            let key_str = String::from_utf8(key.to_vec())?;
-            if key_str.starts_with("token:") && !key_str.contains("_") && !key_str.contains("list") {
+        for (key, value) in self.db_scan(b"token:")? {
+            if Self::is_token_key(&key) {
                 let token: MVM20Token = serde_json::from_slice(&value)?;
                 tokens.push(token);
             }
         }
-        
         Ok(tokens)
     }
 
+    /// `token:` followed by a single `mvm1token` address. The `token_list:` and
+    /// `token_balance:` keys sit under their own prefixes and are never scanned here.
+    fn is_token_key(key: &[u8]) -> bool {
+        key.strip_prefix(b"token:")
+            .is_some_and(|address| address.starts_with(b"mvm1token") && !address.contains(&b':'))
+    }
+
+    /// One page of tokens in address order, after the `after` address
+    /// when given, otherwise skipping the first `offset`
+    pub fn get_tokens_page(&self, after: Option<&str>, offset: usize, limit: usize) -> Result<Vec<MVM20Token>, BoxError> {
+        let after_key = after.map(|a| format!("token:{}", a));
+        let skip = if after.is_some() { 0 } else { offset };
+        let page = self.db_scan_page(b"token:", after_key.as_deref().map(str::as_bytes), skip + limit, Self::is_token_key)?;
+        page.into_iter().skip(skip)
+            .map(|(_, value)| Ok(serde_json::from_slice(&value)?))
+            .collect()
+    }
+
+    /// Kept by `save_token`, with the same scan fallback as `count_mosh_contracts`
+    pub fn count_tokens(&self) -> Result<usize, BoxError> {
+        match self.get_count(b"meta:token_count")? {
+            Some(count) => Ok(count as usize),
+            None => self.scan_tokens(),
+        }
+    }
+
+    fn scan_tokens(&self) -> Result<usize, BoxError> {
+        Ok(self.db_scan(b"token:")?.iter().filter(|(key, _)| Self::is_token_key(key)).count())
+    }
+
+    fn get_count(&self, key: &[u8]) -> Result<Option<u64>, BoxError> {
+        match self.db_get(key)? {
+            Some(bytes) => Ok(Some(u64::from_le_bytes(
+                bytes.as_slice().try_into().map_err(|_| BoxError::from("Invalid counter bytes"))?
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    /// Add one to a counter (started by `backfill_counts` on open)
+    fn bump_count(&mut self, key: &[u8]) -> Result<(), BoxError> {
+        let count = self.get_count(key)?.unwrap_or(0);
+        self.db_put(key, (count + 1).to_le_bytes())
+    }
+
     pub fn set_token_balance(&mut self, contract: &str, address: &str, balance: u64) -> Result<(), BoxError> {
         let key = format!("token_balance:{}:{}", contract, address);
-        self.db.put(key.as_bytes(), balance.to_le_bytes())?;
+        self.db_put(key.as_bytes(), balance.to_le_bytes())?;
         Ok(())
     }
 
     pub fn get_token_balance(&self, contract: &str, address: &str) -> Result<u64, BoxError> {
         let key = format!("token_balance:{}:{}", contract, address);
-        if let Some(bytes) = self.db.get(key.as_bytes())? {
+        if let Some(bytes) = self.db_get(key.as_bytes())? {
             Ok(u64::from_le_bytes(
                 bytes.as_slice().try_into()
                     .map_err(|_| BoxError::from("Invalid token balance bytes"))?
@@ -477,13 +1367,24 @@ impl State {
         }
     }
 
+    /// Base units `owner` lets `spender` move out of its `contract` balance (0 if none)
+    pub fn get_token_allowance(&self, contract: &str, owner: &str, spender: &str) -> Result<u64, BoxError> {
+        let key = format!("token_allowance:{}:{}:{}", contract, owner, spender);
+        if let Some(bytes) = self.db_get(key.as_bytes())? {
+            Ok(u64::from_le_bytes(
+                bytes.as_slice().try_into()
+                    .map_err(|_| BoxError::from("Invalid token allowance bytes"))?
+            ))
+        } else {
+            Ok(0)
+        }
+    }
+
     pub fn get_token_holders(&self, contract: &str) -> Result<Vec<(String, u64)>, BoxError> {
         let mut holders = Vec::new();
         let prefix = format!("token_balance:{}:", contract);
 
-        let iter = self.db.prefix_iterator(prefix.as_bytes());
-        for item in iter {
-            let (key, value) = item?;
+        for (key, value) in self.db_scan(prefix.as_bytes())? {
             let key_str = String::from_utf8(key.to_vec())?;
             if let Some(address) = key_str.strip_prefix(&prefix) {
                 let balance = u64::from_le_bytes(
@@ -499,10 +1400,30 @@ impl State {
         Ok(holders)
     }
 
+    // Name registry (see `crate::names`)
+    pub fn resolve_name(&self, name: &str) -> Result<Option<String>, BoxError> {
+        let key = format!("name:{}", name);
+        Ok(self.db_get(key.as_bytes())?.map(String::from_utf8).transpose()?)
+    }
+
+    pub fn set_name(&mut self, name: &str, address: &str) -> Result<(), BoxError> {
+        self.db_put(format!("name:{}", name), address)
+    }
+
+    /// The name `address` resolves back to, if it has registered one
+    pub fn get_primary_name(&self, address: &str) -> Result<Option<String>, BoxError> {
+        let key = format!("name_of:{}", address);
+        Ok(self.db_get(key.as_bytes())?.map(String::from_utf8).transpose()?)
+    }
+
+    pub fn set_primary_name(&mut self, address: &str, name: &str) -> Result<(), BoxError> {
+        self.db_put(format!("name_of:{}", address), name)
+    }
+
     // Faucet operations
     pub fn get_faucet_claim(&self, address: &str) -> Result<Option<i64>, BoxError> {
         let key = format!("faucet:{}", address);
-        if let Some(bytes) = self.db.get(key.as_bytes())? {
+        if let Some(bytes) = self.db_get(key.as_bytes())? {
             Ok(Some(i64::from_le_bytes(
                 bytes.as_slice().try_into()
                     .map_err(|_| BoxError::from("Invalid faucet timestamp"))?
@@ -514,14 +1435,24 @@ impl State {
 
     pub fn set_faucet_claim(&mut self, address: &str, timestamp: i64) -> Result<(), BoxError> {
         let key = format!("faucet:{}", address);
-        self.db.put(key.as_bytes(), timestamp.to_le_bytes())?;
+        self.db_put(key.as_bytes(), timestamp.to_le_bytes())?;
         Ok(())
     }
 
+    /// Forget `address`'s last faucet claim so its cooldown no longer applies.
+    /// Returns whether there was one.
+    pub fn clear_faucet_claim(&mut self, address: &str) -> Result<bool, BoxError> {
+        let existed = self.get_faucet_claim(address)?.is_some();
+        if existed {
+            self.db_delete(format!("faucet:{}", address))?;
+        }
+        Ok(existed)
+    }
+
     // Transaction operations
     pub fn get_transaction(&self, hash: &str) -> Result<Option<crate::chain::Transaction>, BoxError> {
         let key = format!("tx:{}", hash);
-        if let Some(bytes) = self.db.get(key.as_bytes())? {
+        if let Some(bytes) = self.db_get(key.as_bytes())? {
             let tx: crate::chain::Transaction = serde_json::from_slice(&bytes)?;
             Ok(Some(tx))
         } else {
@@ -532,50 +1463,29 @@ impl State {
     pub fn save_transaction(&mut self, tx: &crate::chain::Transaction) -> Result<(), BoxError> {
         let key = format!("tx:{}", tx.hash);
         let value = serde_json::to_string(tx)?;
-        self.db.put(key.as_bytes(), value.as_bytes())?;
+        self.db_put(key.as_bytes(), value.as_bytes())?;
         Ok(())
     }
 
     pub fn index_transaction(&mut self, tx: &crate::chain::Transaction, block_height: u64) -> Result<(), BoxError> {
-        // Index by sender
-        let from_key = format!("tx_by_addr:{}:{}", tx.from, tx.hash);
-        self.db.put(from_key.as_bytes(), block_height.to_le_bytes())?;
-
-        // Index by recipient if exists
-        if let Some(ref to) = tx.to {
-            let to_key = format!("tx_by_addr:{}:{}", to, tx.hash);
-            self.db.put(to_key.as_bytes(), block_height.to_le_bytes())?;
-        }
-
-        // Index by contract/token address from tx data
-        if let Some(ref data) = tx.data {
-            let contract_addr = match data {
-                crate::chain::TxData::TransferToken { contract, to, .. } => {
-                    // Index by token contract AND by token recipient
-                    let to_key = format!("tx_by_addr:{}:{}", to, tx.hash);
-                    self.db.put(to_key.as_bytes(), block_height.to_le_bytes())?;
-                    Some(contract.as_str())
-                }
-                crate::chain::TxData::CallContract { contract, .. } => Some(contract.as_str()),
-                crate::chain::TxData::Call { contract, .. } => Some(contract.as_str()),
-                _ => None,
-            };
-            if let Some(addr) = contract_addr {
-                let contract_key = format!("tx_by_addr:{}:{}", addr, tx.hash);
-                self.db.put(contract_key.as_bytes(), block_height.to_le_bytes())?;
+        self.write_atomically(|state| {
+            // Index by sender, recipient and any contract/token the tx touches
+            for address in tx_index_addresses(tx) {
+                let key = format!("tx_by_addr:{}:{}", address, tx.hash);
+                state.db_put(key.as_bytes(), block_height.to_le_bytes())?;
             }
-        }
 
-        // Index tx hash → block height
-        let block_key = format!("tx_block:{}", tx.hash);
-        self.db.put(block_key.as_bytes(), block_height.to_le_bytes())?;
+            // Index tx hash → block height
+            let block_key = format!("tx_block:{}", tx.hash);
+            state.db_put(block_key.as_bytes(), block_height.to_le_bytes())?;
 
-        Ok(())
+            Ok(())
+        })
     }
 
     pub fn get_transaction_block_height(&self, tx_hash: &str) -> Result<Option<u64>, BoxError> {
         let key = format!("tx_block:{}", tx_hash);
-        match self.db.get(key.as_bytes())? {
+        match self.db_get(key.as_bytes())? {
             Some(bytes) => {
                 let slice: &[u8] = &bytes;
                 let arr: [u8; 8] = slice.try_into().unwrap_or([0u8; 8]);
@@ -585,13 +1495,55 @@ impl State {
         }
     }
 
+    /// Hashes of the transactions in block `height`, in block order
+    pub fn get_block_tx_hashes(&self, height: u64) -> Result<Vec<String>, BoxError> {
+        let mut hashes = Vec::new();
+        // The index is not zero-padded, so walk it by position rather than scanning
+        while let Some(hash) = self.db_get(format!("tx_by_block:{}:{}", height, hashes.len()))? {
+            hashes.push(String::from_utf8(hash)?);
+        }
+        Ok(hashes)
+    }
+
+    /// Next sequence number in the global tx index (the number of transactions
+    /// ever indexed)
+    pub fn get_tx_seq(&self) -> Result<u64, BoxError> {
+        match self.db_get(b"meta:tx_seq")? {
+            Some(bytes) => Ok(u64::from_le_bytes(
+                bytes.as_slice().try_into()
+                    .map_err(|_| BoxError::from("Invalid tx sequence bytes"))?
+            )),
+            None => Ok(0),
+        }
+    }
+
+    /// Up to `limit` confirmed transactions with their sequence numbers, newest
+    /// first, starting just below `before_seq` (or at the newest). Reads only
+    /// the entries it returns, however long the chain.
+    pub fn get_recent_transactions(
+        &self,
+        limit: usize,
+        before_seq: Option<u64>,
+    ) -> Result<Vec<(u64, crate::chain::Transaction)>, BoxError> {
+        let before = before_seq.map(|seq| format!("tx_seq:{:020}", seq));
+        let mut txs = Vec::new();
+        for (key, hash) in self.db_scan_back(b"tx_seq:", before.as_deref().map(str::as_bytes), limit)? {
+            let seq = std::str::from_utf8(&key)?
+                .strip_prefix("tx_seq:")
+                .and_then(|s| s.parse::<u64>().ok())
+                .ok_or_else(|| BoxError::from("Invalid tx sequence key"))?;
+            if let Some(tx) = self.get_transaction(std::str::from_utf8(&hash)?)? {
+                txs.push((seq, tx));
+            }
+        }
+        Ok(txs)
+    }
+
     pub fn get_transactions_by_address(&self, address: &str, limit: usize) -> Result<Vec<crate::chain::Transaction>, BoxError> {
         let mut txs = Vec::new();
         let prefix = format!("tx_by_addr:{}:", address);
         
-        let iter = self.db.prefix_iterator(prefix.as_bytes());
-        for item in iter.take(limit) {
-            let (key, _) = item?;
+        for (key, _) in self.db_scan(prefix.as_bytes())?.into_iter().take(limit) {
             let key_str = String::from_utf8(key.to_vec())?;
             
             // Extract tx hash from key
@@ -625,9 +1577,7 @@ impl State {
         let mut holdings = Vec::new();
         let prefix = b"token_balance:";
         
-        let iter = self.db.prefix_iterator(prefix);
-        for item in iter {
-            let (key, value) = item?;
+        for (key, value) in self.db_scan(prefix)? {
             let key_str = String::from_utf8(key.to_vec())?;
             
             // Key format: token_balance:CONTRACT:ADDRESS
@@ -660,15 +1610,15 @@ impl State {
     }
 
     // State snapshot for sync
-    pub fn get_state_snapshot(&self) -> Result<StateSnapshot, BoxError> {
+    /// Snapshot for syncing a follower. Light snapshots carry only balances and
+    /// recent blocks; `full` adds nonces, tokens and Mosh contract state.
+    pub fn get_state_snapshot(&self, full: bool) -> Result<StateSnapshot, BoxError> {
         let height = self.get_height()?;
         let total_supply = self.get_total_supply()?;
         
         let mut balances = std::collections::HashMap::new();
         let prefix = b"balance:";
-        let iter = self.db.prefix_iterator(prefix);
-        for item in iter {
-            let (key, value) = item?;
+        for (key, value) in self.db_scan(prefix)? {
             let key_str = String::from_utf8(key.to_vec())?;
             if let Some(address) = key_str.strip_prefix("balance:") {
                 let balance = u64::from_le_bytes(
@@ -680,28 +1630,207 @@ impl State {
         }
 
         let mut recent_blocks = Vec::new();
-        let start = if height > 10 { height - 10 } else { 0 };
+        let start = height.saturating_sub(SNAPSHOT_BLOCKS);
         for h in start..=height {
             if let Some(block) = self.get_block(h)? {
                 recent_blocks.push(block);
             }
         }
 
-        Ok(StateSnapshot {
+        let mut snapshot = StateSnapshot {
             height,
             total_supply,
             balances,
             recent_blocks,
-        })
+            full,
+            nonces: HashMap::new(),
+            tokens: vec![],
+            token_balances: HashMap::new(),
+            mosh_contracts: vec![],
+            mosh_vars: HashMap::new(),
+            mosh_maps: HashMap::new(),
+            names: HashMap::new(),
+            primary_names: HashMap::new(),
+        };
+        if !full {
+            return Ok(snapshot);
+        }
+
+        for (key, value) in self.db_scan(b"nonce:")? {
+            let key_str = String::from_utf8(key.to_vec())?;
+            if let Some(address) = key_str.strip_prefix("nonce:") {
+                let nonce = u64::from_le_bytes(
+                    value.as_ref().try_into()
+                        .map_err(|_| BoxError::from("Invalid nonce bytes"))?
+                );
+                snapshot.nonces.insert(address.to_string(), nonce);
+            }
+        }
+
+        snapshot.tokens = self.get_all_tokens()?;
+        // Key format: token_balance:CONTRACT:ADDRESS
+        for (key, value) in self.db_scan(b"token_balance:")? {
+            let key_str = String::from_utf8(key.to_vec())?;
+            if let Some((contract, address)) = key_str.strip_prefix("token_balance:").and_then(|r| r.split_once(':')) {
+                let balance = u64::from_le_bytes(
+                    value.as_ref().try_into()
+                        .map_err(|_| BoxError::from("Invalid token balance bytes"))?
+                );
+                snapshot.token_balances.entry(contract.to_string()).or_default()
+                    .insert(address.to_string(), balance);
+            }
+        }
+
+        snapshot.mosh_contracts = self.get_all_mosh_contracts()?;
+        // Key format: mosh_var:CONTRACT:VAR
+        for (key, value) in self.db_scan(b"mosh_var:")? {
+            let key_str = String::from_utf8(key.to_vec())?;
+            if let Some((contract, var)) = key_str.strip_prefix("mosh_var:").and_then(|r| r.split_once(':')) {
+                snapshot.mosh_vars.entry(contract.to_string()).or_default()
+                    .insert(var.to_string(), String::from_utf8(value.to_vec())?);
+            }
+        }
+        // Key format: mosh_map:CONTRACT:MAP:KEY
+        for (key, value) in self.db_scan(b"mosh_map:")? {
+            let key_str = String::from_utf8(key.to_vec())?;
+            let mut parts = key_str.strip_prefix("mosh_map:").unwrap_or_default().splitn(3, ':');
+            if let (Some(contract), Some(map), Some(entry)) = (parts.next(), parts.next(), parts.next()) {
+                snapshot.mosh_maps.entry(contract.to_string()).or_default()
+                    .entry(map.to_string()).or_default()
+                    .insert(entry.to_string(), String::from_utf8(value.to_vec())?);
+            }
+        }
+
+        for (key, value) in self.db_scan(b"name:")? {
+            if let Some(name) = key.strip_prefix(b"name:") {
+                snapshot.names.insert(String::from_utf8(name.to_vec())?, String::from_utf8(value.to_vec())?);
+            }
+        }
+        for (key, value) in self.db_scan(b"name_of:")? {
+            if let Some(address) = key.strip_prefix(b"name_of:") {
+                snapshot.primary_names.insert(String::from_utf8(address.to_vec())?, String::from_utf8(value.to_vec())?);
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Load a snapshot received from the master node.
+    ///
+    /// The chain segment is validated before anything is written. Every write is
+    /// keyed, and height is written last, so re-applying after a partial write is safe.
+    pub fn apply_snapshot(&mut self, snap: &StateSnapshot) -> Result<(), BoxError> {
+        for block in &snap.recent_blocks {
+            if !block.is_valid() {
+                return Err(format!("Snapshot block #{} has an invalid hash", block.height).into());
+            }
+        }
+        for pair in snap.recent_blocks.windows(2) {
+            if pair[1].height != pair[0].height + 1 || pair[1].prev_hash != pair[0].hash {
+                return Err(format!("Snapshot blocks not contiguous at #{}", pair[1].height).into());
+            }
+        }
+        if let Some(tip) = snap.recent_blocks.last() {
+            if tip.height != snap.height {
+                return Err(format!("Snapshot tip #{} does not match height #{}", tip.height, snap.height).into());
+            }
+        }
+
+        for (address, balance) in &snap.balances {
+            self.set_balance(address, *balance)?;
+        }
+        for block in &snap.recent_blocks {
+            self.save_block(block)?;
+            for tx in &block.transactions {
+                self.index_transaction(tx, block.height)?;
+            }
+        }
+        if snap.full {
+            for (address, nonce) in &snap.nonces {
+                self.set_nonce(address, *nonce)?;
+            }
+            for token in &snap.tokens {
+                self.save_token(token)?;
+            }
+            for (contract, holders) in &snap.token_balances {
+                for (address, balance) in holders {
+                    self.set_token_balance(contract, address, *balance)?;
+                }
+            }
+            for contract in &snap.mosh_contracts {
+                self.save_mosh_contract(contract)?;
+            }
+            for (contract, vars) in &snap.mosh_vars {
+                for (var, value) in vars {
+                    self.set_mosh_var(contract, var, value)?;
+                }
+            }
+            for (contract, maps) in &snap.mosh_maps {
+                for (map, entries) in maps {
+                    for (key, value) in entries {
+                        self.set_mosh_map(contract, map, key, value)?;
+                    }
+                }
+            }
+            for (name, address) in &snap.names {
+                self.set_name(name, address)?;
+            }
+            for (address, name) in &snap.primary_names {
+                self.set_primary_name(address, name)?;
+            }
+        }
+        self.set_total_supply(snap.total_supply)?;
+        self.set_height(snap.height)?;
+        Ok(())
     }
 }
 
+/// One key rewritten while staging, see `State::staged_changes`
+#[derive(Debug, Clone)]
+pub struct StagedChange {
+    pub key: String,
+    pub before: Option<Vec<u8>>,
+    pub after: Option<Vec<u8>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StateSnapshot {
     pub height: u64,
     pub total_supply: u64,
     pub balances: std::collections::HashMap<String, u64>,
     pub recent_blocks: Vec<Block>,
+    /// Whether the fields below were populated
+    #[serde(default)]
+    pub full: bool,
+    #[serde(default)]
+    pub nonces: HashMap<String, u64>,
+    #[serde(default)]
+    pub tokens: Vec<MVM20Token>,
+    /// contract -> holder -> balance
+    #[serde(default)]
+    pub token_balances: HashMap<String, HashMap<String, u64>>,
+    #[serde(default)]
+    pub mosh_contracts: Vec<crate::mvm::MoshContract>,
+    /// contract -> variable -> value
+    #[serde(default)]
+    pub mosh_vars: HashMap<String, HashMap<String, String>>,
+    /// contract -> mapping -> key -> value
+    #[serde(default)]
+    pub mosh_maps: HashMap<String, HashMap<String, HashMap<String, String>>>,
+    /// Registered name -> owner
+    #[serde(default)]
+    pub names: HashMap<String, String>,
+    /// Address -> primary name
+    #[serde(default)]
+    pub primary_names: HashMap<String, String>,
+}
+
+/// Published source for a contract, checked against its on-chain schema
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifiedSource {
+    pub source: String,
+    pub schema_hash: String,
+    pub verified_at: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -711,4 +1840,334 @@ pub struct TokenHolding {
     pub symbol: String,
     pub balance: u64,
     pub decimals: u8,
-}
\ No newline at end of file
+}
+
+/// Scratch directory for a test's database, removed when dropped
+#[cfg(test)]
+pub(crate) struct TestDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A fresh `State` in its own temp directory, named after the test and the
+/// process so parallel test runs don't share one. Keep the `TestDir` alive
+/// for as long as the state is used.
+#[cfg(test)]
+pub(crate) fn test_state(name: &str) -> (State, TestDir) {
+    let dir = std::env::temp_dir().join(format!("mvm-{}-{}", name, std::process::id()));
+    let state = State::new(dir.to_str().unwrap()).unwrap();
+    (state, TestDir(dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::standards::create_mvm20_token;
+
+    #[test]
+    fn test_get_all_tokens_returns_only_tokens() {
+        let (mut state, _dir) = test_state("tokens");
+
+        let gold = create_mvm20_token(&mut state, "mvm1alice", "Gold", "GLD", 1_000, 8, 0, 1).unwrap();
+        let silver = create_mvm20_token(&mut state, "mvm1bob", "Silver", "SLV", 500, 2, 0, 1).unwrap();
+        state.set_token_balance(&gold, "mvm1bob", 42).unwrap();
+        state.set_token_balance(&silver, "mvm1alice", 7).unwrap();
+
+        // An underscore in the address used to hide a token
+        let legacy = MVM20Token {
+            address: "mvm1token_legacy".to_string(),
+            name: "Legacy".to_string(),
+            symbol: "LGC".to_string(),
+            decimals: 0,
+            total_supply: 1,
+            creator: "mvm1alice".to_string(),
+            created_at: 0,
+            created_block: 0,
+            logo_uri: None,
+            description: None,
+        };
+        state.save_token(&legacy).unwrap();
+
+        let mut addresses: Vec<String> = state.get_all_tokens().unwrap()
+            .into_iter().map(|t| t.address).collect();
+        addresses.sort();
+        let mut expected = vec![gold, silver, legacy.address];
+        expected.sort();
+        assert_eq!(addresses, expected);
+        assert_eq!(state.count_tokens().unwrap(), 3);
+        assert_eq!(state.get_tokens_page(None, 0, 10).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_account_cache_follows_committed_writes() {
+        let (state, _dir) = test_state("cache");
+        let mut state = state.with_account_cache(4);
+
+        state.set_balance("mvm1alice", 100).unwrap();
+        assert_eq!(state.get_balance("mvm1alice").unwrap(), 100);
+        assert_eq!(state.account_cache_stats().hits, 1);
+
+        // A discarded write must not linger in the cache
+        let result: Result<(), BoxError> = state.write_atomically(|s| {
+            s.set_balance("mvm1alice", 5)?;
+            assert_eq!(s.get_balance("mvm1alice")?, 5);
+            Err("abort".into())
+        });
+        assert!(result.is_err());
+        assert_eq!(state.get_balance("mvm1alice").unwrap(), 100);
+
+        state.write_atomically(|s| s.set_balance("mvm1alice", 7)).unwrap();
+        assert_eq!(state.get_balance("mvm1alice").unwrap(), 7);
+    }
+
+    #[test]
+    fn test_savepoints_nest_and_roll_back() {
+        let (state, _dir) = test_state("savepoint");
+        let mut state = state.with_account_cache(4);
+
+        // Outside staging, the outermost savepoint commits on release
+        state.savepoint();
+        state.set_balance("mvm1alice", 100).unwrap();
+        state.release_savepoint().unwrap();
+        assert!(state.staged_changes().unwrap().is_empty());
+        assert_eq!(state.get_balance("mvm1alice").unwrap(), 100);
+
+        state.savepoint();
+        state.set_balance("mvm1alice", 90).unwrap();
+        state.savepoint();
+        state.set_balance("mvm1alice", 50).unwrap();
+        state.set_balance("mvm1bob", 40).unwrap();
+        state.rollback_savepoint();
+        assert_eq!(state.get_balance("mvm1alice").unwrap(), 90);
+        assert_eq!(state.get_balance("mvm1bob").unwrap(), 0);
+
+        // A released inner savepoint is still undone by its parent
+        state.savepoint();
+        state.set_balance("mvm1carol", 10).unwrap();
+        state.savepoint();
+        state.set_balance("mvm1carol", 20).unwrap();
+        state.release_savepoint().unwrap();
+        state.rollback_savepoint();
+        assert_eq!(state.get_balance("mvm1carol").unwrap(), 0);
+
+        // Discarding the outermost savepoint leaves RocksDB untouched
+        state.rollback_savepoint();
+        assert_eq!(state.get_balance("mvm1alice").unwrap(), 100);
+        assert_eq!(state.record_balance_history(1).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_recent_transactions_follow_the_global_index() {
+        use crate::chain::{pending_tx, Block, BlockRewards, Transaction, TxStatus};
+
+        let (mut state, _dir) = test_state("txseq");
+        let block_with_txs = |height: u64| {
+            let txs = (0..2).map(|i| {
+                let mut tx = pending_tx("mvm1alice", height * 2 + i, 0);
+                tx.status = TxStatus::Success;
+                tx
+            }).collect();
+            let rewards = BlockRewards { validator_reward: 0, service_rewards: Vec::new(), total_minted: 0 };
+            Block::new(height, "", "mvm1alice", txs, rewards, 0)
+        };
+        let save = |state: &mut State, block: &Block| {
+            state.save_block(block).unwrap();
+            for tx in &block.transactions {
+                state.index_transaction(tx, block.height).unwrap();
+            }
+            state.set_height(block.height).unwrap();
+        };
+        for height in 1..=3 {
+            save(&mut state, &block_with_txs(height));
+        }
+
+        let seqs = |txs: Vec<(u64, Transaction)>| txs.into_iter().map(|(seq, _)| seq).collect::<Vec<_>>();
+        assert_eq!(state.get_tx_seq().unwrap(), 6);
+        assert_eq!(seqs(state.get_recent_transactions(4, None).unwrap()), vec![5, 4, 3, 2]);
+        assert_eq!(seqs(state.get_recent_transactions(4, Some(2)).unwrap()), vec![1, 0]);
+        let newest = &state.get_recent_transactions(1, None).unwrap()[0].1;
+        assert_eq!(newest.nonce, 7);
+
+        // Staged writes are visible before they commit
+        state.write_atomically(|s| {
+            save(s, &block_with_txs(4));
+            assert_eq!(seqs(s.get_recent_transactions(3, None).unwrap()), vec![7, 6, 5]);
+            Ok(())
+        }).unwrap();
+
+        // Rolled-back and pruned blocks leave the index
+        state.truncate_blocks(2).unwrap();
+        assert_eq!(seqs(state.get_recent_transactions(10, None).unwrap()), vec![3, 2, 1, 0]);
+        save(&mut state, &block_with_txs(3));
+        assert_eq!(seqs(state.get_recent_transactions(2, None).unwrap()), vec![9, 8]);
+        state.prune(SNAPSHOT_BLOCKS + 2, SNAPSHOT_BLOCKS + 1).unwrap();
+        assert_eq!(seqs(state.get_recent_transactions(10, None).unwrap()), vec![9, 8, 3, 2]);
+    }
+
+    #[test]
+    fn test_block_tx_hashes_keep_block_order() {
+        use crate::chain::{pending_tx, Block, BlockRewards, Transaction, TxStatus};
+
+        let (mut state, _dir) = test_state("block-txs");
+        // More than 10 txs, so "10" sorts before "2" in the raw index keys
+        let txs: Vec<Transaction> = (0..12).map(|nonce| {
+            let mut tx = pending_tx("mvm1alice", nonce, 0);
+            tx.status = TxStatus::Success;
+            tx
+        }).collect();
+        let expected: Vec<String> = txs.iter().map(|tx| tx.hash.clone()).collect();
+        let rewards = BlockRewards { validator_reward: 0, service_rewards: Vec::new(), total_minted: 0 };
+        state.save_block(&Block::new(1, "", "mvm1alice", txs, rewards, 0)).unwrap();
+
+        assert_eq!(state.get_block_tx_hashes(1).unwrap(), expected);
+        assert!(state.get_block_tx_hashes(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_clear_faucet_claim() {
+        let (mut state, _dir) = test_state("faucet");
+
+        state.set_faucet_claim("mvm1alice", 1_700_000_000).unwrap();
+        assert!(state.clear_faucet_claim("mvm1alice").unwrap());
+        assert_eq!(state.get_faucet_claim("mvm1alice").unwrap(), None);
+        assert!(!state.clear_faucet_claim("mvm1alice").unwrap());
+    }
+
+    #[test]
+    fn test_recompute_total_supply_sums_balances() {
+        let (mut state, _dir) = test_state("supply");
+
+        state.set_balance("mvm1alice", 700).unwrap();
+        state.set_balance("mvm1bob", 300).unwrap();
+        state.set_total_supply(1_000).unwrap();
+        assert_eq!(state.recompute_total_supply().unwrap(), 1_000);
+
+        state.set_balance("mvm1bob", 250).unwrap();
+        assert_eq!(state.recompute_total_supply().unwrap(), 950);
+        assert_eq!(state.get_total_supply().unwrap(), 1_000);
+    }
+
+    #[test]
+    fn test_balance_at_height_walks_back_from_current() {
+        let (mut state, _dir) = test_state("history");
+
+        state.set_balance("mvm1alice", 100).unwrap();
+        state.record_balance_history(1).unwrap();
+        state.set_balance("mvm1bob", 50).unwrap();
+        state.record_balance_history(2).unwrap();
+        state.set_balance("mvm1alice", 30).unwrap();
+        state.set_balance("mvm1alice", 40).unwrap();
+        state.record_balance_history(3).unwrap();
+        state.set_height(3).unwrap();
+        // Not in a block yet
+        state.set_balance("mvm1alice", 45).unwrap();
+
+        let alice: Vec<u64> = (0..=3).map(|h| state.get_balance_at("mvm1alice", h).unwrap()).collect();
+        assert_eq!(alice, vec![0, 100, 100, 40]);
+        assert_eq!(state.get_balance_at("mvm1bob", 1).unwrap(), 0);
+        assert_eq!(state.get_balance_at("mvm1bob", 3).unwrap(), 50);
+        assert_eq!(state.get_balance("mvm1alice").unwrap(), 45);
+    }
+
+    #[test]
+    fn test_balance_at_seeks_by_height_and_survives_pruning() {
+        use crate::chain::{Block, BlockRewards};
+
+        let (mut state, _dir) = test_state("history-seek");
+
+        // Heights either side of a byte boundary still sort in order
+        for (height, balance) in [(1, 10), (255, 20), (256, 30), (300, 40)] {
+            state.set_balance("mvm1alice", balance).unwrap();
+            state.record_balance_history(height).unwrap();
+        }
+        state.set_height(300).unwrap();
+        let at = |state: &State, h| state.get_balance_at("mvm1alice", h).unwrap();
+        assert_eq!([0, 1, 254, 255, 256, 299, 300].map(|h| at(&state, h)), [0, 10, 10, 20, 30, 30, 40]);
+
+        // With block 1's change pruned, the next change still knows its old value
+        let rewards = BlockRewards { validator_reward: 0, service_rewards: Vec::new(), total_minted: 0 };
+        state.save_block(&Block::new(1, "", "mvm1alice", Vec::new(), rewards, 0)).unwrap();
+        assert_eq!(state.prune(SNAPSHOT_BLOCKS + 2, 1).unwrap(), 1);
+        assert!(state.get_balance_at("mvm1alice", 0).is_err());
+        assert_eq!(at(&state, 1), 10);
+        assert_eq!(at(&state, 254), 10);
+        assert_eq!(at(&state, 300), 40);
+    }
+
+    #[test]
+    fn test_every_key_prefix_maps_to_its_column_family() {
+        let expected = [
+            ("blocks", &["block", "block_hash"][..]),
+            ("txs", &["tx", "tx_block", "tx_by_block", "tx_by_addr", "tx_events", "tx_seq"]),
+            ("accounts", &["balance", "balance_history", "balance_at", "nonce", "faucet", "name", "name_of"]),
+            ("tokens", &["token", "token_balance", "token_list"]),
+            ("contracts", &["mosh", "mosh_var", "mosh_map", "mosh_by_creator", "mosh_source", "code", "storage"]),
+            ("events", &["event"]),
+            ("meta", &["meta"]),
+        ];
+        for (cf, prefixes) in expected {
+            assert!(COLUMN_FAMILIES.contains(&cf), "{} is not opened", cf);
+            for prefix in prefixes {
+                assert_eq!(cf_for(format!("{}:key", prefix).as_bytes()), cf, "{}", prefix);
+            }
+        }
+        // Only the part before the first `:` counts
+        assert_eq!(cf_for(b"tx_seq:00000000000000000001"), "txs");
+        assert_eq!(cf_for(b"tokens:x"), "meta");
+    }
+
+    #[test]
+    fn test_token_and_contract_counts_follow_writes() {
+        let (mut state, _dir) = test_state("counts");
+        assert_eq!((state.count_tokens().unwrap(), state.count_mosh_contracts().unwrap()), (0, 0));
+
+        let gold = create_mvm20_token(&mut state, "mvm1alice", "Gold", "GLD", 1_000, 8, 0, 0).unwrap();
+        create_mvm20_token(&mut state, "mvm1alice", "Silver", "SLV", 1_000, 8, 1, 0).unwrap();
+        // Rewriting a token doesn't count it again
+        let token = state.get_token(&gold).unwrap().unwrap();
+        state.save_token(&token).unwrap();
+        assert_eq!(state.count_tokens().unwrap(), 2);
+
+        // Nor does a batch that is thrown away
+        state.begin_staging();
+        create_mvm20_token(&mut state, "mvm1alice", "Tin", "TIN", 1_000, 8, 2, 0).unwrap();
+        assert_eq!(state.count_tokens().unwrap(), 3);
+        state.discard_staging();
+        assert_eq!(state.count_tokens().unwrap(), 2);
+
+        let creator = Keypair::generate().address().to_string();
+        let address = crate::mvm::MVM::new().deploy(&mut state, &creator, "Counter", None, vec![], vec![], vec![], 0).unwrap();
+        let contract = state.get_mosh_contract(&address).unwrap().unwrap();
+        state.save_mosh_contract(&contract).unwrap();
+        assert_eq!(state.count_mosh_contracts().unwrap(), 1);
+
+        // A database from before the counters is counted by scanning
+        state.db_delete(b"meta:token_count").unwrap();
+        state.db_delete(b"meta:contract_count").unwrap();
+        assert_eq!((state.count_tokens().unwrap(), state.count_mosh_contracts().unwrap()), (2, 1));
+        state.backfill_counts().unwrap();
+        assert_eq!(state.get_count(b"meta:token_count").unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_missing_indexes_are_reported_until_backfilled() {
+        let (mut state, _dir) = test_state("missing-indexes");
+        assert!(state.missing_indexes().unwrap().is_empty());
+
+        // As written by a node from before either index
+        state.set_balance("mvm1alice", 10).unwrap();
+        state.record_balance_history(1).unwrap();
+        state.set_height(1).unwrap();
+        state.db_delete(b"meta:tx_seq").unwrap();
+        state.db_delete(b"meta:balance_at").unwrap();
+        assert_eq!(state.missing_indexes().unwrap(), vec!["tx_seq", "balance_at"]);
+
+        state.backfill_tx_seq().unwrap();
+        state.backfill_balance_at().unwrap();
+        assert!(state.missing_indexes().unwrap().is_empty());
+    }
+}